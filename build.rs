@@ -10,13 +10,23 @@ fn main() {
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=mods/");
     println!("cargo:rerun-if-changed=wit/");
+    println!("cargo:rerun-if-changed=crates/mod_sdk/");
 
+    check_wit();
     let mods = find_mod_packages();
     for mod_ in mods {
         build_mod(mod_.0, mod_.1);
     }
 }
 
+/// Mods that fail to build are only warned about, so a broken WIT would
+/// otherwise land without failing the host build.
+fn check_wit() {
+    if let Err(e) = wit_parser::Resolve::default().push_dir(Path::new("wit")) {
+        panic!("Invalid WIT in wit/: {:?}", e);
+    }
+}
+
 fn build_mod(name: String, path: String) {
     p!("Building \"{}\"", &name);
     let build_script_path = Path::new(&path).join("build.sh");
@@ -55,9 +65,40 @@ fn build_mod(name: String, path: String) {
     fs::create_dir_all(&dest).expect("Failed to create target directory");
     fs::copy(&source, dest.join(format!("{}.wasm", &name))).expect("Failed to copy wasm file");
 
+    let manifest = Path::new(&path).join("mod.toml");
+    if manifest.exists() {
+        fs::copy(&manifest, dest.join(format!("{}.toml", &name)))
+            .expect("Failed to copy mod manifest");
+    }
+
+    for dir in ["assets", "resources"] {
+        let source = Path::new(&path).join(dir);
+        if !source.is_dir() {
+            continue;
+        }
+        let dir_dest = dest.join(&name).join(dir);
+        if dir_dest.exists() {
+            fs::remove_dir_all(&dir_dest).expect("Failed to remove old files");
+        }
+        copy_dir(&source, &dir_dest);
+    }
+
     p!("Built {}", &name);
 }
 
+fn copy_dir(source: &Path, dest: &Path) {
+    fs::create_dir_all(dest).expect("Failed to create directory");
+    for entry in fs::read_dir(source).expect("Failed to read directory") {
+        let path = entry.expect("Failed to read entry").path();
+        let target = dest.join(path.file_name().unwrap());
+        if path.is_dir() {
+            copy_dir(&path, &target);
+        } else {
+            fs::copy(&path, &target).expect("Failed to copy asset");
+        }
+    }
+}
+
 fn find_mod_packages() -> Vec<(String, String)> {
     let mods_dir = env::current_dir()
         .expect("Failed to get current directory")
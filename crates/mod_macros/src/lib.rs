@@ -1,8 +1,10 @@
+mod serde_type;
+
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::{
     parse::{Parse, ParseStream},
-    parse_macro_input, LitStr, Result,
+    parse_macro_input, DeriveInput, Error, Ident, LitStr, Result, Token,
 };
 
 struct WitBindgenArgs {
@@ -17,6 +19,29 @@ impl Parse for WitBindgenArgs {
     }
 }
 
+struct ExportModArgs {
+    path: LitStr,
+    name: Option<LitStr>,
+}
+
+impl Parse for ExportModArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let path = input.parse::<LitStr>()?;
+        let mut name = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key = input.parse::<Ident>()?;
+            if key != "name" {
+                return Err(Error::new(key.span(), "Expected `name`"));
+            }
+            input.parse::<Token![=]>()?;
+            name = Some(input.parse::<LitStr>()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+
+        Ok(ExportModArgs { path, name })
+    }
+}
+
 #[proc_macro]
 pub fn create_mod(input: TokenStream) -> TokenStream {
     let args = parse_macro_input!(input as WitBindgenArgs);
@@ -25,6 +50,7 @@ pub fn create_mod(input: TokenStream) -> TokenStream {
     let expanded = quote! {
         wit_bindgen::generate!({
             path: #path_str,
+            world: "main",
             exports: {
                 "module:guest/general": General,
                 "module:guest/general/main": Main,
@@ -34,6 +60,26 @@ pub fn create_mod(input: TokenStream) -> TokenStream {
         use crate::module::guest::graphics::*;
         use crate::module::guest::utils::*;
         use crate::module::guest::input::*;
+        use crate::module::guest::bus::*;
+        use crate::module::guest::services::*;
+        use crate::module::guest::world::*;
+        use crate::module::guest::ecs::*;
+        use crate::module::guest::saves::*;
+        use crate::module::guest::fs::*;
+        use crate::module::guest::resources::*;
+        use crate::module::guest::settings::*;
+        use crate::module::guest::audio::*;
+        use crate::module::guest::time::*;
+        use crate::module::guest::random::*;
+        // Named explicitly, the interfaces re-export the types they `use`.
+        use crate::module::guest::actions::{
+            action_down, action_pressed, action_released, action_value, list_actions,
+            rebind_action, register_action, reset_action, ActionInfo, Binding, InputSource,
+        };
+        // `Event` already comes in through `general`.
+        use crate::module::guest::events::{
+            subscribe, unsubscribe, EventKind, HotplugEvent, KeyEvent, MouseEvent,
+        };
 
         pub struct General {}
 
@@ -54,3 +100,167 @@ pub fn create_mod(input: TokenStream) -> TokenStream {
 
     expanded.into()
 }
+
+/// Exports a `mod_sdk::Mod` implementation as the mod of the crate. Put it
+/// on the mod's struct at the crate root, with the path of the WIT file like
+/// `create_mod!`. The bindings are generated in the mod crate, reusing the
+/// SDK's types for everything the host provides.
+///
+/// The mod's display name is the package name unless one is given with
+/// `#[export_mod("../../wit/module.wit", name = "My Mod")]`.
+#[proc_macro_attribute]
+pub fn export_mod(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as ExportModArgs);
+    let path_str = args.path.value();
+    let name = match &args.name {
+        Some(name) => quote! { #name },
+        None => quote! { env!("CARGO_PKG_NAME") },
+    };
+    let input = parse_macro_input!(item as DeriveInput);
+    if !input.generics.params.is_empty() {
+        return Error::new_spanned(&input.generics, "Exported mods can't be generic")
+            .to_compile_error()
+            .into();
+    }
+    let ident = &input.ident;
+
+    let expanded = quote! {
+        #input
+
+        wit_bindgen::generate!({
+            path: #path_str,
+            world: "main",
+            exports: {
+                "module:guest/general": SdkGeneral,
+                "module:guest/general/main": SdkMain,
+            },
+            with: {
+                "module:guest/utils": ::mod_sdk::bindings::module::guest::utils,
+                "module:guest/graphics": ::mod_sdk::bindings::module::guest::graphics,
+                "module:guest/input": ::mod_sdk::bindings::module::guest::input,
+                "module:guest/actions": ::mod_sdk::bindings::module::guest::actions,
+                "module:guest/bus": ::mod_sdk::bindings::module::guest::bus,
+                "module:guest/services": ::mod_sdk::bindings::module::guest::services,
+                "module:guest/world": ::mod_sdk::bindings::module::guest::world,
+                "module:guest/ecs": ::mod_sdk::bindings::module::guest::ecs,
+                "module:guest/saves": ::mod_sdk::bindings::module::guest::saves,
+                "module:guest/fs": ::mod_sdk::bindings::module::guest::fs,
+                "module:guest/resources": ::mod_sdk::bindings::module::guest::resources,
+                "module:guest/settings": ::mod_sdk::bindings::module::guest::settings,
+                "module:guest/audio": ::mod_sdk::bindings::module::guest::audio,
+                "module:guest/time": ::mod_sdk::bindings::module::guest::time,
+                "module:guest/random": ::mod_sdk::bindings::module::guest::random,
+                "module:guest/events": ::mod_sdk::bindings::module::guest::events,
+            },
+        });
+
+        #[doc(hidden)]
+        pub struct SdkGeneral;
+
+        impl crate::exports::module::guest::general::Guest for SdkGeneral {
+            fn info() -> Vec<String> {
+                ::mod_sdk::__private::info(
+                    env!("CARGO_PKG_NAME"),
+                    #name,
+                    env!("CARGO_PKG_VERSION"),
+                    env!("CARGO_PKG_AUTHORS"),
+                    env!("CARGO_PKG_DESCRIPTION"),
+                )
+            }
+        }
+
+        #[doc(hidden)]
+        pub struct SdkMain(::mod_sdk::__private::Instance<#ident>);
+
+        impl crate::exports::module::guest::general::GuestMain for SdkMain {
+            fn new() -> Self {
+                SdkMain(::mod_sdk::__private::Instance::new())
+            }
+
+            fn init(&self) {
+                self.0.init()
+            }
+
+            fn update(&self, delta: f32) {
+                self.0.update(delta)
+            }
+
+            fn draw(&self) {
+                self.0.draw()
+            }
+
+            fn on_event(&self, event: ::mod_sdk::events::Event) -> bool {
+                self.0.on_event(event)
+            }
+
+            fn handle_call(
+                &self,
+                service: String,
+                method: String,
+                request: Vec<u8>,
+            ) -> ::std::result::Result<Vec<u8>, String> {
+                self.0.handle_call(service, method, request)
+            }
+
+            fn run_system(&self, name: String) {
+                self.0.run_system(name)
+            }
+
+            fn save_section(&self) -> Option<Vec<u8>> {
+                self.0.save_section()
+            }
+
+            fn load_section(&self, data: Vec<u8>) {
+                self.0.load_section(data)
+            }
+
+            fn migrate_section(
+                &self,
+                from_version: String,
+                data: Vec<u8>,
+            ) -> ::std::result::Result<Vec<u8>, String> {
+                self.0.migrate_section(from_version, data)
+            }
+
+            fn shutdown(&self) {
+                self.0.shutdown()
+            }
+        }
+    };
+
+    expanded.into()
+}
+
+/// Implements `types::SerdeType` for a type that implements serde's
+/// `Serialize` and `Deserialize`. Structs, tuple structs, enums and generic
+/// types all work.
+///
+/// With `#[serde_type(wit)]` the type also gets `SerdeType::WIT`, the
+/// matching WIT definition: a `record` for structs, a `type` alias for tuple
+/// structs, an `enum` for enums without fields and a `variant` otherwise.
+/// Names are converted to kebab-case and types that aren't built in are
+/// referred to by their kebab-case name.
+#[proc_macro_derive(SerdeType, attributes(serde_type))]
+pub fn derive_serde_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    serde_type::derive(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_mod_args() {
+        let args: ExportModArgs = syn::parse_str(r#""../mod.wit""#).unwrap();
+        assert_eq!(args.path.value(), "../mod.wit");
+        assert!(args.name.is_none());
+
+        let args: ExportModArgs = syn::parse_str(r#""../mod.wit", name = "My Mod","#).unwrap();
+        assert_eq!(args.name.unwrap().value(), "My Mod");
+
+        assert!(syn::parse_str::<ExportModArgs>(r#""../mod.wit", title = "My Mod""#).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    spanned::Spanned, Data, DeriveInput, Error, Fields, GenericArgument, Ident, PathArguments,
+    Result, Type,
+};
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let wit = wants_wit(&input)?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut where_clause = where_clause
+        .cloned()
+        .unwrap_or_else(|| syn::parse_quote!(where));
+    where_clause.predicates.push(syn::parse_quote!(
+        #ident #ty_generics: ::types::__private::Serialize
+            + ::types::__private::DeserializeOwned
+    ));
+
+    let wit = if wit {
+        let definition = wit_definition(&input)?;
+        quote! {
+            const WIT: ::std::option::Option<&'static str> =
+                ::std::option::Option::Some(#definition);
+        }
+    } else {
+        quote! {}
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::types::SerdeType for #ident #ty_generics #where_clause {
+            #wit
+        }
+    })
+}
+
+/// Whether the type has `#[serde_type(wit)]`.
+fn wants_wit(input: &DeriveInput) -> Result<bool> {
+    let mut wit = false;
+    for attr in &input.attrs {
+        if !attr.path().is_ident("serde_type") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("wit") {
+                wit = true;
+                Ok(())
+            } else {
+                Err(meta.error("Expected `wit`"))
+            }
+        })?;
+    }
+    Ok(wit)
+}
+
+fn wit_definition(input: &DeriveInput) -> Result<String> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "WIT has no generic types, drop `#[serde_type(wit)]`",
+        ));
+    }
+    let name = wit_name(&input.ident)?;
+    let empty = |what: &str| {
+        Err(Error::new_spanned(
+            &input.ident,
+            format!("WIT has no empty {}", what),
+        ))
+    };
+
+    match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) if fields.named.is_empty() => empty("records"),
+            Fields::Unnamed(fields) if fields.unnamed.is_empty() => empty("tuples"),
+            Fields::Named(fields) => {
+                let mut definition = format!("record {} {{\n", name);
+                for field in &fields.named {
+                    definition.push_str(&format!(
+                        "    {}: {},\n",
+                        wit_name(field.ident.as_ref().unwrap())?,
+                        wit_type(&field.ty)?
+                    ));
+                }
+                definition.push('}');
+                Ok(definition)
+            }
+            Fields::Unnamed(fields) => Ok(format!(
+                "type {} = {};",
+                name,
+                wit_tuple(fields.unnamed.iter().map(|field| &field.ty))?
+            )),
+            Fields::Unit => empty("records"),
+        },
+        Data::Enum(data) if data.variants.is_empty() => empty("enums"),
+        Data::Enum(data) => {
+            let unit_only = data
+                .variants
+                .iter()
+                .all(|variant| matches!(variant.fields, Fields::Unit));
+            let kind = if unit_only { "enum" } else { "variant" };
+            let mut definition = format!("{} {} {{\n", kind, name);
+            for variant in &data.variants {
+                let case = wit_name(&variant.ident)?;
+                match &variant.fields {
+                    Fields::Unit => definition.push_str(&format!("    {},\n", case)),
+                    Fields::Unnamed(fields) => definition.push_str(&format!(
+                        "    {}({}),\n",
+                        case,
+                        wit_tuple(fields.unnamed.iter().map(|field| &field.ty))?
+                    )),
+                    Fields::Named(_) => return Err(Error::new_spanned(
+                        variant,
+                        "WIT variants can't have named fields, use a tuple variant with a record",
+                    )),
+                }
+            }
+            definition.push('}');
+            Ok(definition)
+        }
+        Data::Union(_) => Err(Error::new_spanned(
+            &input.ident,
+            "Unions can't be serialized",
+        )),
+    }
+}
+
+/// A single type as itself, several as a tuple.
+fn wit_tuple<'a>(types: impl Iterator<Item = &'a Type>) -> Result<String> {
+    let types = types.map(wit_type).collect::<Result<Vec<String>>>()?;
+    Ok(if types.len() == 1 {
+        types.into_iter().next().unwrap()
+    } else {
+        format!("tuple<{}>", types.join(", "))
+    })
+}
+
+/// WIT for a Rust type. Types that aren't built in are expected to be
+/// defined in WIT under their `wit_name`.
+fn wit_type(ty: &Type) -> Result<String> {
+    match ty {
+        Type::Tuple(tuple) if !tuple.elems.is_empty() => Ok(format!(
+            "tuple<{}>",
+            tuple
+                .elems
+                .iter()
+                .map(wit_type)
+                .collect::<Result<Vec<String>>>()?
+                .join(", ")
+        )),
+        Type::Reference(reference) => wit_type(&reference.elem),
+        Type::Slice(slice) => Ok(format!("list<{}>", wit_type(&slice.elem)?)),
+        Type::Array(array) => Ok(format!("list<{}>", wit_type(&array.elem)?)),
+        Type::Paren(paren) => wit_type(&paren.elem),
+        Type::Group(group) => wit_type(&group.elem),
+        Type::Path(path) if path.qself.is_none() => {
+            let segment = path.path.segments.last().unwrap();
+            let arguments = match &segment.arguments {
+                PathArguments::None => Vec::new(),
+                PathArguments::AngleBracketed(arguments) => arguments
+                    .args
+                    .iter()
+                    .filter_map(|argument| match argument {
+                        GenericArgument::Type(ty) => Some(wit_type(ty)),
+                        _ => None,
+                    })
+                    .collect::<Result<Vec<String>>>()?,
+                PathArguments::Parenthesized(_) => {
+                    return Err(Error::new(ty.span(), "Functions have no WIT type"))
+                }
+            };
+            let name = segment.ident.to_string();
+            Ok(match (name.as_str(), arguments.as_slice()) {
+                ("bool", []) => "bool".to_string(),
+                ("u8", []) => "u8".to_string(),
+                ("u16", []) => "u16".to_string(),
+                ("u32", []) => "u32".to_string(),
+                ("u64", []) => "u64".to_string(),
+                ("i8", []) => "s8".to_string(),
+                ("i16", []) => "s16".to_string(),
+                ("i32", []) => "s32".to_string(),
+                ("i64", []) => "s64".to_string(),
+                ("f32", []) => "f32".to_string(),
+                ("f64", []) => "f64".to_string(),
+                ("char", []) => "char".to_string(),
+                ("String" | "str", []) => "string".to_string(),
+                ("Box", [inner]) => inner.clone(),
+                ("Vec" | "VecDeque", [inner]) => format!("list<{}>", inner),
+                ("Option", [inner]) => format!("option<{}>", inner),
+                ("Result", [ok, err]) => format!("result<{}, {}>", ok, err),
+                (_, []) => wit_name(&segment.ident)?,
+                _ => {
+                    return Err(Error::new(
+                        ty.span(),
+                        "Generic types other than Box, Vec, VecDeque, Option and Result have no WIT type",
+                    ))
+                }
+            })
+        }
+        _ => Err(Error::new(ty.span(), "Type has no WIT equivalent")),
+    }
+}
+
+/// Words WIT reserves, names that are one of them get a `%` in front.
+const WIT_KEYWORDS: &[&str] = &[
+    "as",
+    "async",
+    "bool",
+    "borrow",
+    "char",
+    "constructor",
+    "enum",
+    "error-context",
+    "export",
+    "f32",
+    "f64",
+    "flags",
+    "float32",
+    "float64",
+    "from",
+    "func",
+    "future",
+    "import",
+    "include",
+    "interface",
+    "list",
+    "option",
+    "own",
+    "package",
+    "record",
+    "resource",
+    "result",
+    "s16",
+    "s32",
+    "s64",
+    "s8",
+    "static",
+    "stream",
+    "string",
+    "tuple",
+    "type",
+    "u16",
+    "u32",
+    "u64",
+    "u8",
+    "use",
+    "variant",
+    "with",
+    "world",
+];
+
+/// WIT identifier for a Rust one. Fails for names that have no valid
+/// kebab-case form, like ones starting with a digit once underscores are
+/// dropped.
+fn wit_name(ident: &Ident) -> Result<String> {
+    let name = kebab_case(&ident.to_string());
+    let valid = !name.is_empty()
+        && name.split('-').all(|word| {
+            word.starts_with(|c: char| c.is_ascii_lowercase())
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        });
+    if !valid {
+        return Err(Error::new(
+            ident.span(),
+            format!("`{}` has no valid WIT name", ident),
+        ));
+    }
+    Ok(if WIT_KEYWORDS.contains(&name.as_str()) {
+        format!("%{}", name)
+    } else {
+        name
+    })
+}
+
+/// Splits a Rust name into lowercase words at underscores and case changes.
+/// Acronyms stay one word and digits stay with the word before them, since
+/// WIT words can't start with one: `PlayerState` and `player_state` are
+/// `player-state`, `HTTPServer` is `http-server` and `player_2` is
+/// `player2`.
+fn kebab_case(name: &str) -> String {
+    let chars: Vec<char> = name.trim_start_matches("r#").chars().collect();
+    let mut kebab = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '_' {
+            if !kebab.is_empty() && !kebab.ends_with('-') {
+                kebab.push('-');
+            }
+            continue;
+        }
+        if c.is_ascii_digit() {
+            if kebab.ends_with('-') {
+                kebab.pop();
+            }
+            kebab.push(*c);
+            continue;
+        }
+        if c.is_uppercase() && !kebab.is_empty() && !kebab.ends_with('-') {
+            let previous = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_lower)
+            {
+                kebab.push('-');
+            }
+        }
+        kebab.extend(c.to_lowercase());
+    }
+    kebab.trim_end_matches('-').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn kebab_case_words() {
+        assert_eq!(kebab_case("PlayerState"), "player-state");
+        assert_eq!(kebab_case("player_state"), "player-state");
+        assert_eq!(kebab_case("HTTPServer"), "http-server");
+        assert_eq!(kebab_case("ParseHTTP"), "parse-http");
+        assert_eq!(kebab_case("player_2"), "player2");
+        assert_eq!(kebab_case("Vec2"), "vec2");
+        assert_eq!(kebab_case("r#type"), "type");
+        assert_eq!(kebab_case("_hidden__field_"), "hidden-field");
+    }
+
+    #[test]
+    fn wit_names() {
+        assert_eq!(
+            wit_name(&parse_quote!(PlayerState)).unwrap(),
+            "player-state"
+        );
+        assert_eq!(wit_name(&parse_quote!(r#type)).unwrap(), "%type");
+        assert_eq!(wit_name(&parse_quote!(List)).unwrap(), "%list");
+        assert!(wit_name(&parse_quote!(_2d)).is_err());
+    }
+
+    #[test]
+    fn records() {
+        let input: DeriveInput = parse_quote! {
+            struct PlayerState {
+                player_2: Option<Vec<u8>>,
+                r#type: (i32, String),
+                server: HTTPServer,
+            }
+        };
+        assert_eq!(
+            wit_definition(&input).unwrap(),
+            "record player-state {\n    player2: option<list<u8>>,\n    %type: tuple<s32, string>,\n    server: http-server,\n}"
+        );
+    }
+
+    #[test]
+    fn tuple_structs() {
+        let input: DeriveInput = parse_quote!(
+            struct Score(u32, String);
+        );
+        assert_eq!(
+            wit_definition(&input).unwrap(),
+            "type score = tuple<u32, string>;"
+        );
+        let input: DeriveInput = parse_quote!(
+            struct Id(u64);
+        );
+        assert_eq!(wit_definition(&input).unwrap(), "type id = u64;");
+    }
+
+    #[test]
+    fn enums_and_variants() {
+        let input: DeriveInput = parse_quote!(
+            enum Team {
+                Red,
+                BlueTeam,
+            }
+        );
+        assert_eq!(
+            wit_definition(&input).unwrap(),
+            "enum team {\n    red,\n    blue-team,\n}"
+        );
+
+        let input: DeriveInput = parse_quote!(
+            enum Shape {
+                Circle(f32),
+                Rect(f32, f32),
+                Empty,
+            }
+        );
+        assert_eq!(
+            wit_definition(&input).unwrap(),
+            "variant shape {\n    circle(f32),\n    rect(tuple<f32, f32>),\n    empty,\n}"
+        );
+    }
+
+    #[test]
+    fn invalid_wit_is_an_error() {
+        let inputs: [DeriveInput; 6] = [
+            parse_quote!(
+                enum Never {}
+            ),
+            parse_quote!(
+                struct Unit;
+            ),
+            parse_quote!(
+                struct Empty {}
+            ),
+            parse_quote!(
+                struct Wrapper<T>(T);
+            ),
+            parse_quote!(
+                enum Named {
+                    A { x: u8 },
+                }
+            ),
+            parse_quote!(
+                struct Map(HashMap<String, u8>);
+            ),
+        ];
+        for input in inputs {
+            assert!(wit_definition(&input).is_err(), "{}", input.ident);
+        }
+    }
+}
@@ -0,0 +1,408 @@
+use crate::{
+    gamepad::{GamepadAxis, GamepadButton},
+    input::{InputState, Key, MouseButton},
+};
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+use utils::logging::*;
+
+/// Physical input an action can be bound to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InputSource {
+    Key(Key),
+    MouseButton(MouseButton),
+    GamepadButton(GamepadButton),
+    GamepadAxis(GamepadAxis),
+}
+
+impl InputSource {
+    /// Current strength of the input: 0 or 1 for buttons, the deadzoned
+    /// deflection for axes.
+    fn value(&self, input: &InputState) -> f32 {
+        let down = match self {
+            InputSource::Key(key) => input.is_key_down(key),
+            InputSource::MouseButton(button) => input.is_button_down(*button),
+            InputSource::GamepadButton(button) => input.gamepads.is_button_down_on_any(*button),
+            InputSource::GamepadAxis(axis) => return input.gamepads.axis_on_any(*axis),
+        };
+        if down {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Whether the input went down since the last step, by its events.
+    /// Axes have no events, their edges come from the action value.
+    fn pressed(&self, input: &InputState) -> bool {
+        match self {
+            InputSource::Key(key) => input.is_key_pressed(key),
+            InputSource::MouseButton(button) => input.is_button_pressed(*button),
+            InputSource::GamepadButton(button) => input.gamepads.is_button_pressed_on_any(*button),
+            InputSource::GamepadAxis(_) => false,
+        }
+    }
+
+    fn normalized(&self) -> Self {
+        match self {
+            InputSource::Key(key) => InputSource::Key(key.normalized()),
+            source => source.clone(),
+        }
+    }
+}
+
+/// An input bound to an action. The input's value is multiplied by `scale`,
+/// so two keys bound with scales -1 and 1 make an axis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binding {
+    pub source: InputSource,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+impl Binding {
+    pub fn new(source: InputSource, scale: f32) -> Self {
+        Self {
+            source: source.normalized(),
+            scale,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Action {
+    defaults: Vec<Binding>,
+    value: f32,
+    down: bool,
+    pressed: bool,
+    released: bool,
+}
+
+/// An action as shown to a settings UI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionInfo {
+    pub owner: String,
+    pub name: String,
+    pub bindings: Vec<Binding>,
+    /// Whether the user replaced the default bindings.
+    pub rebound: bool,
+    /// Actions of other mods bound to one of the same inputs, as
+    /// `owner/name`.
+    pub conflicts: Vec<String>,
+}
+
+/// User rebindings as stored on disk, by mod id, then action name.
+type Rebindings = BTreeMap<String, BTreeMap<String, Vec<Binding>>>;
+
+/// Named actions mods register, keyed by owning mod and action name. Mods
+/// only see their own actions. Rebindings are kept in a TOML file so they
+/// survive restarts and mods being reloaded.
+#[derive(Debug, Default)]
+pub struct ActionMap {
+    actions: BTreeMap<(String, String), Action>,
+    rebindings: Rebindings,
+    config_path: Option<PathBuf>,
+}
+
+/// An action counts as down once its value is at least this strong.
+const DOWN_THRESHOLD: f32 = 0.5;
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads rebindings from `path`, which doesn't have to exist yet.
+    /// Rebindings are written back there. A file that can't be read is
+    /// warned about and every action keeps its defaults.
+    pub fn load(path: &Path) -> Self {
+        let mut map = Self {
+            config_path: Some(path.to_path_buf()),
+            ..Self::default()
+        };
+        if !path.exists() {
+            return map;
+        }
+
+        let rebindings = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| toml::from_str::<Rebindings>(&text).map_err(|e| e.to_string()));
+        match rebindings {
+            Ok(mut rebindings) => {
+                for binding in rebindings
+                    .values_mut()
+                    .flat_map(|actions| actions.values_mut())
+                    .flatten()
+                {
+                    binding.source = binding.source.normalized();
+                }
+                map.rebindings = rebindings;
+            }
+            Err(e) => warn!("Failed to read bindings {}: {}", path.display(), e),
+        }
+        map
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let Some(path) = &self.config_path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).log_msg("Failed to create config directory")?;
+        }
+        let text = toml::to_string(&self.rebindings).log_msg("Failed to serialize bindings")?;
+        std::fs::write(path, text).log_msg("Failed to write bindings")?;
+        Ok(())
+    }
+
+    /// Registers an action for `owner`, replacing an earlier registration
+    /// of the same name. Warns about inputs other mods already use.
+    pub fn register(&mut self, owner: &str, name: &str, defaults: Vec<Binding>) {
+        let key = (owner.to_string(), name.to_string());
+        self.actions.insert(
+            key,
+            Action {
+                defaults,
+                value: 0.0,
+                down: false,
+                pressed: false,
+                released: false,
+            },
+        );
+
+        for conflict in self.conflicts(owner, name) {
+            warn!(
+                "Action {}/{} shares an input with {}",
+                owner, name, conflict
+            );
+        }
+    }
+
+    /// Drops the actions of an unloaded mod. Its rebindings are kept.
+    pub fn release_mod(&mut self, owner: &str) {
+        self.actions
+            .retain(|(action_owner, _), _| action_owner != owner);
+    }
+
+    /// Bindings in effect for an action: the user's if rebound, otherwise
+    /// the defaults the mod registered.
+    pub fn bindings(&self, owner: &str, name: &str) -> Vec<Binding> {
+        if let Some(bindings) = self
+            .rebindings
+            .get(owner)
+            .and_then(|actions| actions.get(name))
+        {
+            return bindings.clone();
+        }
+        self.actions
+            .get(&(owner.to_string(), name.to_string()))
+            .map(|action| action.defaults.clone())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the bindings of an action and saves them.
+    pub fn rebind(&mut self, owner: &str, name: &str, bindings: Vec<Binding>) -> Result<()> {
+        self.rebindings
+            .entry(owner.to_string())
+            .or_default()
+            .insert(name.to_string(), bindings);
+        self.save()
+    }
+
+    /// Goes back to the default bindings of an action and saves.
+    pub fn reset(&mut self, owner: &str, name: &str) -> Result<()> {
+        if let Some(actions) = self.rebindings.get_mut(owner) {
+            actions.remove(name);
+            if actions.is_empty() {
+                self.rebindings.remove(owner);
+            }
+        }
+        self.save()
+    }
+
+    /// Actions of other mods bound to any input of the given action.
+    pub fn conflicts(&self, owner: &str, name: &str) -> Vec<String> {
+        let bindings = self.bindings(owner, name);
+        self.actions
+            .keys()
+            .filter(|(other_owner, _)| other_owner != owner)
+            .filter(|(other_owner, other_name)| {
+                self.bindings(other_owner, other_name).iter().any(|other| {
+                    bindings
+                        .iter()
+                        .any(|binding| binding.source == other.source)
+                })
+            })
+            .map(|(other_owner, other_name)| format!("{}/{}", other_owner, other_name))
+            .collect()
+    }
+
+    /// Every registered action, sorted by mod and name.
+    pub fn list(&self) -> Vec<ActionInfo> {
+        self.actions
+            .keys()
+            .map(|(owner, name)| ActionInfo {
+                owner: owner.clone(),
+                name: name.clone(),
+                bindings: self.bindings(owner, name),
+                rebound: self
+                    .rebindings
+                    .get(owner)
+                    .is_some_and(|actions| actions.contains_key(name)),
+                conflicts: self.conflicts(owner, name),
+            })
+            .collect()
+    }
+
+    /// Recomputes action values from the input state. Called once per
+    /// simulation step, see `ModManager::step`.
+    ///
+    /// Presses and releases also count from the input's own edges, so a
+    /// key tapped between two steps still presses and releases the action
+    /// although it was never down when sampled.
+    pub fn update(&mut self, input: &InputState) {
+        let keys: Vec<_> = self.actions.keys().cloned().collect();
+        for key in keys {
+            let bindings = self.bindings(&key.0, &key.1);
+            let value = bindings
+                .iter()
+                .map(|binding| binding.source.value(input) * binding.scale)
+                .sum::<f32>()
+                .clamp(-1.0, 1.0);
+            let source_pressed = bindings.iter().any(|binding| {
+                binding.scale.abs() >= DOWN_THRESHOLD && binding.source.pressed(input)
+            });
+            if let Some(action) = self.actions.get_mut(&key) {
+                let was_down = action.down;
+                action.value = value;
+                action.down = value.abs() >= DOWN_THRESHOLD;
+                action.pressed = !was_down && (action.down || source_pressed);
+                // Pressed but no longer down: tapped since the last step.
+                action.released = !action.down && (was_down || source_pressed);
+            }
+        }
+    }
+
+    fn action(&self, owner: &str, name: &str) -> Option<&Action> {
+        self.actions.get(&(owner.to_string(), name.to_string()))
+    }
+
+    pub fn value(&self, owner: &str, name: &str) -> f32 {
+        self.action(owner, name).map_or(0.0, |action| action.value)
+    }
+
+    pub fn is_down(&self, owner: &str, name: &str) -> bool {
+        self.action(owner, name).is_some_and(|action| action.down)
+    }
+
+    pub fn is_pressed(&self, owner: &str, name: &str) -> bool {
+        self.action(owner, name)
+            .is_some_and(|action| action.pressed)
+    }
+
+    pub fn is_released(&self, owner: &str, name: &str) -> bool {
+        self.action(owner, name)
+            .is_some_and(|action| action.released)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("actions-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn key(name: &str) -> Binding {
+        Binding::new(InputSource::Key(Key::keycode(name)), 1.0)
+    }
+
+    #[test]
+    fn conflicts_are_between_mods() {
+        let mut actions = ActionMap::new();
+        actions.register("a", "jump", vec![key("Space")]);
+        actions.register("a", "also-jump", vec![key("space")]);
+        actions.register("b", "fire", vec![key("space"), key("f")]);
+        actions.register("b", "reload", vec![key("r")]);
+
+        assert_eq!(actions.conflicts("a", "jump"), ["b/fire"]);
+        assert_eq!(actions.conflicts("b", "fire"), ["a/also-jump", "a/jump"]);
+        assert!(actions.conflicts("b", "reload").is_empty());
+
+        actions.rebind("b", "fire", vec![key("f")]).unwrap();
+        assert!(actions.conflicts("a", "jump").is_empty());
+    }
+
+    #[test]
+    fn rebindings_persist() {
+        let path = temp_dir("persist").join("config").join("bindings.toml");
+        let mut actions = ActionMap::load(&path);
+        actions.register("a", "jump", vec![key("space")]);
+        actions.register("a", "fire", vec![key("f")]);
+        actions.rebind("a", "jump", vec![key("W")]).unwrap();
+        actions.rebind("a", "fire", vec![key("g")]).unwrap();
+        actions.reset("a", "fire").unwrap();
+
+        let mut actions = ActionMap::load(&path);
+        // Rebindings apply before the mod registers again.
+        assert_eq!(actions.bindings("a", "jump"), [key("w")]);
+        actions.register("a", "jump", vec![key("space")]);
+        actions.register("a", "fire", vec![key("f")]);
+        assert_eq!(actions.bindings("a", "jump"), [key("w")]);
+        assert_eq!(actions.bindings("a", "fire"), [key("f")]);
+        let rebound: Vec<_> = actions.list().iter().map(|info| info.rebound).collect();
+        assert_eq!(rebound, [false, true]);
+    }
+
+    #[test]
+    fn bad_bindings_file_falls_back_to_defaults() {
+        let path = temp_dir("bad").join("bindings.toml");
+        std::fs::write(&path, "a = 1").unwrap();
+        let mut actions = ActionMap::load(&path);
+        actions.register("a", "jump", vec![key("space")]);
+        assert_eq!(actions.bindings("a", "jump"), [key("space")]);
+    }
+
+    #[test]
+    fn pressed_and_released_edges() {
+        let mut actions = ActionMap::new();
+        actions.register("a", "jump", vec![key("space")]);
+        let mut input = InputState::new();
+        let mut step = |input: &mut InputState| {
+            actions.update(input);
+            input.end_frame();
+            (
+                actions.is_pressed("a", "jump"),
+                actions.is_down("a", "jump"),
+                actions.is_released("a", "jump"),
+            )
+        };
+
+        input.key_down(Some("space"), None);
+        assert_eq!(step(&mut input), (true, true, false));
+        assert_eq!(step(&mut input), (false, true, false));
+        input.key_up(Some("space"), None);
+        assert_eq!(step(&mut input), (false, false, true));
+        assert_eq!(step(&mut input), (false, false, false));
+
+        // Tapped between two steps, the action was never down when sampled.
+        input.key_down(Some("space"), None);
+        input.key_up(Some("space"), None);
+        assert_eq!(step(&mut input), (true, false, true));
+        assert_eq!(step(&mut input), (false, false, false));
+    }
+}
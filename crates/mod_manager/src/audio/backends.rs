@@ -0,0 +1,66 @@
+use super::{wav, AudioBackend};
+use anyhow::{Error, Result};
+use std::{fs::File, io::BufWriter, path::Path};
+use utils::logging::*;
+
+/// Backend without a device that takes a fixed number of frames per pump
+/// and drops them.
+#[derive(Debug)]
+pub struct NullAudio {
+    frames_per_pump: usize,
+    /// Frames queued so far.
+    pub frames: u64,
+}
+
+impl NullAudio {
+    pub fn new(frames_per_pump: usize) -> Self {
+        Self {
+            frames_per_pump,
+            frames: 0,
+        }
+    }
+}
+
+impl AudioBackend for NullAudio {
+    fn wanted_frames(&mut self) -> usize {
+        self.frames_per_pump
+    }
+
+    fn queue(&mut self, samples: &[f32]) -> Result<()> {
+        self.frames += (samples.len() / 2) as u64;
+        Ok(())
+    }
+}
+
+/// Backend that writes a fixed number of frames per pump into a 16-bit WAV
+/// file, to listen to or compare what was mixed.
+#[derive(Debug)]
+pub struct WavFileAudio {
+    writer: BufWriter<File>,
+    frames_per_pump: usize,
+    frames: u32,
+}
+
+impl WavFileAudio {
+    pub fn create(path: &Path, frames_per_pump: usize) -> Result<Self, Error> {
+        let mut writer = BufWriter::new(File::create(path).log_msg("Failed to create WAV file")?);
+        wav::write_header(&mut writer, 0).log_msg("Failed to write WAV header")?;
+        Ok(Self {
+            writer,
+            frames_per_pump,
+            frames: 0,
+        })
+    }
+}
+
+impl AudioBackend for WavFileAudio {
+    fn wanted_frames(&mut self) -> usize {
+        self.frames_per_pump
+    }
+
+    fn queue(&mut self, samples: &[f32]) -> Result<()> {
+        wav::append(&mut self.writer, &mut self.frames, samples)
+            .log_msg("Failed to write WAV file")?;
+        Ok(())
+    }
+}
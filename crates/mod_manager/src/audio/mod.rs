@@ -0,0 +1,584 @@
+mod backends;
+pub mod wav;
+pub use backends::{NullAudio, WavFileAudio};
+
+use anyhow::Result;
+use std::{
+    collections::{BTreeMap, HashMap},
+    f32::consts::TAU,
+    sync::Arc,
+};
+use tracing::debug;
+
+/// Frames per second the mixer produces, always interleaved stereo.
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// Voices one mod may play at once unless the host sets another limit.
+pub const DEFAULT_VOICE_LIMIT: usize = 16;
+
+/// Sounds one mod may hold at once unless the host sets another limit.
+pub const DEFAULT_SOUND_LIMIT: SoundLimit = SoundLimit {
+    sounds: 256,
+    samples: 16 * 1024 * 1024,
+};
+
+/// Longest sound the generators make, in seconds.
+const MAX_GENERATED_SECONDS: f32 = 30.0;
+
+/// Decoded sound at `SAMPLE_RATE`, mono or interleaved stereo. Cheap to
+/// clone, the samples are shared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sound {
+    channels: usize,
+    samples: Arc<[f32]>,
+}
+
+impl Sound {
+    pub fn new(channels: usize, samples: Vec<f32>) -> Self {
+        Self {
+            channels,
+            samples: samples.into(),
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    /// Sample of `channel` in `frame`; mono sounds have the same on both.
+    fn sample(&self, frame: usize, channel: usize) -> f32 {
+        self.samples[frame * self.channels + channel.min(self.channels - 1)]
+    }
+
+    /// Tone at `frequency` Hz lasting `duration` seconds, with a short fade
+    /// in and out so it doesn't click.
+    pub fn tone(waveform: Waveform, frequency: f32, duration: f32) -> Self {
+        let frames = generated_frames(duration);
+        let fade = (SAMPLE_RATE as usize / 200).min(frames / 2).max(1);
+        let samples = (0..frames)
+            .map(|i| {
+                let phase = (i as f32 * frequency / SAMPLE_RATE as f32).fract();
+                let envelope = (i.min(frames - 1 - i) as f32 / fade as f32).min(1.0);
+                waveform.sample(phase) * envelope
+            })
+            .collect();
+        Self::new(1, samples)
+    }
+
+    /// White noise lasting `duration` seconds. The same `seed` gives the
+    /// same noise.
+    pub fn noise(duration: f32, seed: u32) -> Self {
+        let mut state = seed.max(1);
+        let samples = (0..generated_frames(duration))
+            .map(|_| {
+                // xorshift32
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as f32 / u32::MAX as f32 * 2.0 - 1.0
+            })
+            .collect();
+        Self::new(1, samples)
+    }
+}
+
+fn generated_frames(duration: f32) -> usize {
+    let duration = if duration.is_finite() {
+        duration.clamp(0.0, MAX_GENERATED_SECONDS)
+    } else {
+        0.0
+    };
+    (duration * SAMPLE_RATE as f32) as usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+    Sawtooth,
+}
+
+impl Waveform {
+    pub const ALL: [Waveform; 4] = [
+        Waveform::Sine,
+        Waveform::Square,
+        Waveform::Triangle,
+        Waveform::Sawtooth,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Waveform::Sine => "sine",
+            Waveform::Square => "square",
+            Waveform::Triangle => "triangle",
+            Waveform::Sawtooth => "sawtooth",
+        }
+    }
+
+    /// Value at `phase` in `0.0..1.0`, in `-1.0..=1.0`.
+    fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+        }
+    }
+}
+
+/// How many sounds a mod may hold and how many samples they may take in
+/// total.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoundLimit {
+    pub sounds: usize,
+    pub samples: usize,
+}
+
+/// Options of a playing voice.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayOptions {
+    /// Gain, `0.0..=1.0`.
+    pub volume: f32,
+    /// `-1.0` is left, `1.0` right.
+    pub pan: f32,
+    pub looping: bool,
+}
+
+#[derive(Debug)]
+struct Voice {
+    owner: String,
+    sound: Sound,
+    frame: usize,
+    options: PlayOptions,
+}
+
+/// Mixes the voices mods play into interleaved stereo. Sounds and voices
+/// are handles owned by the mod that created them and released when it
+/// unloads. Every mod has its own volume and a limit of voices playing at
+/// once; a mod at its limit cuts off its oldest voice. The sounds a mod
+/// holds are limited too, adding one past the limit fails.
+#[derive(Debug)]
+pub struct Mixer {
+    next_handle: u32,
+    sounds: HashMap<u32, (String, Sound)>,
+    /// Ordered by handle, so the first voice of a mod is its oldest.
+    voices: BTreeMap<u32, Voice>,
+    mod_volumes: HashMap<String, f32>,
+    voice_limits: HashMap<String, usize>,
+    sound_limits: HashMap<String, SoundLimit>,
+    master_volume: f32,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self {
+            next_handle: 0,
+            sounds: HashMap::new(),
+            voices: BTreeMap::new(),
+            mod_volumes: HashMap::new(),
+            voice_limits: HashMap::new(),
+            sound_limits: HashMap::new(),
+            master_volume: 1.0,
+        }
+    }
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn next_handle(&mut self) -> u32 {
+        self.next_handle += 1;
+        self.next_handle
+    }
+
+    pub fn add_sound(&mut self, owner: &str, sound: Sound) -> Result<u32, String> {
+        let limit = self.sound_limit(owner);
+        let (sounds, samples) = self
+            .sounds
+            .values()
+            .filter(|(sound_owner, _)| sound_owner == owner)
+            .fold((0, 0), |(sounds, samples), (_, sound)| {
+                (sounds + 1, samples + sound.samples.len())
+            });
+        if sounds >= limit.sounds {
+            return Err(format!("No more than {} sounds allowed", limit.sounds));
+        }
+        if samples + sound.samples.len() > limit.samples {
+            return Err(format!(
+                "Sounds may take no more than {} samples",
+                limit.samples
+            ));
+        }
+
+        let handle = self.next_handle();
+        self.sounds.insert(handle, (owner.to_string(), sound));
+        Ok(handle)
+    }
+
+    /// Frees a sound of `owner`. Voices playing it keep playing.
+    pub fn free_sound(&mut self, owner: &str, sound: u32) {
+        if self
+            .sounds
+            .get(&sound)
+            .is_some_and(|(sound_owner, _)| sound_owner == owner)
+        {
+            self.sounds.remove(&sound);
+        }
+    }
+
+    /// Starts playing a sound of `owner`, returns the voice.
+    pub fn play(&mut self, owner: &str, sound: u32, options: PlayOptions) -> Result<u32, String> {
+        let sound = match self.sounds.get(&sound) {
+            Some((sound_owner, sound)) if sound_owner == owner => sound.clone(),
+            _ => return Err(format!("Unknown sound {}", sound)),
+        };
+
+        let limit = self.voice_limit(owner);
+        let playing: Vec<u32> = self
+            .voices
+            .iter()
+            .filter(|(_, voice)| voice.owner == owner)
+            .map(|(handle, _)| *handle)
+            .collect();
+        if limit == 0 {
+            return Err("No voices allowed".to_string());
+        }
+        for handle in playing
+            .iter()
+            .take((playing.len() + 1).saturating_sub(limit))
+        {
+            debug!("{} is at its voice limit, stopping voice {}", owner, handle);
+            self.voices.remove(handle);
+        }
+
+        let handle = self.next_handle();
+        self.voices.insert(
+            handle,
+            Voice {
+                owner: owner.to_string(),
+                sound,
+                frame: 0,
+                options: PlayOptions {
+                    volume: sanitize(options.volume, 0.0, 1.0),
+                    pan: sanitize(options.pan, -1.0, 1.0),
+                    looping: options.looping,
+                },
+            },
+        );
+        Ok(handle)
+    }
+
+    pub fn stop(&mut self, owner: &str, voice: u32) {
+        if self
+            .voices
+            .get(&voice)
+            .is_some_and(|playing| playing.owner == owner)
+        {
+            self.voices.remove(&voice);
+        }
+    }
+
+    pub fn is_playing(&self, voice: u32) -> bool {
+        self.voices.contains_key(&voice)
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = sanitize(volume, 0.0, 1.0);
+    }
+
+    pub fn set_mod_volume(&mut self, mod_id: &str, volume: f32) {
+        self.mod_volumes
+            .insert(mod_id.to_string(), sanitize(volume, 0.0, 1.0));
+    }
+
+    pub fn mod_volume(&self, mod_id: &str) -> f32 {
+        self.mod_volumes.get(mod_id).copied().unwrap_or(1.0)
+    }
+
+    pub fn set_voice_limit(&mut self, mod_id: &str, limit: usize) {
+        self.voice_limits.insert(mod_id.to_string(), limit);
+    }
+
+    pub fn voice_limit(&self, mod_id: &str) -> usize {
+        self.voice_limits
+            .get(mod_id)
+            .copied()
+            .unwrap_or(DEFAULT_VOICE_LIMIT)
+    }
+
+    pub fn set_sound_limit(&mut self, mod_id: &str, limit: SoundLimit) {
+        self.sound_limits.insert(mod_id.to_string(), limit);
+    }
+
+    pub fn sound_limit(&self, mod_id: &str) -> SoundLimit {
+        self.sound_limits
+            .get(mod_id)
+            .copied()
+            .unwrap_or(DEFAULT_SOUND_LIMIT)
+    }
+
+    /// Stops the voices of a mod and frees its sounds. Volume and limits are
+    /// host settings and stay.
+    pub fn release_mod(&mut self, mod_id: &str) {
+        self.voices.retain(|_, voice| voice.owner != mod_id);
+        self.sounds.retain(|_, (owner, _)| owner != mod_id);
+    }
+
+    /// Mixes the next `out.len() / 2` frames into `out`, which is
+    /// overwritten. Voices that reached their end are dropped.
+    pub fn mix(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        let frames = out.len() / 2;
+        for voice in self.voices.values_mut() {
+            let volume = self.master_volume
+                * self.mod_volumes.get(&voice.owner).copied().unwrap_or(1.0)
+                * voice.options.volume;
+            // Linear pan, a centered voice plays at full volume on both.
+            let pan = voice.options.pan;
+            let gains = [volume * (1.0 - pan).min(1.0), volume * (1.0 + pan).min(1.0)];
+            let length = voice.sound.frames();
+
+            for frame in out.chunks_exact_mut(2).take(frames) {
+                if voice.frame >= length {
+                    if !voice.options.looping || length == 0 {
+                        break;
+                    }
+                    voice.frame = 0;
+                }
+                frame[0] += voice.sound.sample(voice.frame, 0) * gains[0];
+                frame[1] += voice.sound.sample(voice.frame, 1) * gains[1];
+                voice.frame += 1;
+            }
+        }
+
+        self.voices.retain(|_, voice| {
+            (voice.options.looping && voice.sound.frames() > 0)
+                || voice.frame < voice.sound.frames()
+        });
+        for sample in out {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// Clamps `value`, mapping NaN to `min`.
+fn sanitize(value: f32, min: f32, max: f32) -> f32 {
+    if value.is_nan() {
+        min
+    } else {
+        value.clamp(min, max)
+    }
+}
+
+/// Where mixed audio goes: an SDL audio queue in the game, `NullAudio` or
+/// `WavFileAudio` where there is no sound device.
+pub trait AudioBackend {
+    /// Frames the backend wants now to keep playing without gaps.
+    fn wanted_frames(&mut self) -> usize;
+    /// Plays interleaved stereo samples at `SAMPLE_RATE`.
+    fn queue(&mut self, samples: &[f32]) -> Result<()>;
+}
+
+/// Mixes as many frames as the backend wants and hands them over. Called
+/// once per frame.
+pub fn pump(backend: &mut dyn AudioBackend, mixer: &mut Mixer) -> Result<()> {
+    let frames = backend.wanted_frames();
+    if frames == 0 {
+        return Ok(());
+    }
+    let mut samples = vec![0.0; frames * 2];
+    mixer.mix(&mut samples);
+    backend.queue(&samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CENTERED: PlayOptions = PlayOptions {
+        volume: 1.0,
+        pan: 0.0,
+        looping: false,
+    };
+
+    fn frames(out: &[f32]) -> Vec<[f32; 2]> {
+        out.chunks_exact(2)
+            .map(|frame| [frame[0], frame[1]])
+            .collect()
+    }
+
+    #[test]
+    fn voices_end_with_their_sound() {
+        let mut mixer = Mixer::new();
+        let sound = mixer
+            .add_sound("a", Sound::new(1, vec![0.5, 0.25]))
+            .unwrap();
+        let voice = mixer.play("a", sound, CENTERED).unwrap();
+
+        let mut out = [1.0; 6];
+        mixer.mix(&mut out);
+        assert_eq!(frames(&out), [[0.5, 0.5], [0.25, 0.25], [0.0, 0.0]]);
+        assert!(!mixer.is_playing(voice));
+    }
+
+    #[test]
+    fn pan() {
+        let mut mixer = Mixer::new();
+        let sound = mixer.add_sound("a", Sound::new(1, vec![0.5])).unwrap();
+        for (pan, expected) in [
+            (-1.0, [0.5, 0.0]),
+            (0.5, [0.25, 0.5]),
+            (f32::NAN, [0.5, 0.0]),
+        ] {
+            let options = PlayOptions { pan, ..CENTERED };
+            mixer.play("a", sound, options).unwrap();
+            let mut out = [0.0; 2];
+            mixer.mix(&mut out);
+            assert_eq!(out, expected, "pan {}", pan);
+        }
+    }
+
+    #[test]
+    fn looping() {
+        let mut mixer = Mixer::new();
+        let sound = mixer
+            .add_sound("a", Sound::new(2, vec![0.1, -0.1, 0.2, -0.2]))
+            .unwrap();
+        let options = PlayOptions {
+            looping: true,
+            ..CENTERED
+        };
+        let voice = mixer.play("a", sound, options).unwrap();
+
+        let mut out = [0.0; 10];
+        mixer.mix(&mut out);
+        assert_eq!(
+            frames(&out),
+            [
+                [0.1, -0.1],
+                [0.2, -0.2],
+                [0.1, -0.1],
+                [0.2, -0.2],
+                [0.1, -0.1]
+            ]
+        );
+        assert!(mixer.is_playing(voice));
+        mixer.stop("b", voice);
+        assert!(mixer.is_playing(voice));
+        mixer.stop("a", voice);
+        assert!(!mixer.is_playing(voice));
+    }
+
+    #[test]
+    fn voice_limit_stops_the_oldest_voice() {
+        let mut mixer = Mixer::new();
+        mixer.set_voice_limit("a", 2);
+        let sound = mixer.add_sound("a", Sound::new(1, vec![0.5])).unwrap();
+        let voices: Vec<u32> = (0..3)
+            .map(|_| mixer.play("a", sound, CENTERED).unwrap())
+            .collect();
+        assert!(!mixer.is_playing(voices[0]));
+        assert!(mixer.is_playing(voices[1]));
+        assert!(mixer.is_playing(voices[2]));
+
+        mixer.set_voice_limit("a", 0);
+        assert!(mixer.play("a", sound, CENTERED).is_err());
+        assert!(mixer.play("b", sound, CENTERED).is_err());
+    }
+
+    #[test]
+    fn volumes_multiply_and_mixes_clip() {
+        let mut mixer = Mixer::new();
+        mixer.set_master_volume(0.5);
+        mixer.set_mod_volume("a", 0.5);
+        let sound = mixer.add_sound("a", Sound::new(1, vec![1.0])).unwrap();
+        mixer.play("a", sound, CENTERED).unwrap();
+        let mut out = [0.0; 2];
+        mixer.mix(&mut out);
+        assert_eq!(out, [0.25, 0.25]);
+
+        mixer.set_master_volume(1.0);
+        mixer.set_mod_volume("a", 1.0);
+        for _ in 0..3 {
+            mixer.play("a", sound, CENTERED).unwrap();
+        }
+        mixer.mix(&mut out);
+        assert_eq!(out, [1.0, 1.0]);
+    }
+
+    #[test]
+    fn sound_limit() {
+        let mut mixer = Mixer::new();
+        mixer.set_sound_limit(
+            "a",
+            SoundLimit {
+                sounds: 2,
+                samples: 4,
+            },
+        );
+        let first = mixer.add_sound("a", Sound::new(1, vec![0.0; 3])).unwrap();
+        assert!(mixer.add_sound("a", Sound::new(1, vec![0.0; 2])).is_err());
+        mixer.add_sound("a", Sound::new(1, vec![0.0])).unwrap();
+        assert!(mixer.add_sound("a", Sound::new(1, Vec::new())).is_err());
+        // Other mods have limits of their own.
+        mixer.add_sound("b", Sound::new(1, vec![0.0; 8])).unwrap();
+
+        mixer.free_sound("a", first);
+        mixer.add_sound("a", Sound::new(1, vec![0.0; 3])).unwrap();
+        mixer.release_mod("a");
+        mixer.add_sound("a", Sound::new(1, vec![0.0; 4])).unwrap();
+    }
+
+    #[test]
+    fn pump_null_audio() {
+        let mut mixer = Mixer::new();
+        let mut backend = NullAudio::new(64);
+        pump(&mut backend, &mut mixer).unwrap();
+        pump(&mut backend, &mut mixer).unwrap();
+        assert_eq!(backend.frames, 128);
+
+        let mut backend = NullAudio::new(0);
+        pump(&mut backend, &mut mixer).unwrap();
+        assert_eq!(backend.frames, 0);
+    }
+
+    #[test]
+    fn pump_wav_file_audio() {
+        let path = std::env::temp_dir().join(format!("mixer-{}.wav", std::process::id()));
+        let mut mixer = Mixer::new();
+        let sound = mixer
+            .add_sound("a", Sound::new(2, vec![0.5, -0.5, 0.25, -0.25]))
+            .unwrap();
+        mixer.play("a", sound, CENTERED).unwrap();
+
+        let mut backend = WavFileAudio::create(&path, 2).unwrap();
+        pump(&mut backend, &mut mixer).unwrap();
+        pump(&mut backend, &mut mixer).unwrap();
+        drop(backend);
+
+        let written = wav::parse(&std::fs::read(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written.frames(), 4);
+        let expected = [0.5, -0.5, 0.25, -0.25, 0.0, 0.0, 0.0, 0.0];
+        for (frame, expected) in expected.chunks_exact(2).enumerate() {
+            for (channel, expected) in expected.iter().enumerate() {
+                let sample = written.sample(frame, channel);
+                assert!(
+                    (sample - expected).abs() < 0.001,
+                    "{} != {}",
+                    sample,
+                    expected
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use super::{Sound, SAMPLE_RATE};
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// Lowest sample rate accepted. Resampling up to `SAMPLE_RATE` multiplies
+/// the samples by up to `SAMPLE_RATE / MIN_SAMPLE_RATE`.
+pub const MIN_SAMPLE_RATE: u32 = 8000;
+
+/// Decodes a RIFF WAVE file: PCM with 8, 16, 24 or 32 bits, or 32-bit
+/// float, mono or stereo. The samples are resampled to `SAMPLE_RATE`.
+pub fn parse(bytes: &[u8]) -> Result<Sound, String> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err("Not a WAV file".to_string());
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut rest = &bytes[12..];
+    while rest.len() >= 8 {
+        let id = &rest[0..4];
+        let size = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let body = rest
+            .get(8..8 + size)
+            .ok_or_else(|| "Truncated WAV chunk".to_string())?;
+        match id {
+            b"fmt " => format = Some(Format::parse(body)?),
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        // Chunks are padded to an even size.
+        rest = rest.get(8 + size + size % 2..).unwrap_or(&[]);
+    }
+    let format = format.ok_or_else(|| "WAV file has no fmt chunk".to_string())?;
+    let data = data.ok_or_else(|| "WAV file has no data chunk".to_string())?;
+
+    let width = (format.bits / 8) as usize;
+    let samples: Vec<f32> = data
+        .chunks_exact(width)
+        .map(|sample| match (format.float, format.bits) {
+            (true, _) => f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]),
+            (false, 8) => (sample[0] as f32 - 128.0) / 128.0,
+            (false, 16) => i16::from_le_bytes([sample[0], sample[1]]) as f32 / 32768.0,
+            (false, 24) => {
+                i32::from_le_bytes([0, sample[0], sample[1], sample[2]]) as f32 / 2147483648.0
+            }
+            (false, _) => {
+                i32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]) as f32
+                    / 2147483648.0
+            }
+        })
+        .collect();
+
+    let channels = format.channels as usize;
+    Ok(Sound::new(
+        channels,
+        resample(&samples, channels, format.sample_rate),
+    ))
+}
+
+struct Format {
+    channels: u16,
+    sample_rate: u32,
+    bits: u16,
+    float: bool,
+}
+
+impl Format {
+    fn parse(body: &[u8]) -> Result<Self, String> {
+        if body.len() < 16 {
+            return Err("Truncated fmt chunk".to_string());
+        }
+        let tag = u16::from_le_bytes([body[0], body[1]]);
+        let channels = u16::from_le_bytes([body[2], body[3]]);
+        let sample_rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+        let bits = u16::from_le_bytes([body[14], body[15]]);
+
+        // 0xfffe is WAVE_FORMAT_EXTENSIBLE, whose sub format is taken on
+        // trust to match the bit depth.
+        let float = match (tag, bits) {
+            (1 | 0xfffe, 8 | 16 | 24 | 32) => false,
+            (3, 32) => true,
+            _ => return Err(format!("Unsupported WAV format {} with {} bits", tag, bits)),
+        };
+        if !(1..=2).contains(&channels) {
+            return Err(format!("Unsupported channel count {}", channels));
+        }
+        if sample_rate < MIN_SAMPLE_RATE {
+            return Err(format!(
+                "WAV sample rate {} is below {}",
+                sample_rate, MIN_SAMPLE_RATE
+            ));
+        }
+
+        Ok(Self {
+            channels,
+            sample_rate,
+            bits,
+            float,
+        })
+    }
+}
+
+/// Linear resampling of interleaved samples from `rate` to `SAMPLE_RATE`.
+fn resample(samples: &[f32], channels: usize, rate: u32) -> Vec<f32> {
+    if rate == SAMPLE_RATE {
+        return samples.to_vec();
+    }
+    let frames = samples.len() / channels;
+    if frames == 0 {
+        return Vec::new();
+    }
+
+    let step = rate as f64 / SAMPLE_RATE as f64;
+    let out_frames = (frames as f64 / step) as usize;
+    let mut out = Vec::with_capacity(out_frames * channels);
+    for i in 0..out_frames {
+        let position = i as f64 * step;
+        let frame = position as usize;
+        let next = (frame + 1).min(frames - 1);
+        let t = (position - frame as f64) as f32;
+        for channel in 0..channels {
+            let a = samples[frame * channels + channel];
+            let b = samples[next * channels + channel];
+            out.push(a + (b - a) * t);
+        }
+    }
+    out
+}
+
+/// Writes the header of a 16-bit stereo PCM WAV file at `SAMPLE_RATE`
+/// holding `frames` frames.
+pub fn write_header(writer: &mut impl Write, frames: u32) -> io::Result<()> {
+    let data_size = frames * 4;
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVEfmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&2u16.to_le_bytes())?;
+    writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&(SAMPLE_RATE * 4).to_le_bytes())?;
+    writer.write_all(&4u16.to_le_bytes())?;
+    writer.write_all(&16u16.to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())
+}
+
+/// Appends interleaved stereo samples as 16-bit PCM and updates the header
+/// so the file is valid after every call.
+pub fn append<W: Write + Seek>(
+    writer: &mut W,
+    frames: &mut u32,
+    samples: &[f32],
+) -> io::Result<()> {
+    writer.seek(SeekFrom::End(0))?;
+    let bytes: Vec<u8> = samples
+        .iter()
+        .flat_map(|sample| ((sample.clamp(-1.0, 1.0) * 32767.0) as i16).to_le_bytes())
+        .collect();
+    writer.write_all(&bytes)?;
+    *frames += (samples.len() / 2) as u32;
+
+    writer.seek(SeekFrom::Start(0))?;
+    write_header(writer, *frames)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// WAV file holding `samples` as 16-bit stereo at `sample_rate`.
+    fn file(sample_rate: u32, samples: &[f32]) -> Vec<u8> {
+        let mut file = io::Cursor::new(Vec::new());
+        write_header(&mut file, 0).unwrap();
+        append(&mut file, &mut 0, samples).unwrap();
+        let mut bytes = file.into_inner();
+        bytes[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn low_sample_rates_are_rejected() {
+        assert!(parse(&file(MIN_SAMPLE_RATE - 1, &[0.0, 0.0])).is_err());
+        assert!(parse(&file(1, &[0.0; 64])).is_err());
+    }
+
+    #[test]
+    fn resamples_to_the_mixer_rate() {
+        let sound = parse(&file(MIN_SAMPLE_RATE, &[0.5; 16])).unwrap();
+        let ratio = SAMPLE_RATE as f64 / MIN_SAMPLE_RATE as f64;
+        assert_eq!(sound.frames(), (8.0 * ratio) as usize);
+
+        let sound = parse(&file(SAMPLE_RATE, &[0.5, -0.5])).unwrap();
+        assert_eq!(sound.frames(), 1);
+    }
+
+    #[test]
+    fn bad_files_are_rejected() {
+        assert!(parse(b"RIFF").is_err());
+        let mut truncated = file(SAMPLE_RATE, &[0.0; 4]);
+        truncated.truncate(40);
+        assert!(parse(&truncated).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use tracing::{debug, trace, warn};
+use types::{DecodeError, SerdeType};
+
+/// Default limit of the payload bytes queued for one mod.
+pub const DEFAULT_MAX_BYTES: usize = 1024 * 1024;
+
+/// A message published on the bus. Payloads are opaque to the host, mods
+/// encode them with `types::SerdeType`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub topic: String,
+    pub sender: String,
+    pub payload: Vec<u8>,
+    /// Position in the global publish order.
+    pub sequence: u64,
+}
+
+impl Message {
+    pub fn decode<T: SerdeType>(&self) -> Result<T, DecodeError> {
+        T::de(&self.payload)
+    }
+}
+
+/// Host-side publish/subscribe bus between mods.
+///
+/// Every subscriber of a topic gets its own copy of a message in a bounded
+/// queue, except the sender. Messages come out of a queue in publish order;
+/// when a queue is full, by count or by payload bytes, the oldest messages
+/// are dropped. A payload larger than a whole queue isn't delivered.
+#[derive(Debug)]
+pub struct MessageBus {
+    /// Subscribed mods by topic.
+    topics: BTreeMap<String, BTreeSet<String>>,
+    queues: HashMap<String, VecDeque<Message>>,
+    capacity: usize,
+    max_bytes: usize,
+    next_sequence: u64,
+}
+
+impl Default for MessageBus {
+    fn default() -> Self {
+        Self::with_capacity(256)
+    }
+}
+
+impl MessageBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bus whose per-mod queues hold at most `capacity` messages.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_limits(capacity, DEFAULT_MAX_BYTES)
+    }
+
+    /// Bus whose per-mod queues hold at most `capacity` messages with at
+    /// most `max_bytes` of payload together.
+    pub fn with_limits(capacity: usize, max_bytes: usize) -> Self {
+        Self {
+            topics: BTreeMap::new(),
+            queues: HashMap::new(),
+            capacity: capacity.max(1),
+            max_bytes,
+            next_sequence: 0,
+        }
+    }
+
+    pub fn subscribe(&mut self, mod_id: &str, topic: &str) {
+        debug!("{} subscribed to {}", mod_id, topic);
+        self.topics
+            .entry(topic.to_string())
+            .or_default()
+            .insert(mod_id.to_string());
+    }
+
+    pub fn unsubscribe(&mut self, mod_id: &str, topic: &str) {
+        debug!("{} unsubscribed from {}", mod_id, topic);
+        if let Some(subscribers) = self.topics.get_mut(topic) {
+            subscribers.remove(mod_id);
+            if subscribers.is_empty() {
+                self.topics.remove(topic);
+            }
+        }
+    }
+
+    /// Queues a copy of the message for every subscriber of `topic`.
+    /// Returns the number of mods it was queued for.
+    pub fn publish(&mut self, sender: &str, topic: &str, payload: Vec<u8>) -> usize {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        let Some(subscribers) = self.topics.get(topic) else {
+            trace!(
+                sequence,
+                "{} published to {} without subscribers",
+                sender,
+                topic
+            );
+            return 0;
+        };
+        if payload.len() > self.max_bytes {
+            warn!(
+                "{} published {} bytes to {}, more than the {} a queue holds",
+                sender,
+                payload.len(),
+                topic,
+                self.max_bytes
+            );
+            return 0;
+        }
+
+        let mut delivered = 0;
+        for subscriber in subscribers.iter().filter(|id| *id != sender) {
+            let queue = self.queues.entry(subscriber.clone()).or_default();
+            let mut bytes: usize = queue.iter().map(|message| message.payload.len()).sum();
+            while queue.len() >= self.capacity || bytes + payload.len() > self.max_bytes {
+                let Some(dropped) = queue.pop_front() else {
+                    break;
+                };
+                bytes -= dropped.payload.len();
+                warn!(
+                    "Message queue of {} is full, dropped message {} on {}",
+                    subscriber, dropped.sequence, dropped.topic
+                );
+            }
+            queue.push_back(Message {
+                topic: topic.to_string(),
+                sender: sender.to_string(),
+                payload: payload.clone(),
+                sequence,
+            });
+            delivered += 1;
+        }
+        trace!(
+            sequence,
+            "{} published {} bytes to {} for {} subscribers",
+            sender,
+            payload.len(),
+            topic,
+            delivered
+        );
+
+        delivered
+    }
+
+    /// Takes the oldest message queued for `mod_id`.
+    pub fn poll(&mut self, mod_id: &str) -> Option<Message> {
+        self.queues.get_mut(mod_id)?.pop_front()
+    }
+
+    /// Takes every message queued for `mod_id`, oldest first.
+    pub fn drain(&mut self, mod_id: &str) -> Vec<Message> {
+        self.queues
+            .get_mut(mod_id)
+            .map(|queue| queue.drain(..).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn pending(&self, mod_id: &str) -> usize {
+        self.queues.get(mod_id).map_or(0, |queue| queue.len())
+    }
+
+    /// Drops the subscriptions and queue of an unloaded mod.
+    pub fn release_mod(&mut self, mod_id: &str) {
+        self.topics.retain(|_, subscribers| {
+            subscribers.remove(mod_id);
+            !subscribers.is_empty()
+        });
+        self.queues.remove(mod_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Position;
+
+    fn sequences(messages: &[Message]) -> Vec<u64> {
+        messages.iter().map(|message| message.sequence).collect()
+    }
+
+    #[test]
+    fn full_queues_drop_the_oldest() {
+        let mut bus = MessageBus::with_capacity(2);
+        bus.subscribe("b", "chat");
+        for payload in [1, 2, 3] {
+            assert_eq!(bus.publish("a", "chat", vec![payload]), 1);
+        }
+
+        let messages = bus.drain("b");
+        assert_eq!(sequences(&messages), [1, 2]);
+        assert_eq!(messages[0].payload, [2]);
+    }
+
+    #[test]
+    fn full_queues_drop_the_oldest_by_bytes() {
+        let mut bus = MessageBus::with_limits(16, 10);
+        bus.subscribe("b", "chat");
+        bus.publish("a", "chat", vec![0; 4]);
+        bus.publish("a", "chat", vec![0; 4]);
+        bus.publish("a", "chat", vec![0; 4]);
+        assert_eq!(sequences(&bus.drain("b")), [1, 2]);
+
+        // Too big for any queue, nothing is dropped for it.
+        bus.publish("a", "chat", vec![0; 4]);
+        assert_eq!(bus.publish("a", "chat", vec![0; 11]), 0);
+        assert_eq!(sequences(&bus.drain("b")), [3]);
+    }
+
+    #[test]
+    fn sequences_follow_the_global_publish_order() {
+        let mut bus = MessageBus::new();
+        bus.subscribe("b", "chat");
+        bus.subscribe("b", "moves");
+        bus.subscribe("c", "moves");
+
+        bus.publish("a", "chat", Vec::new());
+        bus.publish("a", "nobody", Vec::new());
+        bus.publish("b", "moves", Vec::new());
+        bus.publish("a", "moves", Vec::new());
+
+        // The sender doesn't get its own message, topics without
+        // subscribers still take a sequence number.
+        assert_eq!(sequences(&bus.drain("b")), [0, 3]);
+        assert_eq!(sequences(&bus.drain("c")), [2, 3]);
+        assert_eq!(bus.poll("c"), None);
+    }
+
+    #[test]
+    fn release_mod_drops_queue_and_subscriptions() {
+        let mut bus = MessageBus::new();
+        bus.subscribe("b", "chat");
+        bus.publish("a", "chat", Vec::new());
+        assert_eq!(bus.pending("b"), 1);
+
+        bus.release_mod("b");
+        assert_eq!(bus.pending("b"), 0);
+        assert_eq!(bus.publish("a", "chat", Vec::new()), 0);
+    }
+
+    #[test]
+    fn messages_decode_their_payload() {
+        let position = Position {
+            x: 1.0,
+            y: -2.5,
+            z: 3,
+        };
+        let message = Message {
+            topic: "moves".to_string(),
+            sender: "a".to_string(),
+            payload: position.se().unwrap(),
+            sequence: 0,
+        };
+        assert_eq!(message.decode::<Position>().unwrap(), position);
+
+        let garbage = Message {
+            payload: vec![0xc1],
+            ..message
+        };
+        assert!(garbage.decode::<Position>().is_err());
+    }
+}
@@ -0,0 +1,392 @@
+use crate::{
+    render::{BlendMode, DrawCommand, DrawItem, Layer, Rect},
+    storage::Storages,
+    world::{Entity, WorldStore},
+};
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::{debug, warn};
+use types::Position;
+
+/// Component type drawn by the built-in sprite system.
+pub const POSITION: &str = "Position";
+/// Side length of the squares the sprite system draws, in world units.
+const SPRITE_SIZE: f32 = 16.0;
+
+/// A system a mod registered, run by the host once per step through the
+/// mod's `run-system` export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct System {
+    pub owner: String,
+    pub name: String,
+    pub reads: BTreeSet<String>,
+    pub writes: BTreeSet<String>,
+}
+
+impl System {
+    /// Whether `self` has to run after `earlier` has been committed: it reads
+    /// or writes what `earlier` writes, or writes what `earlier` reads.
+    fn conflicts_with(&self, earlier: &System) -> bool {
+        earlier
+            .writes
+            .iter()
+            .any(|type_name| self.reads.contains(type_name) || self.writes.contains(type_name))
+            || self
+                .writes
+                .iter()
+                .any(|type_name| earlier.reads.contains(type_name))
+    }
+
+    fn can_read(&self, type_name: &str) -> bool {
+        self.reads.contains(type_name) || self.writes.contains(type_name)
+    }
+}
+
+/// Entities and systems on top of the `WorldStore`, which keeps the
+/// components. Entities belong to the mod that spawned them; components can
+/// be attached by any mod that owns their type.
+#[derive(Debug, Default)]
+pub struct Ecs {
+    next_entity: Entity,
+    /// Living entities with the mod that spawned them.
+    entities: BTreeMap<Entity, String>,
+    /// Systems in registration order.
+    systems: Vec<System>,
+    /// System being run, its read and write sets apply to world access.
+    running: Option<System>,
+}
+
+impl Ecs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self, mod_id: &str) -> Entity {
+        self.next_entity += 1;
+        let entity = self.next_entity;
+        self.entities.insert(entity, mod_id.to_string());
+        entity
+    }
+
+    /// Despawns an entity of `mod_id`. Its components are removed with the
+    /// next commit.
+    pub fn despawn(
+        &mut self,
+        world: &mut WorldStore,
+        mod_id: &str,
+        entity: Entity,
+    ) -> Result<(), String> {
+        match self.entities.get(&entity) {
+            None => return Err(format!("Entity {} doesn't exist", entity)),
+            Some(owner) if owner != mod_id => {
+                return Err(format!("Entity {} belongs to {}", entity, owner))
+            }
+            Some(_) => {}
+        }
+        self.entities.remove(&entity);
+        world.despawn(mod_id, entity);
+        Ok(())
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.contains_key(&entity)
+    }
+
+    /// Checks that `entity` was spawned here and is still alive, so mods
+    /// can't attach components to made up ids.
+    pub fn check_alive(&self, entity: Entity) -> Result<(), String> {
+        if self.is_alive(entity) {
+            Ok(())
+        } else {
+            Err(format!("Entity {} doesn't exist", entity))
+        }
+    }
+
+    /// Entities that have all of `type_names`, or every living entity if
+    /// none are given.
+    pub fn query(&self, world: &WorldStore, type_names: &[String]) -> Vec<Entity> {
+        let Some((first, rest)) = type_names.split_first() else {
+            return self.entities.keys().copied().collect();
+        };
+        let mut entities = world.entities_with(first);
+        for type_name in rest {
+            let with: BTreeSet<_> = world.entities_with(type_name).into_iter().collect();
+            entities.retain(|entity| with.contains(entity));
+        }
+        entities
+    }
+
+    /// Registers a system for `mod_id`, replacing one of the same name. The
+    /// mod has to own, or be able to claim, every type it writes.
+    pub fn register_system(
+        &mut self,
+        world: &mut WorldStore,
+        system: System,
+    ) -> Result<(), String> {
+        for type_name in &system.writes {
+            world.claim(&system.owner, type_name)?;
+        }
+        debug!(
+            "{} registered system {} reading {:?}, writing {:?}",
+            system.owner, system.name, system.reads, system.writes
+        );
+        match self
+            .systems
+            .iter_mut()
+            .find(|other| other.owner == system.owner && other.name == system.name)
+        {
+            Some(other) => *other = system,
+            None => self.systems.push(system),
+        }
+        Ok(())
+    }
+
+    /// Groups the systems into stages. Systems run in mod `order`, then in
+    /// registration order, and a system goes into the stage after the last
+    /// earlier system it conflicts with. Systems of mods missing from `order`
+    /// are left out.
+    pub fn schedule(&self, order: &[String]) -> Vec<Vec<System>> {
+        let mut systems: Vec<(usize, &System)> = self
+            .systems
+            .iter()
+            .filter_map(|system| {
+                let position = order.iter().position(|id| *id == system.owner)?;
+                Some((position, system))
+            })
+            .collect();
+        systems.sort_by_key(|(position, _)| *position);
+
+        let mut stages: Vec<Vec<System>> = Vec::new();
+        let mut placed: Vec<(usize, &System)> = Vec::new();
+        for (_, system) in systems {
+            let stage = placed
+                .iter()
+                .filter(|(_, earlier)| system.conflicts_with(earlier))
+                .map(|(stage, _)| stage + 1)
+                .max()
+                .unwrap_or(0);
+            if stages.len() <= stage {
+                stages.resize(stage + 1, Vec::new());
+            }
+            stages[stage].push(system.clone());
+            placed.push((stage, system));
+        }
+        stages
+    }
+
+    pub fn begin_system(&mut self, system: System) {
+        self.running = Some(system);
+    }
+
+    pub fn end_system(&mut self) {
+        self.running = None;
+    }
+
+    /// Checks a read against the read set of the running system, if any.
+    pub fn check_read(&self, type_name: &str) -> Result<(), String> {
+        match &self.running {
+            Some(system) if !system.can_read(type_name) => Err(format!(
+                "System {} doesn't declare reading {}",
+                system.name, type_name
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a write against the write set of the running system, if any.
+    pub fn check_write(&self, type_name: &str) -> Result<(), String> {
+        match &self.running {
+            Some(system) if !system.writes.contains(type_name) => Err(format!(
+                "System {} doesn't declare writing {}",
+                system.name, type_name
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drops the systems of an unloaded mod and despawns its entities.
+    pub fn release_mod(&mut self, world: &mut WorldStore, mod_id: &str) {
+        self.systems.retain(|system| system.owner != mod_id);
+        let entities: Vec<_> = self
+            .entities
+            .iter()
+            .filter(|(_, owner)| *owner == mod_id)
+            .map(|(entity, _)| *entity)
+            .collect();
+        for entity in entities {
+            self.entities.remove(&entity);
+            world.despawn(mod_id, entity);
+        }
+    }
+}
+
+/// Built-in system that draws a white square on the world layer for every
+/// entity with a `Position`, centered on it. `z` of the position orders the
+/// squares.
+pub fn draw_sprites(storages: &mut Storages) {
+    let view = storages.camera.get().view(*storages.window_size.get());
+    let mod_id = storages
+        .world
+        .owner(POSITION)
+        .unwrap_or_default()
+        .to_string();
+    for entity in storages.world.entities_with(POSITION) {
+        let Some(position) = storages.world.get_as::<Position>(entity, POSITION) else {
+            warn!("Position of entity {} can't be decoded", entity);
+            continue;
+        };
+        let rect = Rect::new(
+            position.x - SPRITE_SIZE / 2.0,
+            position.y - SPRITE_SIZE / 2.0,
+            SPRITE_SIZE,
+            SPRITE_SIZE,
+        );
+        let (rect, rotation) = rect.transformed(&view);
+        storages.draw_list.add(DrawItem {
+            layer: Layer::World,
+            z: position.z,
+            mod_id: mod_id.clone(),
+            target: None,
+            command: DrawCommand::Rect {
+                rect,
+                rotation,
+                color: (255, 255, 255, 255),
+                blend_mode: BlendMode::default(),
+            },
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system(owner: &str, name: &str, reads: &[&str], writes: &[&str]) -> System {
+        System {
+            owner: owner.to_string(),
+            name: name.to_string(),
+            reads: reads
+                .iter()
+                .map(|type_name| type_name.to_string())
+                .collect(),
+            writes: writes
+                .iter()
+                .map(|type_name| type_name.to_string())
+                .collect(),
+        }
+    }
+
+    fn names(stages: &[Vec<System>]) -> Vec<Vec<&str>> {
+        stages
+            .iter()
+            .map(|stage| stage.iter().map(|system| system.name.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn conflicts_are_shared_writes_or_reads_of_writes() {
+        let writes_position = system("a", "move", &[], &["Position"]);
+        let reads_position = system("a", "draw", &["Position"], &[]);
+        let writes_health = system("a", "heal", &[], &["Health"]);
+
+        assert!(reads_position.conflicts_with(&writes_position));
+        assert!(writes_position.conflicts_with(&reads_position));
+        assert!(writes_position.conflicts_with(&writes_position));
+        assert!(!reads_position.conflicts_with(&reads_position));
+        assert!(!writes_health.conflicts_with(&writes_position));
+    }
+
+    #[test]
+    fn schedule_stages_conflicting_systems() {
+        let mut ecs = Ecs::new();
+        let mut world = WorldStore::new();
+        let order = ["a".to_string(), "b".to_string()];
+        for system in [
+            system("b", "draw", &["Position"], &[]),
+            system("a", "move", &["Velocity"], &["Position"]),
+            system("a", "heal", &[], &["Health"]),
+            system("b", "push", &[], &["Velocity"]),
+            system("c", "unknown", &[], &[]),
+        ] {
+            ecs.register_system(&mut world, system).unwrap();
+        }
+
+        // Mod order comes first, "push" waits for "move" to read velocities
+        // and systems of mods missing from the order don't run.
+        assert_eq!(
+            names(&ecs.schedule(&order)),
+            [vec!["move", "heal"], vec!["draw", "push"]]
+        );
+    }
+
+    #[test]
+    fn systems_need_to_own_what_they_write() {
+        let mut ecs = Ecs::new();
+        let mut world = WorldStore::new();
+        ecs.register_system(&mut world, system("a", "move", &[], &["Position"]))
+            .unwrap();
+        assert!(ecs
+            .register_system(&mut world, system("b", "move", &[], &["Position"]))
+            .is_err());
+
+        ecs.begin_system(system("a", "move", &["Velocity"], &["Position"]));
+        assert!(ecs.check_read("Velocity").is_ok());
+        assert!(ecs.check_read("Position").is_ok());
+        assert!(ecs.check_read("Health").is_err());
+        assert!(ecs.check_write("Velocity").is_err());
+        ecs.end_system();
+        assert!(ecs.check_write("Velocity").is_ok());
+    }
+
+    #[test]
+    fn only_the_spawner_despawns() {
+        let mut ecs = Ecs::new();
+        let mut world = WorldStore::new();
+        let entity = ecs.spawn("a");
+        world.set("a", entity, "Position", vec![1]).unwrap();
+        world.commit();
+
+        assert!(ecs.despawn(&mut world, "b", entity).is_err());
+        assert!(ecs.check_alive(entity).is_ok());
+        ecs.despawn(&mut world, "a", entity).unwrap();
+        assert!(ecs.check_alive(entity).is_err());
+        assert!(ecs.despawn(&mut world, "a", entity).is_err());
+
+        // The components go with the next commit.
+        assert_eq!(world.get(entity, "Position"), Some(&[1][..]));
+        world.commit();
+        assert_eq!(world.get(entity, "Position"), None);
+    }
+
+    #[test]
+    fn made_up_entities_are_not_alive() {
+        let mut ecs = Ecs::new();
+        let entity = ecs.spawn("a");
+        assert!(ecs.check_alive(entity).is_ok());
+        assert!(ecs.check_alive(entity + 1).is_err());
+        assert!(ecs.check_alive(0).is_err());
+    }
+
+    #[test]
+    fn release_mod_drops_its_systems_and_entities() {
+        let mut ecs = Ecs::new();
+        let mut world = WorldStore::new();
+        let a = ecs.spawn("a");
+        let b = ecs.spawn("b");
+        world.set("a", a, "Position", vec![1]).unwrap();
+        world.set("a", b, "Position", vec![2]).unwrap();
+        ecs.register_system(&mut world, system("a", "move", &[], &["Position"]))
+            .unwrap();
+        ecs.register_system(&mut world, system("b", "draw", &["Position"], &[]))
+            .unwrap();
+        world.commit();
+
+        ecs.release_mod(&mut world, "a");
+        world.commit();
+
+        assert!(!ecs.is_alive(a));
+        assert!(ecs.is_alive(b));
+        assert_eq!(world.entities_with("Position"), [b]);
+        let order = ["a".to_string(), "b".to_string()];
+        assert_eq!(names(&ecs.schedule(&order)), [vec!["draw"]]);
+    }
+}
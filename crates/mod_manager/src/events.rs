@@ -0,0 +1,114 @@
+use crate::{bus::Message, input::MouseButton};
+
+/// Groups of events a mod can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Key,
+    Mouse,
+    WindowResized,
+    FocusLost,
+    ModLoaded,
+    ModUnloaded,
+    Gamepad,
+    Message,
+    ResourceChanged,
+    SettingsChanged,
+    Timer,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 11] = [
+        EventKind::Key,
+        EventKind::Mouse,
+        EventKind::WindowResized,
+        EventKind::FocusLost,
+        EventKind::ModLoaded,
+        EventKind::ModUnloaded,
+        EventKind::Gamepad,
+        EventKind::Message,
+        EventKind::ResourceChanged,
+        EventKind::SettingsChanged,
+        EventKind::Timer,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Key => "key",
+            EventKind::Mouse => "mouse",
+            EventKind::WindowResized => "window-resized",
+            EventKind::FocusLost => "focus-lost",
+            EventKind::ModLoaded => "mod-loaded",
+            EventKind::ModUnloaded => "mod-unloaded",
+            EventKind::Gamepad => "gamepad",
+            EventKind::Message => "message",
+            EventKind::ResourceChanged => "resource-changed",
+            EventKind::SettingsChanged => "settings-changed",
+            EventKind::Timer => "timer",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MouseEvent {
+    Moved { x: f32, y: f32 },
+    ButtonDown(MouseButton),
+    ButtonUp(MouseButton),
+    Wheel { x: f32, y: f32 },
+}
+
+/// Gamepad being plugged in or removed, by gamepad id.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HotplugEvent {
+    Connected(u32),
+    Disconnected(u32),
+}
+
+/// Event delivered to subscribed mods through their `on-event` export.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Key going down or up. Names are empty if the platform doesn't know
+    /// them. Repeats are not reported.
+    Key {
+        keycode: String,
+        scancode: String,
+        down: bool,
+    },
+    Mouse(MouseEvent),
+    WindowResized {
+        width: u32,
+        height: u32,
+    },
+    FocusLost,
+    ModLoaded(String),
+    ModUnloaded(String),
+    Gamepad(HotplugEvent),
+    /// Bus message for a mod subscribed to its topic. Only delivered to that
+    /// mod, see `ModManager::dispatch_messages`.
+    Message(Message),
+    /// Path of a resource that changed since a mod read it.
+    ResourceChanged(String),
+    /// Names of the settings of a mod that changed. Only delivered to that
+    /// mod, see `ModManager::dispatch_settings_changes`.
+    SettingsChanged(Vec<String>),
+    /// Timer that came due. Only delivered to the mod that set it, see
+    /// `ModManager::update_timers`.
+    Timer(u64),
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::Key { .. } => EventKind::Key,
+            Event::Mouse(_) => EventKind::Mouse,
+            Event::WindowResized { .. } => EventKind::WindowResized,
+            Event::FocusLost => EventKind::FocusLost,
+            Event::ModLoaded(_) => EventKind::ModLoaded,
+            Event::ModUnloaded(_) => EventKind::ModUnloaded,
+            Event::Gamepad(_) => EventKind::Gamepad,
+            Event::Message(_) => EventKind::Message,
+            Event::ResourceChanged(_) => EventKind::ResourceChanged,
+            Event::SettingsChanged(_) => EventKind::SettingsChanged,
+            Event::Timer(_) => EventKind::Timer,
+        }
+    }
+}
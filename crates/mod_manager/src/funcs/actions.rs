@@ -0,0 +1,371 @@
+use super::{
+    super::Storages,
+    input::{
+        gamepad_axis_type, gamepad_button_type, key_from_value, key_type, key_value,
+        mouse_button_type,
+    },
+    string_param,
+};
+use crate::{
+    actions::{ActionInfo, ActionMap, Binding, InputSource},
+    gamepad::{GamepadAxis, GamepadButton},
+    input::MouseButton,
+};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use utils::logging::*;
+use wasm_component_layer::{
+    Enum, Func, FuncType, Linker, LinkerInstance, List, ListType, Record, RecordType, Store, Value,
+    ValueType, Variant, VariantCase, VariantType,
+};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+fn input_source_type() -> VariantType {
+    VariantType::new(
+        None,
+        [
+            VariantCase::new("key", Some(ValueType::Variant(key_type()))),
+            VariantCase::new(
+                "mouse-button",
+                Some(ValueType::Variant(mouse_button_type())),
+            ),
+            VariantCase::new(
+                "gamepad-button",
+                Some(ValueType::Enum(gamepad_button_type())),
+            ),
+            VariantCase::new("gamepad-axis", Some(ValueType::Enum(gamepad_axis_type()))),
+        ],
+    )
+    .expect("Failed to create input source type")
+}
+
+fn binding_type() -> RecordType {
+    RecordType::new(
+        None,
+        [
+            ("source", ValueType::Variant(input_source_type())),
+            ("scale", ValueType::F32),
+        ],
+    )
+    .expect("Failed to create binding type")
+}
+
+fn action_info_type() -> RecordType {
+    RecordType::new(
+        None,
+        [
+            ("owner", ValueType::String),
+            ("name", ValueType::String),
+            (
+                "bindings",
+                ValueType::List(ListType::new(ValueType::Record(binding_type()))),
+            ),
+            ("rebound", ValueType::Bool),
+            (
+                "conflicts",
+                ValueType::List(ListType::new(ValueType::String)),
+            ),
+        ],
+    )
+    .expect("Failed to create action info type")
+}
+
+fn bindings_from_value(value: &Value) -> Vec<Binding> {
+    let Value::List(list) = value else {
+        panic!("Unexpected parameter type");
+    };
+    list.iter()
+        .map(|binding| {
+            let Value::Record(binding) = binding else {
+                panic!("Unexpected list element type");
+            };
+            let source = match binding.field("source") {
+                Some(Value::Variant(source)) => match (source.discriminant(), source.value()) {
+                    (0, Some(key)) => InputSource::Key(key_from_value(&key)),
+                    (1, Some(Value::Variant(button))) => {
+                        InputSource::MouseButton(MouseButton::ALL[button.discriminant()])
+                    }
+                    (2, Some(Value::Enum(button))) => {
+                        InputSource::GamepadButton(GamepadButton::ALL[button.discriminant()])
+                    }
+                    (3, Some(Value::Enum(axis))) => {
+                        InputSource::GamepadAxis(GamepadAxis::ALL[axis.discriminant()])
+                    }
+                    _ => panic!("Unexpected input source case"),
+                },
+                _ => panic!("Unexpected record field type"),
+            };
+            let scale = match binding.field("scale") {
+                Some(Value::F32(scale)) => scale,
+                _ => panic!("Unexpected record field type"),
+            };
+            Binding::new(source, scale)
+        })
+        .collect()
+}
+
+fn binding_value(binding: &Binding) -> Value {
+    let (case, payload) = match &binding.source {
+        InputSource::Key(key) => (0, key_value(key)),
+        InputSource::MouseButton(button) => (
+            1,
+            Value::Variant(
+                Variant::new(mouse_button_type(), *button as usize, None)
+                    .expect("Failed to create variant"),
+            ),
+        ),
+        InputSource::GamepadButton(button) => (
+            2,
+            Value::Enum(
+                Enum::new(gamepad_button_type(), *button as usize).expect("Failed to create enum"),
+            ),
+        ),
+        InputSource::GamepadAxis(axis) => (
+            3,
+            Value::Enum(
+                Enum::new(gamepad_axis_type(), *axis as usize).expect("Failed to create enum"),
+            ),
+        ),
+    };
+    let source =
+        Variant::new(input_source_type(), case, Some(payload)).expect("Failed to create variant");
+
+    Value::Record(
+        Record::new(
+            binding_type(),
+            [
+                ("source", Value::Variant(source)),
+                ("scale", Value::F32(binding.scale)),
+            ],
+        )
+        .expect("Failed to create record"),
+    )
+}
+
+fn action_info_value(info: &ActionInfo) -> Value {
+    let bindings = info.bindings.iter().map(binding_value).collect::<Vec<_>>();
+    let conflicts = info
+        .conflicts
+        .iter()
+        .map(|conflict| Value::String(conflict.as_str().into()))
+        .collect::<Vec<_>>();
+
+    Value::Record(
+        Record::new(
+            action_info_type(),
+            [
+                ("owner", Value::String(info.owner.as_str().into())),
+                ("name", Value::String(info.name.as_str().into())),
+                (
+                    "bindings",
+                    Value::List(
+                        List::new(ListType::new(ValueType::Record(binding_type())), bindings)
+                            .expect("Failed to create list"),
+                    ),
+                ),
+                ("rebound", Value::Bool(info.rebound)),
+                (
+                    "conflicts",
+                    Value::List(
+                        List::new(ListType::new(ValueType::String), conflicts)
+                            .expect("Failed to create list"),
+                    ),
+                ),
+            ],
+        )
+        .expect("Failed to create record"),
+    )
+}
+
+/// Defines a query on one of the calling mod's actions.
+fn define_action_query(
+    interface: &mut LinkerInstance,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+    name: &str,
+    result_type: ValueType,
+    query: fn(&ActionMap, &str, &str) -> Value,
+) -> Result<()> {
+    interface
+        .define_func(
+            name,
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], [result_type]),
+                move |_, params, results| {
+                    let action = string_param(&params[0]);
+
+                    results[0] = {
+                        let storages = storages.lock().unwrap();
+                        query(&storages.actions, storages.current_mod.get(), &action)
+                    };
+                    Ok(())
+                },
+            ),
+        )
+        .log()
+}
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/actions".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "register-action",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::String,
+                        ValueType::List(ListType::new(ValueType::Record(binding_type()))),
+                    ],
+                    [],
+                ),
+                move |_, params, _results| {
+                    let action = string_param(&params[0]);
+                    let bindings = bindings_from_value(&params[1]);
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.actions.register(&mod_id, &action, bindings);
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    define_action_query(
+        interface,
+        store,
+        storages.clone(),
+        "action-down",
+        ValueType::Bool,
+        |actions, owner, name| Value::Bool(actions.is_down(owner, name)),
+    )?;
+    define_action_query(
+        interface,
+        store,
+        storages.clone(),
+        "action-pressed",
+        ValueType::Bool,
+        |actions, owner, name| Value::Bool(actions.is_pressed(owner, name)),
+    )?;
+    define_action_query(
+        interface,
+        store,
+        storages.clone(),
+        "action-released",
+        ValueType::Bool,
+        |actions, owner, name| Value::Bool(actions.is_released(owner, name)),
+    )?;
+    define_action_query(
+        interface,
+        store,
+        storages.clone(),
+        "action-value",
+        ValueType::F32,
+        |actions, owner, name| Value::F32(actions.value(owner, name)),
+    )?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "list-actions",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [],
+                    [ValueType::List(ListType::new(ValueType::Record(
+                        action_info_type(),
+                    )))],
+                ),
+                move |_, _params, results| {
+                    let actions = {
+                        let storages = storages_clone.lock().unwrap();
+                        storages.actions.list()
+                    };
+
+                    results[0] = Value::List(
+                        List::new(
+                            ListType::new(ValueType::Record(action_info_type())),
+                            actions.iter().map(action_info_value),
+                        )
+                        .expect("Failed to create list"),
+                    );
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "rebind-action",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::String,
+                        ValueType::List(ListType::new(ValueType::Record(binding_type()))),
+                    ],
+                    [],
+                ),
+                move |_, params, _results| {
+                    let action = string_param(&params[0]);
+                    let bindings = bindings_from_value(&params[1]);
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages
+                            .actions
+                            .rebind(&mod_id, &action, bindings)
+                            .log_msg("Failed to rebind action")
+                            .ok();
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "reset-action",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], []),
+                move |_, params, _results| {
+                    let action = string_param(&params[0]);
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages
+                            .actions
+                            .reset(&mod_id, &action)
+                            .log_msg("Failed to reset action")
+                            .ok();
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use super::{super::Storages, f32_param, handle_result, handle_result_type, u32_param};
+use crate::audio::{wav, PlayOptions, Sound, Waveform};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use utils::logging::*;
+use wasm_component_layer::{EnumType, Func, FuncType, Linker, Store, Value, ValueType};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+fn waveform_type() -> EnumType {
+    EnumType::new(None, Waveform::ALL.iter().map(|waveform| waveform.name()))
+        .expect("Failed to create waveform type")
+}
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/audio".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "load-sound",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::String],
+                    [ValueType::Result(handle_result_type())],
+                ),
+                move |_, params, results| {
+                    let path = match &params[0] {
+                        Value::String(path) => path.to_string(),
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let handle = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages
+                            .vfs
+                            .read(&mod_id, &path)
+                            .and_then(|bytes| wav::parse(&bytes))
+                            .and_then(|sound| storages.audio.add_sound(&mod_id, sound))
+                    };
+
+                    results[0] = handle_result(handle)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "tone",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::Enum(waveform_type()),
+                        ValueType::F32,
+                        ValueType::F32,
+                    ],
+                    [ValueType::Result(handle_result_type())],
+                ),
+                move |_, params, results| {
+                    let waveform = match &params[0] {
+                        Value::Enum(waveform) => Waveform::ALL[waveform.discriminant()],
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let frequency = f32_param(&params[1]);
+                    let duration = f32_param(&params[2]);
+
+                    let sound = Sound::tone(waveform, frequency, duration);
+                    let handle = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.audio.add_sound(&mod_id, sound)
+                    };
+
+                    results[0] = handle_result(handle)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "noise",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::F32, ValueType::U32],
+                    [ValueType::Result(handle_result_type())],
+                ),
+                move |_, params, results| {
+                    let duration = f32_param(&params[0]);
+                    let seed = u32_param(&params[1]);
+
+                    let sound = Sound::noise(duration, seed);
+                    let handle = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.audio.add_sound(&mod_id, sound)
+                    };
+
+                    results[0] = handle_result(handle)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "free-sound",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::U32], []),
+                move |_, params, _results| {
+                    let sound = u32_param(&params[0]);
+
+                    let mut storages = storages_clone.lock().unwrap();
+                    let mod_id = storages.current_mod.get().clone();
+                    storages.audio.free_sound(&mod_id, sound);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "play",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::U32,
+                        ValueType::F32,
+                        ValueType::F32,
+                        ValueType::Bool,
+                    ],
+                    [ValueType::Result(handle_result_type())],
+                ),
+                move |_, params, results| {
+                    let sound = u32_param(&params[0]);
+                    let options = PlayOptions {
+                        volume: f32_param(&params[1]),
+                        pan: f32_param(&params[2]),
+                        looping: match params[3] {
+                            Value::Bool(looping) => looping,
+                            _ => panic!("Unexpected parameter type"),
+                        },
+                    };
+
+                    let voice = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.audio.play(&mod_id, sound, options)
+                    };
+
+                    results[0] = handle_result(voice)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "stop",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::U32], []),
+                move |_, params, _results| {
+                    let voice = u32_param(&params[0]);
+
+                    let mut storages = storages_clone.lock().unwrap();
+                    let mod_id = storages.current_mod.get().clone();
+                    storages.audio.stop(&mod_id, voice);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "is-playing",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::U32], [ValueType::Bool]),
+                move |_, params, results| {
+                    let voice = u32_param(&params[0]);
+
+                    results[0] =
+                        Value::Bool(storages_clone.lock().unwrap().audio.is_playing(voice));
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
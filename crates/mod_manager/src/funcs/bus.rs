@@ -0,0 +1,155 @@
+use super::{super::Storages, string_param};
+use crate::bus::Message;
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use utils::logging::*;
+use wasm_component_layer::{
+    Func, FuncType, Linker, List, ListType, OptionType, OptionValue, Record, RecordType, Store,
+    Value, ValueType,
+};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+pub(crate) fn message_type() -> RecordType {
+    RecordType::new(
+        None,
+        [
+            ("topic", ValueType::String),
+            ("sender", ValueType::String),
+            ("payload", ValueType::List(ListType::new(ValueType::U8))),
+        ],
+    )
+    .expect("Failed to create message type")
+}
+
+pub(crate) fn message_value(message: &Message) -> Result<Value> {
+    Ok(Value::Record(Record::new(
+        message_type(),
+        [
+            ("topic", Value::String(message.topic.as_str().into())),
+            ("sender", Value::String(message.sender.as_str().into())),
+            (
+                "payload",
+                Value::List(List::from(message.payload.as_slice())),
+            ),
+        ],
+    )?))
+}
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/bus".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "publish",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::String,
+                        ValueType::List(ListType::new(ValueType::U8)),
+                    ],
+                    [ValueType::U32],
+                ),
+                move |_, params, results| {
+                    let topic = string_param(&params[0]);
+                    let payload = match &params[1] {
+                        Value::List(list) => list.typed::<u8>()?.to_vec(),
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let delivered = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.bus.publish(&mod_id, &topic, payload)
+                    };
+
+                    results[0] = Value::U32(delivered as u32);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "subscribe-topic",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], []),
+                move |_, params, _results| {
+                    let topic = string_param(&params[0]);
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.bus.subscribe(&mod_id, &topic);
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "unsubscribe-topic",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], []),
+                move |_, params, _results| {
+                    let topic = string_param(&params[0]);
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.bus.unsubscribe(&mod_id, &topic);
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "poll-message",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [],
+                    [ValueType::Option(OptionType::new(ValueType::Record(
+                        message_type(),
+                    )))],
+                ),
+                move |_, _params, results| {
+                    let message = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.bus.poll(&mod_id)
+                    };
+
+                    let message = message.as_ref().map(message_value).transpose()?;
+                    results[0] = Value::Option(OptionValue::new(
+                        OptionType::new(ValueType::Record(message_type())),
+                        message,
+                    )?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use super::{
+    super::Storages, entity_param, string_param, strings_param, unit_result, unit_result_type,
+};
+use crate::ecs::System;
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use utils::logging::*;
+use wasm_component_layer::{
+    Func, FuncType, Linker, List, ListType, ResultType, ResultValue, Store, Value, ValueType,
+};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+fn entities_result_type() -> ResultType {
+    ResultType::new(
+        Some(ValueType::List(ListType::new(ValueType::U64))),
+        Some(ValueType::String),
+    )
+}
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/ecs".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "spawn",
+            Func::new(
+                &mut *store,
+                FuncType::new([], [ValueType::U64]),
+                move |_, _params, results| {
+                    let entity = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.ecs.spawn(&mod_id)
+                    };
+
+                    results[0] = Value::U64(entity);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "despawn",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::U64], [ValueType::Result(unit_result_type())]),
+                move |_, params, results| {
+                    let entity = entity_param(&params[0]);
+
+                    let despawned = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        let storages = &mut *storages;
+                        storages.ecs.despawn(&mut storages.world, &mod_id, entity)
+                    };
+
+                    results[0] = unit_result(despawned)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "alive",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::U64], [ValueType::Bool]),
+                move |_, params, results| {
+                    let entity = entity_param(&params[0]);
+
+                    results[0] = Value::Bool(storages_clone.lock().unwrap().ecs.is_alive(entity));
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "query",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::List(ListType::new(ValueType::String))],
+                    [ValueType::Result(entities_result_type())],
+                ),
+                move |_, params, results| {
+                    let type_names = strings_param(&params[0]);
+
+                    let entities = {
+                        let storages = storages_clone.lock().unwrap();
+                        type_names
+                            .iter()
+                            .try_for_each(|type_name| storages.ecs.check_read(type_name))
+                            .map(|_| storages.ecs.query(&storages.world, &type_names))
+                    };
+
+                    let entities = match entities {
+                        Ok(entities) => Ok(Some(Value::List(List::from(entities.as_slice())))),
+                        Err(e) => Err(Some(Value::String(e.into()))),
+                    };
+                    results[0] = Value::Result(ResultValue::new(entities_result_type(), entities)?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "register-system",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::String,
+                        ValueType::List(ListType::new(ValueType::String)),
+                        ValueType::List(ListType::new(ValueType::String)),
+                    ],
+                    [ValueType::Result(unit_result_type())],
+                ),
+                move |_, params, results| {
+                    let name = string_param(&params[0]);
+                    let reads = strings_param(&params[1]);
+                    let writes = strings_param(&params[2]);
+
+                    let registered = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let system = System {
+                            owner: storages.current_mod.get().clone(),
+                            name,
+                            reads: reads.into_iter().collect(),
+                            writes: writes.into_iter().collect(),
+                        };
+                        let storages = &mut *storages;
+                        storages.ecs.register_system(&mut storages.world, system)
+                    };
+
+                    results[0] = unit_result(registered)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
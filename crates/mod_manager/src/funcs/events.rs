@@ -0,0 +1,75 @@
+use super::super::Storages;
+use crate::events::EventKind;
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use utils::logging::*;
+use wasm_component_layer::{EnumType, Func, FuncType, Linker, Store, Value, ValueType};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+fn event_kind_type() -> EnumType {
+    EnumType::new(None, EventKind::ALL.iter().map(|kind| kind.name()))
+        .expect("Failed to create event kind type")
+}
+
+fn event_kind(value: &Value) -> EventKind {
+    match value {
+        Value::Enum(kind) => EventKind::ALL[kind.discriminant()],
+        _ => panic!("Unexpected parameter type"),
+    }
+}
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/events".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "subscribe",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::Enum(event_kind_type())], []),
+                move |_, params, _results| {
+                    let kind = event_kind(&params[0]);
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.subscriptions.entry(&mod_id).insert(kind);
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "unsubscribe",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::Enum(event_kind_type())], []),
+                move |_, params, _results| {
+                    let kind = event_kind(&params[0]);
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.subscriptions.entry(&mod_id).remove(&kind);
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use super::{super::Storages, bytes_result_type, string_param, unit_result, unit_result_type};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use utils::logging::*;
+use wasm_component_layer::{
+    Func, FuncType, Linker, List, ListType, ResultType, ResultValue, Store, Value, ValueType,
+};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+fn strings_result_type() -> ResultType {
+    ResultType::new(
+        Some(ValueType::List(ListType::new(ValueType::String))),
+        Some(ValueType::String),
+    )
+}
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/fs".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "read-file",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::String],
+                    [ValueType::Result(bytes_result_type())],
+                ),
+                move |_, params, results| {
+                    let path = string_param(&params[0]);
+
+                    let data = {
+                        let storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.vfs.read(&mod_id, &path)
+                    };
+
+                    results[0] = Value::Result(ResultValue::new(
+                        bytes_result_type(),
+                        data.map(|data| Some(Value::List(List::from(data.as_slice()))))
+                            .map_err(|e| Some(Value::String(e.into()))),
+                    )?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "write-file",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::String,
+                        ValueType::List(ListType::new(ValueType::U8)),
+                    ],
+                    [ValueType::Result(unit_result_type())],
+                ),
+                move |_, params, results| {
+                    let path = string_param(&params[0]);
+                    let data = match &params[1] {
+                        Value::List(list) => list.typed::<u8>()?.to_vec(),
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let written = {
+                        let storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.vfs.write(&mod_id, &path, &data)
+                    };
+
+                    results[0] = unit_result(written)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "remove-file",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], [ValueType::Result(unit_result_type())]),
+                move |_, params, results| {
+                    let path = string_param(&params[0]);
+
+                    let removed = {
+                        let storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.vfs.remove(&mod_id, &path)
+                    };
+
+                    results[0] = unit_result(removed)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "file-exists",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], [ValueType::Bool]),
+                move |_, params, results| {
+                    let path = string_param(&params[0]);
+
+                    let exists = {
+                        let storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.vfs.exists(&mod_id, &path)
+                    };
+
+                    results[0] = Value::Bool(exists);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "list-dir",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::String],
+                    [ValueType::Result(strings_result_type())],
+                ),
+                move |_, params, results| {
+                    let path = string_param(&params[0]);
+
+                    let names = {
+                        let storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.vfs.list(&mod_id, &path)
+                    };
+
+                    let names = match names {
+                        Ok(names) => Ok(Some(Value::List(List::new(
+                            ListType::new(ValueType::String),
+                            names.into_iter().map(|name| Value::String(name.into())),
+                        )?))),
+                        Err(e) => Err(Some(Value::String(e.into()))),
+                    };
+                    results[0] = Value::Result(ResultValue::new(strings_result_type(), names)?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
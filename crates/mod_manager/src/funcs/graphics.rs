@@ -1,10 +1,100 @@
-use super::super::Storages;
+use super::{
+    super::{
+        render::{
+            BlendMode, Camera, DrawCommand, DrawItem, Layer, Rect, RenderState, Transform,
+            MAX_TARGET_SIZE,
+        },
+        Storages,
+    },
+    handle_result, handle_result_type, point_type, point_value,
+};
 use anyhow::Result;
 use std::sync::{Arc, Mutex};
+use tracing::warn;
 use utils::logging::*;
-use wasm_component_layer::{Func, FuncType, Linker, Store, Value, ValueType};
+use wasm_component_layer::{
+    EnumType, Func, FuncType, Linker, Record, RecordType, Store, Value, ValueType,
+};
 use wasmi_runtime_layer::Engine as WasmEngine;
 
+fn camera_type() -> RecordType {
+    RecordType::new(
+        None,
+        [
+            ("x", ValueType::F32),
+            ("y", ValueType::F32),
+            ("zoom", ValueType::F32),
+            ("rotation", ValueType::F32),
+        ],
+    )
+    .expect("Failed to create camera type")
+}
+
+fn layer_type() -> EnumType {
+    EnumType::new(None, Layer::ALL.iter().map(|layer| layer.name()))
+        .expect("Failed to create layer type")
+}
+
+fn blend_mode_type() -> EnumType {
+    EnumType::new(None, BlendMode::ALL.iter().map(|mode| mode.name()))
+        .expect("Failed to create blend mode type")
+}
+
+fn f32_field(record: &Record, name: &str) -> f32 {
+    match record.field(name) {
+        Some(Value::F32(value)) => value,
+        _ => panic!("Unexpected record field type"),
+    }
+}
+
+/// Multiplies the current transform by `transform`, so it applies to
+/// everything drawn afterwards in local space.
+fn apply_transform(storages: &Arc<Mutex<Storages>>, transform: Transform) {
+    let mut storages = storages.lock().unwrap();
+    let state = storages.render_state();
+    state.transform = state.transform * transform;
+}
+
+/// Maps `rect` from `local` space through the calling mod's transform (and
+/// the camera, if its layer uses one and it draws to the screen) and adds
+/// the command built from it to the draw list.
+fn submit(
+    storages: &Arc<Mutex<Storages>>,
+    rect: Rect,
+    local: Transform,
+    command: impl FnOnce(Rect, f32, &RenderState) -> DrawCommand,
+) {
+    let mut storages = storages.lock().unwrap();
+    let camera = storages.camera.get().view(*storages.window_size.get());
+    let mod_id = storages.current_mod.get().clone();
+    let state = storages.render_state();
+    let view = if state.target.is_none() && state.layer.uses_camera() {
+        camera * state.transform * local
+    } else {
+        state.transform * local
+    };
+    let (rect, rotation) = rect.transformed(&view);
+    let item = DrawItem {
+        layer: state.layer,
+        z: state.z,
+        mod_id,
+        target: state.target,
+        command: command(rect, rotation, state),
+    };
+    storages.draw_list.add(item);
+}
+
+fn submit_rect(storages: &Arc<Mutex<Storages>>, rect: Rect, local: Transform) {
+    submit(storages, rect, local, |rect, rotation, state| {
+        DrawCommand::Rect {
+            rect,
+            rotation,
+            color: state.color,
+            blend_mode: state.blend_mode,
+        }
+    });
+}
+
 pub fn register(
     linker: &mut Linker,
     store: &mut Store<(), WasmEngine>,
@@ -31,32 +121,23 @@ pub fn register(
                 ),
                 move |_, params, _results| {
                     let x = match params[0] {
-                        Value::F32(x) => x as u32,
+                        Value::F32(x) => x,
                         _ => panic!("Unexpected parameter type"),
                     };
                     let y = match params[1] {
-                        Value::F32(y) => y as u32,
+                        Value::F32(y) => y,
                         _ => panic!("Unexpected parameter type"),
                     };
                     let w = match params[2] {
-                        Value::F32(w) => w as u32,
+                        Value::F32(w) => w,
                         _ => panic!("Unexpected parameter type"),
                     };
                     let h = match params[3] {
-                        Value::F32(h) => h as u32,
+                        Value::F32(h) => h,
                         _ => panic!("Unexpected parameter type"),
                     };
 
-                    let color = {
-                        let storages_ref = storages_clone.lock().unwrap();
-                        let storages = storages_ref;
-                        storages.color.get().clone()
-                    };
-                    {
-                        let mut storages = storages_clone.lock().unwrap();
-                        let textures = &mut storages.textures;
-                        textures.add(((x, y, w, h), color));
-                    }
+                    submit_rect(&storages_clone, Rect::new(x, y, w, h), Transform::IDENTITY);
 
                     Ok(())
                 },
@@ -99,13 +180,12 @@ pub fn register(
 
                     {
                         let mut storages = storages_clone.lock().unwrap();
-                        let color = &mut storages.color;
-                        color.set((
+                        storages.render_state().color = (
                             (r * 255.0) as u8,
                             (g * 255.0) as u8,
                             (b * 255.0) as u8,
                             (a * 255.0) as u8,
-                        ));
+                        );
                     }
 
                     Ok(())
@@ -144,14 +224,579 @@ pub fn register(
 
                     {
                         let mut storages = storages_clone.lock().unwrap();
-                        let color = &mut storages.color;
-                        color.set((r, g, b, a));
+                        storages.render_state().color = (r, g, b, a);
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "set-layer",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::Enum(layer_type())], []),
+                move |_, params, _results| {
+                    let layer = match &params[0] {
+                        Value::Enum(layer) => Layer::ALL[layer.discriminant()],
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        storages.render_state().layer = layer;
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "set-z",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::U32], []),
+                move |_, params, _results| {
+                    let z = match params[0] {
+                        Value::U32(z) => z,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        storages.render_state().z = z;
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "set-blend-mode",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::Enum(blend_mode_type())], []),
+                move |_, params, _results| {
+                    let blend_mode = match &params[0] {
+                        Value::Enum(mode) => BlendMode::ALL[mode.discriminant()],
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        storages.render_state().blend_mode = blend_mode;
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "set-line-width",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::F32], []),
+                move |_, params, _results| {
+                    let width = match params[0] {
+                        Value::F32(width) => width,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        storages.render_state().line_width = width;
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "draw-line",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::F32,
+                        ValueType::F32,
+                        ValueType::F32,
+                        ValueType::F32,
+                    ],
+                    [],
+                ),
+                move |_, params, _results| {
+                    let x1 = match params[0] {
+                        Value::F32(x1) => x1,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let y1 = match params[1] {
+                        Value::F32(y1) => y1,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let x2 = match params[2] {
+                        Value::F32(x2) => x2,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let y2 = match params[3] {
+                        Value::F32(y2) => y2,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let width = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        storages.render_state().line_width
+                    };
+                    // A line is a rect along the x axis, rotated into place.
+                    let (dx, dy) = (x2 - x1, y2 - y1);
+                    let local = Transform::translation(x1, y1) * Transform::rotation(dy.atan2(dx));
+                    submit_rect(
+                        &storages_clone,
+                        Rect::new(0.0, -width / 2.0, dx.hypot(dy), width),
+                        local,
+                    );
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "push-transform",
+            Func::new(
+                &mut *store,
+                FuncType::new([], []),
+                move |_, _params, _results| {
+                    let mut storages = storages_clone.lock().unwrap();
+                    let state = storages.render_state();
+                    state.transform_stack.push(state.transform);
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "pop-transform",
+            Func::new(
+                &mut *store,
+                FuncType::new([], []),
+                move |_, _params, _results| {
+                    let mut storages = storages_clone.lock().unwrap();
+                    let state = storages.render_state();
+                    state.transform = state.transform_stack.pop().unwrap_or_default();
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "translate",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::F32, ValueType::F32], []),
+                move |_, params, _results| {
+                    let x = match params[0] {
+                        Value::F32(x) => x,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let y = match params[1] {
+                        Value::F32(y) => y,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    apply_transform(&storages_clone, Transform::translation(x, y));
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "rotate",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::F32], []),
+                move |_, params, _results| {
+                    let radians = match params[0] {
+                        Value::F32(radians) => radians,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    apply_transform(&storages_clone, Transform::rotation(radians));
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "scale",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::F32, ValueType::F32], []),
+                move |_, params, _results| {
+                    let x = match params[0] {
+                        Value::F32(x) => x,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let y = match params[1] {
+                        Value::F32(y) => y,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    apply_transform(&storages_clone, Transform::scaling(x, y));
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "set-camera",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::Record(camera_type())], []),
+                move |_, params, _results| {
+                    let camera = match &params[0] {
+                        Value::Record(record) => Camera {
+                            x: f32_field(record, "x"),
+                            y: f32_field(record, "y"),
+                            zoom: f32_field(record, "zoom"),
+                            rotation: f32_field(record, "rotation"),
+                        },
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        storages.camera.set(camera);
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "get-camera",
+            Func::new(
+                &mut *store,
+                FuncType::new([], [ValueType::Record(camera_type())]),
+                move |_, _params, results| {
+                    let camera = {
+                        let storages = storages_clone.lock().unwrap();
+                        *storages.camera.get()
+                    };
+
+                    results[0] = Value::Record(
+                        Record::new(
+                            camera_type(),
+                            [
+                                ("x", Value::F32(camera.x)),
+                                ("y", Value::F32(camera.y)),
+                                ("zoom", Value::F32(camera.zoom)),
+                                ("rotation", Value::F32(camera.rotation)),
+                            ],
+                        )
+                        .expect("Failed to create record"),
+                    );
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "world-to-screen",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::F32, ValueType::F32],
+                    [ValueType::Tuple(point_type())],
+                ),
+                move |_, params, results| {
+                    let x = match params[0] {
+                        Value::F32(x) => x,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let y = match params[1] {
+                        Value::F32(y) => y,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let point = {
+                        let storages = storages_clone.lock().unwrap();
+                        storages
+                            .camera
+                            .get()
+                            .world_to_screen(*storages.window_size.get(), (x, y))
+                    };
+                    results[0] = point_value(point);
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "screen-to-world",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::F32, ValueType::F32],
+                    [ValueType::Tuple(point_type())],
+                ),
+                move |_, params, results| {
+                    let x = match params[0] {
+                        Value::F32(x) => x,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let y = match params[1] {
+                        Value::F32(y) => y,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let point = {
+                        let storages = storages_clone.lock().unwrap();
+                        storages
+                            .camera
+                            .get()
+                            .screen_to_world(*storages.window_size.get(), (x, y))
+                    };
+                    results[0] = point_value(point);
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "create-render-target",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::U32, ValueType::U32],
+                    [ValueType::Result(handle_result_type())],
+                ),
+                move |_, params, results| {
+                    let width = match params[0] {
+                        Value::U32(width) => width,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let height = match params[1] {
+                        Value::U32(height) => height,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let handle = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let owner = storages.current_mod.get().clone();
+                        storages.render_targets.create(&owner, width, height)
+                    };
+                    let handle = handle.ok_or_else(|| {
+                        format!(
+                            "Render target of {}x{} must be between 1x1 and {}x{}",
+                            width, height, MAX_TARGET_SIZE, MAX_TARGET_SIZE
+                        )
+                    });
+                    results[0] = handle_result(handle)?;
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "destroy-render-target",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::U32], []),
+                move |_, params, _results| {
+                    let handle = match params[0] {
+                        Value::U32(handle) => handle,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let mut storages = storages_clone.lock().unwrap();
+                    let owner = storages.current_mod.get().clone();
+                    if !storages.render_targets.is_owned_by(handle, &owner) {
+                        warn!("Mod {} can't destroy render target {}", owner, handle);
+                        return Ok(());
+                    }
+                    storages.render_targets.destroy(handle);
+                    let state = storages.render_state();
+                    if state.target == Some(handle) {
+                        state.target = None;
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "begin-target",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::U32], []),
+                move |_, params, _results| {
+                    let handle = match params[0] {
+                        Value::U32(handle) => handle,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let mut storages = storages_clone.lock().unwrap();
+                    let owner = storages.current_mod.get().clone();
+                    if !storages.render_targets.is_owned_by(handle, &owner) {
+                        warn!("Mod {} can't draw to render target {}", owner, handle);
+                        return Ok(());
                     }
+                    storages.render_targets.begin(handle);
+                    storages.render_state().target = Some(handle);
 
                     Ok(())
                 },
             ),
         )
         .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "end-target",
+            Func::new(
+                &mut *store,
+                FuncType::new([], []),
+                move |_, _params, _results| {
+                    let mut storages = storages_clone.lock().unwrap();
+                    storages.render_state().target = None;
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "draw-target",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::U32,
+                        ValueType::F32,
+                        ValueType::F32,
+                        ValueType::F32,
+                        ValueType::F32,
+                    ],
+                    [],
+                ),
+                move |_, params, _results| {
+                    let handle = match params[0] {
+                        Value::U32(handle) => handle,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let x = match params[1] {
+                        Value::F32(x) => x,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let y = match params[2] {
+                        Value::F32(y) => y,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let w = match params[3] {
+                        Value::F32(w) => w,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let h = match params[4] {
+                        Value::F32(h) => h,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    {
+                        let storages = storages_clone.lock().unwrap();
+                        let owner = storages.current_mod.get();
+                        if !storages.render_targets.is_owned_by(handle, owner) {
+                            warn!("Mod {} can't draw render target {}", owner, handle);
+                            return Ok(());
+                        }
+                    }
+                    submit(
+                        &storages_clone,
+                        Rect::new(x, y, w, h),
+                        Transform::IDENTITY,
+                        |rect, rotation, state| DrawCommand::Target {
+                            handle,
+                            rect,
+                            rotation,
+                            blend_mode: state.blend_mode,
+                        },
+                    );
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
     Ok(())
 }
@@ -1,10 +1,170 @@
-use super::super::Storages;
+use super::{super::Storages, point_value};
+use crate::{
+    gamepad::{GamepadAxis, GamepadButton, Gamepads, RumbleRequest},
+    input::{InputState, Key, MouseButton},
+};
 use anyhow::Result;
 use std::sync::{Arc, Mutex};
 use utils::logging::*;
-use wasm_component_layer::{Func, FuncType, Linker, Store, Tuple, TupleType, Value, ValueType};
+use wasm_component_layer::{
+    EnumType, Func, FuncType, Linker, LinkerInstance, List, ListType, OptionType, OptionValue,
+    Store, Tuple, TupleType, Value, ValueType, Variant, VariantCase, VariantType,
+};
 use wasmi_runtime_layer::Engine as WasmEngine;
 
+pub(super) fn key_type() -> VariantType {
+    VariantType::new(
+        None,
+        [
+            VariantCase::new("keycode", Some(ValueType::String)),
+            VariantCase::new("scancode", Some(ValueType::String)),
+        ],
+    )
+    .expect("Failed to create key type")
+}
+
+pub(super) fn mouse_button_type() -> VariantType {
+    VariantType::new(
+        None,
+        MouseButton::ALL
+            .iter()
+            .map(|button| VariantCase::new(button.name(), None)),
+    )
+    .expect("Failed to create mouse button type")
+}
+
+pub(super) fn gamepad_button_type() -> EnumType {
+    EnumType::new(None, GamepadButton::ALL.iter().map(|button| button.name()))
+        .expect("Failed to create gamepad button type")
+}
+
+pub(super) fn gamepad_axis_type() -> EnumType {
+    EnumType::new(None, GamepadAxis::ALL.iter().map(|axis| axis.name()))
+        .expect("Failed to create gamepad axis type")
+}
+
+pub(super) fn key_from_value(value: &Value) -> Key {
+    match value {
+        Value::Variant(key) => match (key.discriminant(), key.value()) {
+            (0, Some(Value::String(name))) => Key::keycode(&name),
+            (1, Some(Value::String(name))) => Key::scancode(&name),
+            _ => panic!("Unexpected key case"),
+        },
+        _ => panic!("Unexpected parameter type"),
+    }
+}
+
+pub(super) fn key_value(key: &Key) -> Value {
+    let (case, name) = match key {
+        Key::Keycode(name) => (0, name),
+        Key::Scancode(name) => (1, name),
+    };
+    Value::Variant(
+        Variant::new(key_type(), case, Some(Value::String(name.as_str().into())))
+            .expect("Failed to create variant"),
+    )
+}
+
+fn define_key_query(
+    interface: &mut LinkerInstance,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+    name: &str,
+    query: fn(&InputState, &Key) -> bool,
+) -> Result<()> {
+    interface
+        .define_func(
+            name,
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::Variant(key_type())], [ValueType::Bool]),
+                move |_, params, results| {
+                    let key = key_from_value(&params[0]);
+
+                    let result = {
+                        let storages = storages.lock().unwrap();
+                        query(&storages.input, &key)
+                    };
+
+                    results[0] = Value::Bool(result);
+                    Ok(())
+                },
+            ),
+        )
+        .log()
+}
+
+fn define_button_query(
+    interface: &mut LinkerInstance,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+    name: &str,
+    query: fn(&InputState, MouseButton) -> bool,
+) -> Result<()> {
+    interface
+        .define_func(
+            name,
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::Variant(mouse_button_type())], [ValueType::Bool]),
+                move |_, params, results| {
+                    let button = match &params[0] {
+                        Value::Variant(button) => MouseButton::ALL[button.discriminant()],
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let result = {
+                        let storages = storages.lock().unwrap();
+                        query(&storages.input, button)
+                    };
+
+                    results[0] = Value::Bool(result);
+                    Ok(())
+                },
+            ),
+        )
+        .log()
+}
+
+fn define_gamepad_button_query(
+    interface: &mut LinkerInstance,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+    name: &str,
+    query: fn(&Gamepads, u32, GamepadButton) -> bool,
+) -> Result<()> {
+    interface
+        .define_func(
+            name,
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::U32, ValueType::Enum(gamepad_button_type())],
+                    [ValueType::Bool],
+                ),
+                move |_, params, results| {
+                    let id = match params[0] {
+                        Value::U32(id) => id,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let button = match &params[1] {
+                        Value::Enum(button) => GamepadButton::ALL[button.discriminant()],
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let result = {
+                        let storages = storages.lock().unwrap();
+                        query(&storages.input.gamepads, id, button)
+                    };
+
+                    results[0] = Value::Bool(result);
+                    Ok(())
+                },
+            ),
+        )
+        .log()
+}
+
 pub fn register(
     linker: &mut Linker,
     store: &mut Store<(), WasmEngine>,
@@ -51,5 +211,279 @@ pub fn register(
         )
         .log()?;
 
+    define_key_query(
+        interface,
+        store,
+        storages.clone(),
+        "key-down",
+        InputState::is_key_down,
+    )?;
+    define_key_query(
+        interface,
+        store,
+        storages.clone(),
+        "key-pressed",
+        InputState::is_key_pressed,
+    )?;
+    define_key_query(
+        interface,
+        store,
+        storages.clone(),
+        "key-released",
+        InputState::is_key_released,
+    )?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "mouse-position",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [],
+                    [ValueType::Tuple(TupleType::new(
+                        None,
+                        vec![ValueType::F32, ValueType::F32],
+                    ))],
+                ),
+                move |_, _params, results| {
+                    let position = {
+                        let storages = storages_clone.lock().unwrap();
+                        storages.input.mouse_position()
+                    };
+
+                    results[0] = point_value(position);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    define_button_query(
+        interface,
+        store,
+        storages.clone(),
+        "mouse-button-down",
+        InputState::is_button_down,
+    )?;
+    define_button_query(
+        interface,
+        store,
+        storages.clone(),
+        "mouse-button-pressed",
+        InputState::is_button_pressed,
+    )?;
+    define_button_query(
+        interface,
+        store,
+        storages.clone(),
+        "mouse-button-released",
+        InputState::is_button_released,
+    )?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "mouse-wheel",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [],
+                    [ValueType::Tuple(TupleType::new(
+                        None,
+                        vec![ValueType::F32, ValueType::F32],
+                    ))],
+                ),
+                move |_, _params, results| {
+                    let wheel = {
+                        let storages = storages_clone.lock().unwrap();
+                        storages.input.wheel()
+                    };
+
+                    results[0] = point_value(wheel);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "text-input",
+            Func::new(
+                &mut *store,
+                FuncType::new([], [ValueType::List(ListType::new(ValueType::String))]),
+                move |_, _params, results| {
+                    let text = {
+                        let storages = storages_clone.lock().unwrap();
+                        storages
+                            .input
+                            .text()
+                            .iter()
+                            .map(|text| Value::String(text.as_str().into()))
+                            .collect::<Vec<_>>()
+                    };
+
+                    results[0] = Value::List(
+                        List::new(ListType::new(ValueType::String), text)
+                            .expect("Failed to create list"),
+                    );
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "gamepads",
+            Func::new(
+                &mut *store,
+                FuncType::new([], [ValueType::List(ListType::new(ValueType::U32))]),
+                move |_, _params, results| {
+                    let ids = {
+                        let storages = storages_clone.lock().unwrap();
+                        storages.input.gamepads.connected()
+                    };
+
+                    results[0] = Value::List(List::from(ids.as_slice()));
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "gamepad-name",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::U32],
+                    [ValueType::Option(OptionType::new(ValueType::String))],
+                ),
+                move |_, params, results| {
+                    let id = match params[0] {
+                        Value::U32(id) => id,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let name = {
+                        let storages = storages_clone.lock().unwrap();
+                        storages
+                            .input
+                            .gamepads
+                            .name(id)
+                            .map(|name| Value::String(name.into()))
+                    };
+
+                    results[0] = Value::Option(
+                        OptionValue::new(OptionType::new(ValueType::String), name)
+                            .expect("Failed to create option"),
+                    );
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    define_gamepad_button_query(
+        interface,
+        store,
+        storages.clone(),
+        "gamepad-button-down",
+        Gamepads::is_button_down,
+    )?;
+    define_gamepad_button_query(
+        interface,
+        store,
+        storages.clone(),
+        "gamepad-button-pressed",
+        Gamepads::is_button_pressed,
+    )?;
+    define_gamepad_button_query(
+        interface,
+        store,
+        storages.clone(),
+        "gamepad-button-released",
+        Gamepads::is_button_released,
+    )?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "gamepad-axis-value",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::U32, ValueType::Enum(gamepad_axis_type())],
+                    [ValueType::F32],
+                ),
+                move |_, params, results| {
+                    let id = match params[0] {
+                        Value::U32(id) => id,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let axis = match &params[1] {
+                        Value::Enum(axis) => GamepadAxis::ALL[axis.discriminant()],
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let value = {
+                        let storages = storages_clone.lock().unwrap();
+                        storages.input.gamepads.axis(id, axis)
+                    };
+
+                    results[0] = Value::F32(value);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "gamepad-rumble",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::U32,
+                        ValueType::F32,
+                        ValueType::F32,
+                        ValueType::U32,
+                    ],
+                    [],
+                ),
+                move |_, params, _results| {
+                    let strength = |value: &Value| match value {
+                        Value::F32(strength) => strength.clamp(0.0, 1.0),
+                        _ => panic!("Unexpected parameter type"),
+                    };
+                    let request = match (&params[0], &params[3]) {
+                        (Value::U32(id), Value::U32(duration_ms)) => RumbleRequest {
+                            id: *id,
+                            low_frequency: strength(&params[1]),
+                            high_frequency: strength(&params[2]),
+                            duration_ms: *duration_ms,
+                        },
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    {
+                        let mut storages = storages_clone.lock().unwrap();
+                        storages.input.gamepads.request_rumble(request);
+                    }
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
     Ok(())
 }
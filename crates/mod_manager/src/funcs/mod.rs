@@ -1,23 +1,142 @@
+pub mod actions;
+pub mod audio;
+pub mod bus;
+pub mod ecs;
+pub mod events;
+pub mod fs;
 pub mod graphics;
 pub mod input;
+pub mod random;
+pub mod resources;
+pub mod saves;
+pub mod services;
+pub mod settings;
+pub mod time;
 pub mod util_funcs;
+pub mod world;
 
-use super::Storages;
+use super::{registry::ModRegistry, Storages};
 use anyhow::Result;
 use std::sync::{Arc, Mutex};
 use utils::logging::*;
-use wasm_component_layer::{Linker, Store};
+use wasm_component_layer::{
+    Linker, ListType, ResultType, ResultValue, Store, Tuple, TupleType, Value, ValueType,
+};
 use wasmi_runtime_layer::Engine as WasmEngine;
 
 pub fn register(
     linker: &mut Linker,
     store: &mut Store<(), WasmEngine>,
     storages: Arc<Mutex<Storages>>,
+    registry: Arc<Mutex<ModRegistry>>,
 ) -> Result<()> {
+    actions::register(linker, store, storages.clone())
+        .log_msg("Failed to register actions funcs")?;
+    audio::register(linker, store, storages.clone()).log_msg("Failed to register audio funcs")?;
+    bus::register(linker, store, storages.clone()).log_msg("Failed to register bus funcs")?;
+    ecs::register(linker, store, storages.clone()).log_msg("Failed to register ecs funcs")?;
+    events::register(linker, store, storages.clone()).log_msg("Failed to register events funcs")?;
+    fs::register(linker, store, storages.clone()).log_msg("Failed to register fs funcs")?;
     graphics::register(linker, store, storages.clone())
         .log_msg("Failed to register storage funcs")?;
     input::register(linker, store, storages.clone())?;
+    random::register(linker, store, storages.clone()).log_msg("Failed to register random funcs")?;
+    resources::register(linker, store, storages.clone())
+        .log_msg("Failed to register resources funcs")?;
+    saves::register(linker, store, storages.clone()).log_msg("Failed to register saves funcs")?;
+    services::register(linker, store, storages.clone(), registry)
+        .log_msg("Failed to register services funcs")?;
+    settings::register(linker, store, storages.clone())
+        .log_msg("Failed to register settings funcs")?;
+    time::register(linker, store, storages.clone()).log_msg("Failed to register time funcs")?;
     util_funcs::register(linker, store, storages.clone())
         .log_msg("Failed to register utils funcs")?;
+    world::register(linker, store, storages.clone()).log_msg("Failed to register world funcs")?;
     Ok(())
 }
+
+pub(super) fn bytes_result_type() -> ResultType {
+    ResultType::new(
+        Some(ValueType::List(ListType::new(ValueType::U8))),
+        Some(ValueType::String),
+    )
+}
+
+pub(super) fn entity_param(value: &Value) -> u64 {
+    match value {
+        Value::U64(entity) => *entity,
+        _ => panic!("Unexpected parameter type"),
+    }
+}
+
+pub(super) fn f32_param(value: &Value) -> f32 {
+    match value {
+        Value::F32(value) => *value,
+        _ => panic!("Unexpected parameter type"),
+    }
+}
+
+pub(super) fn handle_result(result: Result<u32, String>) -> Result<Value> {
+    Ok(Value::Result(ResultValue::new(
+        handle_result_type(),
+        result
+            .map(|handle| Some(Value::U32(handle)))
+            .map_err(|e| Some(Value::String(e.into()))),
+    )?))
+}
+
+pub(super) fn handle_result_type() -> ResultType {
+    ResultType::new(Some(ValueType::U32), Some(ValueType::String))
+}
+
+pub(super) fn point_type() -> TupleType {
+    TupleType::new(None, vec![ValueType::F32, ValueType::F32])
+}
+
+pub(super) fn point_value(point: (f32, f32)) -> Value {
+    Value::Tuple(
+        Tuple::new(point_type(), vec![Value::F32(point.0), Value::F32(point.1)])
+            .expect("Failed to create tuple"),
+    )
+}
+
+pub(super) fn s32_param(value: &Value) -> i32 {
+    match value {
+        Value::S32(value) => *value,
+        _ => panic!("Unexpected parameter type"),
+    }
+}
+
+pub(super) fn string_param(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.to_string(),
+        _ => panic!("Unexpected parameter type"),
+    }
+}
+
+pub(super) fn strings_param(value: &Value) -> Vec<String> {
+    match value {
+        Value::List(list) => list.iter().map(|value| string_param(&value)).collect(),
+        _ => panic!("Unexpected parameter type"),
+    }
+}
+
+pub(super) fn u32_param(value: &Value) -> u32 {
+    match value {
+        Value::U32(value) => *value,
+        _ => panic!("Unexpected parameter type"),
+    }
+}
+
+pub(super) fn unit_result(result: Result<(), String>) -> Result<Value> {
+    Ok(Value::Result(ResultValue::new(
+        unit_result_type(),
+        result
+            .map(|_| None)
+            .map_err(|e| Some(Value::String(e.into()))),
+    )?))
+}
+
+pub(super) fn unit_result_type() -> ResultType {
+    ResultType::new(None, Some(ValueType::String))
+}
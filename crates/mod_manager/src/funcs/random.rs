@@ -0,0 +1,72 @@
+use super::{super::Storages, s32_param};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use utils::logging::*;
+use wasm_component_layer::{Func, FuncType, Linker, Store, Value, ValueType};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/random".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "random-u32",
+            Func::new(
+                &mut *store,
+                FuncType::new([], [ValueType::U32]),
+                move |_, _, results| {
+                    let mut storages = storages_clone.lock().unwrap();
+                    let mod_id = storages.current_mod.get().clone();
+                    results[0] = Value::U32(storages.random.next_u32(&mod_id));
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "random-range",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::S32, ValueType::S32], [ValueType::S32]),
+                move |_, params, results| {
+                    let min = s32_param(&params[0]);
+                    let max = s32_param(&params[1]);
+
+                    let mut storages = storages_clone.lock().unwrap();
+                    let mod_id = storages.current_mod.get().clone();
+                    results[0] = Value::S32(storages.random.range(&mod_id, min, max));
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "random-f32",
+            Func::new(
+                &mut *store,
+                FuncType::new([], [ValueType::F32]),
+                move |_, _, results| {
+                    let mut storages = storages_clone.lock().unwrap();
+                    let mod_id = storages.current_mod.get().clone();
+                    results[0] = Value::F32(storages.random.next_f32(&mod_id));
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use super::{super::Storages, bytes_result_type, string_param};
+use crate::resources::Conflict;
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use utils::logging::*;
+use wasm_component_layer::{
+    Func, FuncType, Linker, List, ListType, OptionType, OptionValue, Record, RecordType,
+    ResultValue, Store, Value, ValueType,
+};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+fn conflict_type() -> RecordType {
+    RecordType::new(
+        None,
+        [
+            ("path", ValueType::String),
+            ("layers", ValueType::List(ListType::new(ValueType::String))),
+        ],
+    )
+    .expect("Failed to create resource conflict type")
+}
+
+fn conflict_value(conflict: &Conflict) -> Value {
+    let layers = conflict
+        .layers
+        .iter()
+        .map(|layer| Value::String(layer.name().into()))
+        .collect::<Vec<_>>();
+
+    Value::Record(
+        Record::new(
+            conflict_type(),
+            [
+                ("path", Value::String(conflict.path.as_str().into())),
+                (
+                    "layers",
+                    Value::List(
+                        List::new(ListType::new(ValueType::String), layers)
+                            .expect("Failed to create list"),
+                    ),
+                ),
+            ],
+        )
+        .expect("Failed to create record"),
+    )
+}
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/resources".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "read-resource",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::String],
+                    [ValueType::Result(bytes_result_type())],
+                ),
+                move |_, params, results| {
+                    let path = string_param(&params[0]);
+
+                    let data = storages_clone.lock().unwrap().resources.read(&path);
+
+                    results[0] = Value::Result(ResultValue::new(
+                        bytes_result_type(),
+                        data.map(|data| Some(Value::List(List::from(data.as_slice()))))
+                            .map_err(|e| Some(Value::String(e.into()))),
+                    )?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "resource-provider",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::String],
+                    [ValueType::Option(OptionType::new(ValueType::String))],
+                ),
+                move |_, params, results| {
+                    let path = string_param(&params[0]);
+
+                    let provider = storages_clone.lock().unwrap().resources.provider(&path);
+
+                    results[0] = Value::Option(OptionValue::new(
+                        OptionType::new(ValueType::String),
+                        provider.map(|provider| Value::String(provider.into())),
+                    )?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "resource-conflicts",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [],
+                    [ValueType::List(ListType::new(ValueType::Record(
+                        conflict_type(),
+                    )))],
+                ),
+                move |_, _, results| {
+                    let conflicts = storages_clone.lock().unwrap().resources.conflicts();
+
+                    results[0] = Value::List(List::new(
+                        ListType::new(ValueType::Record(conflict_type())),
+                        conflicts.iter().map(conflict_value).collect::<Vec<_>>(),
+                    )?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
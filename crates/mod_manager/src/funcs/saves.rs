@@ -0,0 +1,229 @@
+use super::{super::Storages, string_param, unit_result, unit_result_type};
+use crate::saves::{Saved, SlotRequest};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use utils::logging::*;
+use wasm_component_layer::{
+    Func, FuncType, Linker, List, ListType, OptionType, OptionValue, Record, RecordType,
+    ResultType, ResultValue, Store, Value, ValueType,
+};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+fn saved_type() -> RecordType {
+    RecordType::new(
+        None,
+        [
+            ("version", ValueType::String),
+            ("data", ValueType::List(ListType::new(ValueType::U8))),
+        ],
+    )
+    .expect("Failed to create saved type")
+}
+
+fn load_result_type() -> ResultType {
+    ResultType::new(
+        Some(ValueType::Option(OptionType::new(ValueType::Record(
+            saved_type(),
+        )))),
+        Some(ValueType::String),
+    )
+}
+
+fn saved_value(saved: Option<Saved>) -> Result<Value> {
+    let saved = saved
+        .map(|saved| {
+            Record::new(
+                saved_type(),
+                [
+                    ("version", Value::String(saved.version.into())),
+                    ("data", Value::List(List::from(saved.data.as_slice()))),
+                ],
+            )
+            .map(Value::Record)
+        })
+        .transpose()?;
+    Ok(Value::Option(OptionValue::new(
+        OptionType::new(ValueType::Record(saved_type())),
+        saved,
+    )?))
+}
+
+fn strings_value(strings: Vec<String>) -> Result<Value> {
+    Ok(Value::List(List::new(
+        ListType::new(ValueType::String),
+        strings
+            .into_iter()
+            .map(|string| Value::String(string.into())),
+    )?))
+}
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/saves".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "save",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::String,
+                        ValueType::List(ListType::new(ValueType::U8)),
+                    ],
+                    [ValueType::Result(unit_result_type())],
+                ),
+                move |_, params, results| {
+                    let key = string_param(&params[0]);
+                    let data = match &params[1] {
+                        Value::List(list) => list.typed::<u8>()?.to_vec(),
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let saved = {
+                        let storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.saves.save(&mod_id, &key, data)
+                    };
+
+                    results[0] = unit_result(saved)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "load",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], [ValueType::Result(load_result_type())]),
+                move |_, params, results| {
+                    let key = string_param(&params[0]);
+
+                    let loaded = {
+                        let storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.saves.load(&mod_id, &key)
+                    };
+
+                    let loaded = match loaded {
+                        Ok(saved) => Ok(Some(saved_value(saved)?)),
+                        Err(e) => Err(Some(Value::String(e.into()))),
+                    };
+                    results[0] = Value::Result(ResultValue::new(load_result_type(), loaded)?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "delete",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], [ValueType::Result(unit_result_type())]),
+                move |_, params, results| {
+                    let key = string_param(&params[0]);
+
+                    let deleted = {
+                        let storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.saves.delete(&mod_id, &key)
+                    };
+
+                    results[0] = unit_result(deleted)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "list-keys",
+            Func::new(
+                &mut *store,
+                FuncType::new([], [ValueType::List(ListType::new(ValueType::String))]),
+                move |_, _params, results| {
+                    let keys = {
+                        let storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.saves.keys(&mod_id)
+                    };
+
+                    results[0] = strings_value(keys)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "save-slot",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], []),
+                move |_, params, _results| {
+                    let slot = string_param(&params[0]);
+
+                    let mut storages = storages_clone.lock().unwrap();
+                    storages.saves.requests.push(SlotRequest::Save(slot));
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "load-slot",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], []),
+                move |_, params, _results| {
+                    let slot = string_param(&params[0]);
+
+                    let mut storages = storages_clone.lock().unwrap();
+                    storages.saves.requests.push(SlotRequest::Load(slot));
+
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "list-slots",
+            Func::new(
+                &mut *store,
+                FuncType::new([], [ValueType::List(ListType::new(ValueType::String))]),
+                move |_, _params, results| {
+                    let slots = storages_clone.lock().unwrap().saves.slots();
+
+                    results[0] = strings_value(slots)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
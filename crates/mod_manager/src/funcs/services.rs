@@ -0,0 +1,309 @@
+use super::{
+    super::{registry::ModRegistry, Storages},
+    bytes_result_type, string_param, unit_result, unit_result_type,
+};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, debug_span};
+use utils::logging::*;
+use wasm_component_layer::{
+    Func, FuncType, Linker, List, ListType, ResultValue, Store, Value, ValueType,
+};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+/// Runs `method` of `service` for the current mod. Anything that goes wrong,
+/// including the provider trapping, is returned to the caller as an error.
+fn call_service(
+    storages: &Mutex<Storages>,
+    registry: &Mutex<ModRegistry>,
+    service: &str,
+    method: &str,
+    request: &[u8],
+) -> Result<Vec<u8>, String> {
+    let caller = storages.lock().unwrap().current_mod.get().clone();
+    let span = debug_span!("call_service", caller = caller.as_str(), service, method);
+    let _guard = span.enter();
+
+    let (provider, mod_handle) = {
+        let registry = registry.lock().unwrap();
+        let provider = registry
+            .service_provider(service)
+            .ok_or_else(|| format!("No mod provides service {}", service))?
+            .to_string();
+        if !registry.dependencies(&caller).contains(&provider) {
+            return Err(format!(
+                "{} has to depend on {} to call service {}",
+                caller, provider, service
+            ));
+        }
+        let mod_handle = registry
+            .get_mod(&provider)
+            .ok_or_else(|| format!("Mod {} isn't loaded", provider))?;
+        (provider, mod_handle)
+    };
+
+    let Some(mut mod_instance) = mod_handle.try_lock() else {
+        return Err(format!(
+            "Mod {} is already running, service {} can't be called reentrantly",
+            provider, service
+        ));
+    };
+    debug!("Calling {} on {}", method, provider);
+    storages.lock().unwrap().current_mod.set(provider.clone());
+    let result = mod_instance
+        .handle_call(service, method, request)
+        .unwrap_or_else(|e| Err(format!("Mod {} failed: {}", provider, e)));
+    drop(mod_instance);
+    storages.lock().unwrap().current_mod.set(caller);
+
+    result
+}
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+    registry: Arc<Mutex<ModRegistry>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/services".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    let registry_clone = registry.clone();
+    interface
+        .define_func(
+            "provide-service",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], [ValueType::Result(unit_result_type())]),
+                move |_, params, results| {
+                    let service = string_param(&params[0]);
+
+                    let mod_id = storages_clone.lock().unwrap().current_mod.get().clone();
+                    let provided = registry_clone
+                        .lock()
+                        .unwrap()
+                        .provide_service(&mod_id, &service);
+
+                    results[0] = unit_result(provided)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    let registry_clone = registry.clone();
+    interface
+        .define_func(
+            "call-service",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::String,
+                        ValueType::String,
+                        ValueType::List(ListType::new(ValueType::U8)),
+                    ],
+                    [ValueType::Result(bytes_result_type())],
+                ),
+                move |_, params, results| {
+                    let service = string_param(&params[0]);
+                    let method = string_param(&params[1]);
+                    let request = match &params[2] {
+                        Value::List(list) => list.typed::<u8>()?.to_vec(),
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let response = call_service(
+                        &storages_clone,
+                        &registry_clone,
+                        &service,
+                        &method,
+                        &request,
+                    );
+
+                    results[0] = Value::Result(ResultValue::new(
+                        bytes_result_type(),
+                        response
+                            .map(|response| Some(Value::List(List::from(response.as_slice()))))
+                            .map_err(|e| Some(Value::String(e.into()))),
+                    )?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{events::Event, ModContext, ModInfo, ModInterface, ModManifest};
+    use anyhow::Error;
+
+    /// Host-side mod that answers with the mod it runs as, or passes the
+    /// call on to `forward_to` if set.
+    struct Provider {
+        storages: Arc<Mutex<Storages>>,
+        registry: Arc<Mutex<ModRegistry>>,
+        forward_to: Option<&'static str>,
+    }
+
+    impl ModInterface for Provider {
+        fn call_info(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_info(&self) -> ModInfo {
+            ModInfo::default()
+        }
+
+        fn init(&mut self, _context: ModContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn update(&mut self, _delta_time: f32) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn draw(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn on_event(&mut self, _event: &Event) -> Result<bool, Error> {
+            Ok(false)
+        }
+
+        fn handle_call(
+            &mut self,
+            _service: &str,
+            method: &str,
+            request: &[u8],
+        ) -> Result<Result<Vec<u8>, String>, Error> {
+            Ok(match self.forward_to {
+                Some(service) => {
+                    call_service(&self.storages, &self.registry, service, method, request)
+                }
+                None => Ok(self
+                    .storages
+                    .lock()
+                    .unwrap()
+                    .current_mod
+                    .get()
+                    .as_bytes()
+                    .to_vec()),
+            })
+        }
+
+        fn save_section(&mut self) -> Result<Option<Vec<u8>>, Error> {
+            Ok(None)
+        }
+
+        fn load_section(&mut self, _data: &[u8]) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn migrate_section(
+            &mut self,
+            _from_version: &str,
+            data: &[u8],
+        ) -> Result<Result<Vec<u8>, String>, Error> {
+            Ok(Ok(data.to_vec()))
+        }
+
+        fn run_system(&mut self, _name: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn shutdown(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    /// Registers mods by id with their dependencies, the service they
+    /// provide and where they forward calls to.
+    fn setup(
+        mods: &[(&str, &[&str], &str, Option<&'static str>)],
+    ) -> (Arc<Mutex<Storages>>, Arc<Mutex<ModRegistry>>) {
+        let storages = Arc::new(Mutex::new(Storages::new()));
+        let registry = Arc::new(Mutex::new(ModRegistry::new()));
+        for (id, dependencies, service, forward_to) in mods {
+            let provider = Provider {
+                storages: storages.clone(),
+                registry: registry.clone(),
+                forward_to: *forward_to,
+            };
+            let manifest = ModManifest {
+                dependencies: dependencies.iter().map(|id| id.to_string()).collect(),
+                ..Default::default()
+            };
+            let mut registry = registry.lock().unwrap();
+            registry
+                .register_mod(id, Box::new(provider), manifest, [0; 32])
+                .unwrap();
+            registry.provide_service(id, service).unwrap();
+        }
+        (storages, registry)
+    }
+
+    fn call_as(
+        storages: &Mutex<Storages>,
+        registry: &Mutex<ModRegistry>,
+        caller: &str,
+        service: &str,
+    ) -> Result<Vec<u8>, String> {
+        storages.lock().unwrap().current_mod.set(caller.to_string());
+        let result = call_service(storages, registry, service, "ping", &[]);
+        assert_eq!(storages.lock().unwrap().current_mod.get(), caller);
+        result
+    }
+
+    #[test]
+    fn calls_run_as_the_provider() {
+        let (storages, registry) = setup(&[
+            ("a", &[], "a-service", None),
+            ("b", &["a"], "b-service", None),
+        ]);
+        assert_eq!(
+            call_as(&storages, &registry, "b", "a-service"),
+            Ok(b"a".to_vec())
+        );
+    }
+
+    #[test]
+    fn callers_have_to_depend_on_the_provider() {
+        let (storages, registry) =
+            setup(&[("a", &[], "a-service", None), ("b", &[], "b-service", None)]);
+        let error = call_as(&storages, &registry, "b", "a-service").unwrap_err();
+        assert!(error.contains("has to depend on a"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_services_are_an_error() {
+        let (storages, registry) = setup(&[("a", &[], "a-service", None)]);
+        let error = call_as(&storages, &registry, "a", "missing").unwrap_err();
+        assert!(
+            error.contains("No mod provides service missing"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn calling_back_into_a_running_mod_is_an_error() {
+        let (storages, registry) = setup(&[
+            ("a", &["b"], "a-service", None),
+            ("b", &["a"], "b-service", Some("a-service")),
+        ]);
+        // "a" is running while it calls "b", which calls back into "a".
+        let a = registry.lock().unwrap().get_mod("a").unwrap();
+        let _running = a.lock();
+
+        let error = call_as(&storages, &registry, "a", "b-service").unwrap_err();
+        assert!(error.contains("can't be called reentrantly"), "{}", error);
+    }
+}
@@ -0,0 +1,192 @@
+use super::{super::Storages, string_param, unit_result_type};
+use crate::settings::{SettingInfo, SettingValue};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use utils::logging::*;
+use wasm_component_layer::{
+    Func, FuncType, Linker, List, ListType, OptionType, OptionValue, Record, RecordType,
+    ResultValue, Store, Value, ValueType, Variant, VariantCase, VariantType,
+};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+fn setting_value_type() -> VariantType {
+    VariantType::new(
+        None,
+        [
+            VariantCase::new("boolean", Some(ValueType::Bool)),
+            VariantCase::new("integer", Some(ValueType::S64)),
+            VariantCase::new("float", Some(ValueType::F64)),
+            VariantCase::new("text", Some(ValueType::String)),
+        ],
+    )
+    .expect("Failed to create setting value type")
+}
+
+fn setting_info_type() -> RecordType {
+    RecordType::new(
+        None,
+        [
+            ("name", ValueType::String),
+            ("description", ValueType::String),
+            ("value", ValueType::Variant(setting_value_type())),
+            ("default", ValueType::Variant(setting_value_type())),
+            ("min", ValueType::Option(OptionType::new(ValueType::F64))),
+            ("max", ValueType::Option(OptionType::new(ValueType::F64))),
+        ],
+    )
+    .expect("Failed to create setting info type")
+}
+
+fn setting_from_value(value: &Value) -> SettingValue {
+    let Value::Variant(variant) = value else {
+        panic!("Unexpected parameter type");
+    };
+    match (variant.discriminant(), variant.value()) {
+        (0, Some(Value::Bool(value))) => SettingValue::Boolean(value),
+        (1, Some(Value::S64(value))) => SettingValue::Integer(value),
+        (2, Some(Value::F64(value))) => SettingValue::Float(value),
+        (3, Some(Value::String(value))) => SettingValue::Text(value.to_string()),
+        _ => panic!("Unexpected setting value case"),
+    }
+}
+
+fn setting_value(value: &SettingValue) -> Value {
+    let (case, payload) = match value {
+        SettingValue::Boolean(value) => (0, Value::Bool(*value)),
+        SettingValue::Integer(value) => (1, Value::S64(*value)),
+        SettingValue::Float(value) => (2, Value::F64(*value)),
+        SettingValue::Text(value) => (3, Value::String(value.as_str().into())),
+    };
+    Value::Variant(
+        Variant::new(setting_value_type(), case, Some(payload)).expect("Failed to create variant"),
+    )
+}
+
+fn bound_value(bound: Option<f64>) -> Value {
+    Value::Option(
+        OptionValue::new(OptionType::new(ValueType::F64), bound.map(Value::F64))
+            .expect("Failed to create option"),
+    )
+}
+
+fn setting_info_value(info: &SettingInfo) -> Value {
+    Value::Record(
+        Record::new(
+            setting_info_type(),
+            [
+                ("name", Value::String(info.name.as_str().into())),
+                (
+                    "description",
+                    Value::String(info.description.as_str().into()),
+                ),
+                ("value", setting_value(&info.value)),
+                ("default", setting_value(&info.default)),
+                ("min", bound_value(info.min)),
+                ("max", bound_value(info.max)),
+            ],
+        )
+        .expect("Failed to create record"),
+    )
+}
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/settings".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "get-setting",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::String],
+                    [ValueType::Option(OptionType::new(ValueType::Variant(
+                        setting_value_type(),
+                    )))],
+                ),
+                move |_, params, results| {
+                    let name = string_param(&params[0]);
+
+                    let value = {
+                        let storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.settings.get(&mod_id, &name).map(setting_value)
+                    };
+
+                    results[0] = Value::Option(OptionValue::new(
+                        OptionType::new(ValueType::Variant(setting_value_type())),
+                        value,
+                    )?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "set-setting",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::String, ValueType::Variant(setting_value_type())],
+                    [ValueType::Result(unit_result_type())],
+                ),
+                move |_, params, results| {
+                    let name = string_param(&params[0]);
+                    let value = setting_from_value(&params[1]);
+
+                    let set = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.settings.set(&mod_id, &name, value)
+                    };
+
+                    results[0] = Value::Result(ResultValue::new(
+                        unit_result_type(),
+                        set.map(|_| None).map_err(|e| Some(Value::String(e.into()))),
+                    )?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "list-settings",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [],
+                    [ValueType::List(ListType::new(ValueType::Record(
+                        setting_info_type(),
+                    )))],
+                ),
+                move |_, _, results| {
+                    let settings = {
+                        let storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.settings.list(&mod_id)
+                    };
+
+                    results[0] = Value::List(List::new(
+                        ListType::new(ValueType::Record(setting_info_type())),
+                        settings.iter().map(setting_info_value).collect::<Vec<_>>(),
+                    )?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
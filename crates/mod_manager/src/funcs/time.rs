@@ -0,0 +1,124 @@
+use super::{super::Storages, u32_param};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use utils::logging::*;
+use wasm_component_layer::{Func, FuncType, Linker, Record, RecordType, Store, Value, ValueType};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+fn clock_type() -> RecordType {
+    RecordType::new(
+        None,
+        [("game-ms", ValueType::F64), ("real-ms", ValueType::F64)],
+    )
+    .expect("Failed to create clock type")
+}
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/time".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "now",
+            Func::new(
+                &mut *store,
+                FuncType::new([], [ValueType::Record(clock_type())]),
+                move |_, _, results| {
+                    let (game, real) = {
+                        let storages = storages_clone.lock().unwrap();
+                        (storages.timers.game_time(), storages.timers.real_time())
+                    };
+
+                    results[0] = Value::Record(Record::new(
+                        clock_type(),
+                        [("game-ms", Value::F64(game)), ("real-ms", Value::F64(real))],
+                    )?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "set-timeout",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::U32], [ValueType::U64]),
+                move |_, params, results| {
+                    let ms = u32_param(&params[0]);
+
+                    let mut storages = storages_clone.lock().unwrap();
+                    let mod_id = storages.current_mod.get().clone();
+                    results[0] = Value::U64(storages.timers.set_timeout(&mod_id, ms));
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "set-interval",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::U32], [ValueType::U64]),
+                move |_, params, results| {
+                    let ms = u32_param(&params[0]);
+
+                    let mut storages = storages_clone.lock().unwrap();
+                    let mod_id = storages.current_mod.get().clone();
+                    results[0] = Value::U64(storages.timers.set_interval(&mod_id, ms));
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "cancel",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::U64], [ValueType::Bool]),
+                move |_, params, results| {
+                    let timer = match params[0] {
+                        Value::U64(timer) => timer,
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let mut storages = storages_clone.lock().unwrap();
+                    let mod_id = storages.current_mod.get().clone();
+                    results[0] = Value::Bool(storages.timers.cancel(&mod_id, timer));
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "alpha",
+            Func::new(
+                &mut *store,
+                FuncType::new([], [ValueType::F32]),
+                move |_, _, results| {
+                    results[0] = Value::F32(storages_clone.lock().unwrap().timers.alpha());
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use super::{super::Storages, entity_param, string_param, unit_result, unit_result_type};
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+use utils::logging::*;
+use wasm_component_layer::{
+    Func, FuncType, Linker, List, ListType, OptionType, OptionValue, Store, Value, ValueType,
+};
+use wasmi_runtime_layer::Engine as WasmEngine;
+
+pub fn register(
+    linker: &mut Linker,
+    store: &mut Store<(), WasmEngine>,
+    storages: Arc<Mutex<Storages>>,
+) -> Result<()> {
+    let interface = linker
+        .define_instance("module:guest/world".try_into().unwrap())
+        .log_msg("Failed to define instance")?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "claim-component",
+            Func::new(
+                &mut *store,
+                FuncType::new([ValueType::String], [ValueType::Result(unit_result_type())]),
+                move |_, params, results| {
+                    let type_name = string_param(&params[0]);
+
+                    let claimed = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages.world.claim(&mod_id, &type_name)
+                    };
+
+                    results[0] = unit_result(claimed)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "set-component",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [
+                        ValueType::U64,
+                        ValueType::String,
+                        ValueType::List(ListType::new(ValueType::U8)),
+                    ],
+                    [ValueType::Result(unit_result_type())],
+                ),
+                move |_, params, results| {
+                    let entity = entity_param(&params[0]);
+                    let type_name = string_param(&params[1]);
+                    let value = match &params[2] {
+                        Value::List(list) => list.typed::<u8>()?.to_vec(),
+                        _ => panic!("Unexpected parameter type"),
+                    };
+
+                    let set = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages
+                            .ecs
+                            .check_alive(entity)
+                            .and_then(|_| storages.ecs.check_write(&type_name))
+                            .and_then(|_| storages.world.set(&mod_id, entity, &type_name, value))
+                    };
+
+                    results[0] = unit_result(set)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "remove-component",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::U64, ValueType::String],
+                    [ValueType::Result(unit_result_type())],
+                ),
+                move |_, params, results| {
+                    let entity = entity_param(&params[0]);
+                    let type_name = string_param(&params[1]);
+
+                    let removed = {
+                        let mut storages = storages_clone.lock().unwrap();
+                        let mod_id = storages.current_mod.get().clone();
+                        storages
+                            .ecs
+                            .check_write(&type_name)
+                            .and_then(|_| storages.world.remove(&mod_id, entity, &type_name))
+                    };
+
+                    results[0] = unit_result(removed)?;
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "get-component",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::U64, ValueType::String],
+                    [ValueType::Option(OptionType::new(ValueType::List(
+                        ListType::new(ValueType::U8),
+                    )))],
+                ),
+                move |_, params, results| {
+                    let entity = entity_param(&params[0]);
+                    let type_name = string_param(&params[1]);
+
+                    let value = {
+                        let storages = storages_clone.lock().unwrap();
+                        match storages.ecs.check_read(&type_name) {
+                            Ok(()) => storages
+                                .world
+                                .get(entity, &type_name)
+                                .map(|value| Value::List(List::from(value))),
+                            Err(e) => {
+                                warn!("{}", e);
+                                None
+                            }
+                        }
+                    };
+
+                    results[0] = Value::Option(OptionValue::new(
+                        OptionType::new(ValueType::List(ListType::new(ValueType::U8))),
+                        value,
+                    )?);
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    let storages_clone = storages.clone();
+    interface
+        .define_func(
+            "entities-with",
+            Func::new(
+                &mut *store,
+                FuncType::new(
+                    [ValueType::String],
+                    [ValueType::List(ListType::new(ValueType::U64))],
+                ),
+                move |_, params, results| {
+                    let type_name = string_param(&params[0]);
+
+                    let entities = storages_clone
+                        .lock()
+                        .unwrap()
+                        .world
+                        .entities_with(&type_name);
+
+                    results[0] = Value::List(List::from(entities.as_slice()));
+                    Ok(())
+                },
+            ),
+        )
+        .log()?;
+
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use std::time::Duration;
+use tracing::debug;
+
+/// 60 simulation steps per second.
+pub const DEFAULT_STEP: Duration = Duration::from_nanos(16_666_667);
+
+/// Most steps one frame runs before the simulation gives up catching up.
+pub const DEFAULT_MAX_STEPS: u32 = 5;
+
+/// What a frame has to do: run `steps` fixed steps, then draw interpolated
+/// `alpha` of the way from the previous step to the next one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub steps: u32,
+    pub alpha: f32,
+}
+
+/// Fixed-timestep driver. Real time is scaled and accumulated, and every
+/// full `step` of it is one simulation step, so the simulation runs at the
+/// same rate however fast frames are drawn. While paused no time
+/// accumulates. A frame that is behind by more than `max_steps` drops the
+/// rest instead of spiralling into ever longer frames.
+#[derive(Debug)]
+pub struct GameLoop {
+    step: Duration,
+    max_steps: u32,
+    accumulator: Duration,
+    paused: bool,
+    time_scale: f64,
+}
+
+impl Default for GameLoop {
+    fn default() -> Self {
+        Self::new(DEFAULT_STEP)
+    }
+}
+
+impl GameLoop {
+    pub fn new(step: Duration) -> Self {
+        Self {
+            step: step.max(Duration::from_micros(100)),
+            max_steps: DEFAULT_MAX_STEPS,
+            accumulator: Duration::ZERO,
+            paused: false,
+            time_scale: 1.0,
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    pub fn max_steps(&self) -> u32 {
+        self.max_steps
+    }
+
+    pub fn set_max_steps(&mut self, max_steps: u32) {
+        self.max_steps = max_steps;
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Sets how fast the simulation runs compared to real time. Negative and
+    /// NaN scales are treated as 0 and 1.
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = if time_scale.is_nan() {
+            1.0
+        } else {
+            time_scale.max(0.0)
+        };
+    }
+
+    /// Accounts for `real_delta` of real time since the last frame.
+    pub fn advance(&mut self, real_delta: Duration) -> Frame {
+        if !self.paused {
+            let scaled = Duration::try_from_secs_f64(real_delta.as_secs_f64() * self.time_scale)
+                .unwrap_or(Duration::MAX);
+            self.accumulator = self.accumulator.saturating_add(scaled);
+        }
+
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        if self.accumulator >= self.step {
+            let behind = self.accumulator.as_nanos() / self.step.as_nanos();
+            debug!("Dropping {} steps the simulation can't catch up on", behind);
+            self.accumulator =
+                Duration::from_nanos((self.accumulator.as_nanos() % self.step.as_nanos()) as u64);
+        }
+
+        Frame {
+            steps,
+            alpha: self.accumulator.as_secs_f32() / self.step.as_secs_f32(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn catches_up_on_whole_steps() {
+        let mut game_loop = GameLoop::new(STEP);
+        assert_eq!(game_loop.advance(Duration::from_millis(5)).steps, 0);
+        let frame = game_loop.advance(Duration::from_millis(20));
+        assert_eq!(frame.steps, 2);
+        assert!((frame.alpha - 0.5).abs() < 1e-4);
+        let frame = game_loop.advance(Duration::from_millis(5));
+        assert_eq!(frame.steps, 1);
+        assert!(frame.alpha.abs() < 1e-4);
+    }
+
+    #[test]
+    fn drops_steps_past_max_steps() {
+        let mut game_loop = GameLoop::new(STEP).with_max_steps(3);
+        let frame = game_loop.advance(Duration::from_millis(105));
+        assert_eq!(frame.steps, 3);
+        assert!((frame.alpha - 0.5).abs() < 1e-4);
+        // What was dropped isn't caught up on later.
+        assert_eq!(game_loop.advance(Duration::ZERO).steps, 0);
+        assert_eq!(game_loop.advance(Duration::MAX).steps, 3);
+    }
+
+    #[test]
+    fn pause() {
+        let mut game_loop = GameLoop::new(STEP);
+        game_loop.advance(Duration::from_millis(5));
+        game_loop.set_paused(true);
+        let frame = game_loop.advance(Duration::from_millis(100));
+        assert_eq!(frame.steps, 0);
+        assert!((frame.alpha - 0.5).abs() < 1e-4);
+        game_loop.set_paused(false);
+        assert_eq!(game_loop.advance(Duration::from_millis(5)).steps, 1);
+    }
+
+    #[test]
+    fn time_scale() {
+        let mut game_loop = GameLoop::new(STEP);
+        game_loop.set_time_scale(2.0);
+        assert_eq!(game_loop.advance(Duration::from_millis(20)).steps, 4);
+        game_loop.set_time_scale(0.5);
+        assert_eq!(game_loop.advance(Duration::from_millis(20)).steps, 1);
+
+        game_loop.set_time_scale(-1.0);
+        assert_eq!(game_loop.time_scale(), 0.0);
+        assert_eq!(game_loop.advance(Duration::from_secs(1)).steps, 0);
+        game_loop.set_time_scale(f64::NAN);
+        assert_eq!(game_loop.time_scale(), 1.0);
+        game_loop.set_time_scale(f64::INFINITY);
+        assert_eq!(game_loop.advance(STEP).steps, DEFAULT_MAX_STEPS);
+    }
+
+    #[test]
+    fn tiny_steps_are_raised() {
+        assert_eq!(
+            GameLoop::new(Duration::ZERO).step(),
+            Duration::from_micros(100)
+        );
+    }
+}
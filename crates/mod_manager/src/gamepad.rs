@@ -0,0 +1,345 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GamepadButton {
+    A,
+    B,
+    X,
+    Y,
+    Back,
+    Guide,
+    Start,
+    LeftStick,
+    RightStick,
+    LeftShoulder,
+    RightShoulder,
+    DpadUp,
+    DpadDown,
+    DpadLeft,
+    DpadRight,
+}
+
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 15] = [
+        GamepadButton::A,
+        GamepadButton::B,
+        GamepadButton::X,
+        GamepadButton::Y,
+        GamepadButton::Back,
+        GamepadButton::Guide,
+        GamepadButton::Start,
+        GamepadButton::LeftStick,
+        GamepadButton::RightStick,
+        GamepadButton::LeftShoulder,
+        GamepadButton::RightShoulder,
+        GamepadButton::DpadUp,
+        GamepadButton::DpadDown,
+        GamepadButton::DpadLeft,
+        GamepadButton::DpadRight,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GamepadButton::A => "a",
+            GamepadButton::B => "b",
+            GamepadButton::X => "x",
+            GamepadButton::Y => "y",
+            GamepadButton::Back => "back",
+            GamepadButton::Guide => "guide",
+            GamepadButton::Start => "start",
+            GamepadButton::LeftStick => "left-stick",
+            GamepadButton::RightStick => "right-stick",
+            GamepadButton::LeftShoulder => "left-shoulder",
+            GamepadButton::RightShoulder => "right-shoulder",
+            GamepadButton::DpadUp => "dpad-up",
+            GamepadButton::DpadDown => "dpad-down",
+            GamepadButton::DpadLeft => "dpad-left",
+            GamepadButton::DpadRight => "dpad-right",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    pub const ALL: [GamepadAxis; 6] = [
+        GamepadAxis::LeftX,
+        GamepadAxis::LeftY,
+        GamepadAxis::RightX,
+        GamepadAxis::RightY,
+        GamepadAxis::LeftTrigger,
+        GamepadAxis::RightTrigger,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GamepadAxis::LeftX => "left-x",
+            GamepadAxis::LeftY => "left-y",
+            GamepadAxis::RightX => "right-x",
+            GamepadAxis::RightY => "right-y",
+            GamepadAxis::LeftTrigger => "left-trigger",
+            GamepadAxis::RightTrigger => "right-trigger",
+        }
+    }
+}
+
+/// Change reported by a gamepad backend. Gamepads are identified by an id
+/// that stays the same while the device is connected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GamepadEvent {
+    Connected {
+        id: u32,
+        name: String,
+    },
+    Disconnected {
+        id: u32,
+    },
+    ButtonDown {
+        id: u32,
+        button: GamepadButton,
+    },
+    ButtonUp {
+        id: u32,
+        button: GamepadButton,
+    },
+    /// Sticks range from -1 to 1, triggers from 0 to 1.
+    AxisMoved {
+        id: u32,
+        axis: GamepadAxis,
+        value: f32,
+    },
+}
+
+/// Rumble a mod asked for. Motor strengths range from 0 to 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RumbleRequest {
+    pub id: u32,
+    pub low_frequency: f32,
+    pub high_frequency: f32,
+    pub duration_ms: u32,
+}
+
+/// Source of gamepad input, SDL's GameController subsystem in the game and
+/// `ScriptedGamepad` where there is no hardware.
+pub trait GamepadBackend {
+    /// Returns the changes since the last call.
+    fn poll(&mut self) -> Vec<GamepadEvent>;
+    fn rumble(&mut self, request: &RumbleRequest) -> Result<()>;
+}
+
+/// Fake backend that replays a script, one batch of events per poll, and
+/// records rumble requests instead of playing them.
+#[derive(Debug, Default)]
+pub struct ScriptedGamepad {
+    frames: VecDeque<Vec<GamepadEvent>>,
+    rumbles: Vec<RumbleRequest>,
+}
+
+impl ScriptedGamepad {
+    pub fn new(frames: impl IntoIterator<Item = Vec<GamepadEvent>>) -> Self {
+        Self {
+            frames: frames.into_iter().collect(),
+            rumbles: Vec::new(),
+        }
+    }
+
+    /// Appends a batch of events to the end of the script.
+    pub fn push_frame(&mut self, events: Vec<GamepadEvent>) {
+        self.frames.push_back(events);
+    }
+
+    pub fn rumbles(&self) -> &[RumbleRequest] {
+        &self.rumbles
+    }
+}
+
+impl GamepadBackend for ScriptedGamepad {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        self.frames.pop_front().unwrap_or_default()
+    }
+
+    fn rumble(&mut self, request: &RumbleRequest) -> Result<()> {
+        self.rumbles.push(request.clone());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct GamepadState {
+    name: String,
+    buttons_down: HashSet<GamepadButton>,
+    buttons_pressed: HashSet<GamepadButton>,
+    buttons_released: HashSet<GamepadButton>,
+    axes: [f32; GamepadAxis::ALL.len()],
+}
+
+/// State of every connected gamepad, kept next to the keyboard and mouse
+/// state in `InputState`.
+#[derive(Debug)]
+pub struct Gamepads {
+    devices: BTreeMap<u32, GamepadState>,
+    deadzone: f32,
+    rumble_requests: Vec<RumbleRequest>,
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self {
+            devices: BTreeMap::new(),
+            deadzone: 0.15,
+            rumble_requests: Vec::new(),
+        }
+    }
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply(&mut self, event: &GamepadEvent) {
+        match event {
+            GamepadEvent::Connected { id, name } => {
+                self.devices.insert(
+                    *id,
+                    GamepadState {
+                        name: name.clone(),
+                        ..Default::default()
+                    },
+                );
+            }
+            GamepadEvent::Disconnected { id } => {
+                self.devices.remove(id);
+            }
+            GamepadEvent::ButtonDown { id, button } => {
+                if let Some(device) = self.devices.get_mut(id) {
+                    if device.buttons_down.insert(*button) {
+                        device.buttons_pressed.insert(*button);
+                    }
+                }
+            }
+            GamepadEvent::ButtonUp { id, button } => {
+                if let Some(device) = self.devices.get_mut(id) {
+                    if device.buttons_down.remove(button) {
+                        device.buttons_released.insert(*button);
+                    }
+                }
+            }
+            GamepadEvent::AxisMoved { id, axis, value } => {
+                if let Some(device) = self.devices.get_mut(id) {
+                    device.axes[*axis as usize] = value.clamp(-1.0, 1.0);
+                }
+            }
+        }
+    }
+
+    /// Releases all held buttons, see `InputState::release_all`.
+    pub fn release_all(&mut self) {
+        for device in self.devices.values_mut() {
+            device.buttons_released.extend(device.buttons_down.drain());
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        for device in self.devices.values_mut() {
+            device.buttons_pressed.clear();
+            device.buttons_released.clear();
+        }
+    }
+
+    /// Ids of connected gamepads in ascending order.
+    pub fn connected(&self) -> Vec<u32> {
+        self.devices.keys().copied().collect()
+    }
+
+    pub fn name(&self, id: u32) -> Option<&str> {
+        self.devices.get(&id).map(|device| device.name.as_str())
+    }
+
+    pub fn is_button_down(&self, id: u32, button: GamepadButton) -> bool {
+        self.devices
+            .get(&id)
+            .is_some_and(|device| device.buttons_down.contains(&button))
+    }
+
+    pub fn is_button_pressed(&self, id: u32, button: GamepadButton) -> bool {
+        self.devices
+            .get(&id)
+            .is_some_and(|device| device.buttons_pressed.contains(&button))
+    }
+
+    pub fn is_button_down_on_any(&self, button: GamepadButton) -> bool {
+        self.devices
+            .values()
+            .any(|device| device.buttons_down.contains(&button))
+    }
+
+    pub fn is_button_pressed_on_any(&self, button: GamepadButton) -> bool {
+        self.devices
+            .values()
+            .any(|device| device.buttons_pressed.contains(&button))
+    }
+
+    pub fn is_button_released(&self, id: u32, button: GamepadButton) -> bool {
+        self.devices
+            .get(&id)
+            .is_some_and(|device| device.buttons_released.contains(&button))
+    }
+
+    /// Axis value with the deadzone cut out and the rest rescaled, so the
+    /// value still reaches 1 at full deflection.
+    pub fn axis(&self, id: u32, axis: GamepadAxis) -> f32 {
+        let Some(device) = self.devices.get(&id) else {
+            return 0.0;
+        };
+        let value = device.axes[axis as usize];
+        if value.abs() <= self.deadzone {
+            return 0.0;
+        }
+        value.signum() * (value.abs() - self.deadzone) / (1.0 - self.deadzone)
+    }
+
+    /// The strongest deflection of `axis` across all gamepads.
+    pub fn axis_on_any(&self, axis: GamepadAxis) -> f32 {
+        self.devices
+            .keys()
+            .map(|id| self.axis(*id, axis))
+            .fold(0.0, |strongest, value| {
+                if value.abs() > f32::abs(strongest) {
+                    value
+                } else {
+                    strongest
+                }
+            })
+    }
+
+    pub fn deadzone(&self) -> f32 {
+        self.deadzone
+    }
+
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        self.deadzone = deadzone.clamp(0.0, 0.99);
+    }
+
+    pub fn request_rumble(&mut self, request: RumbleRequest) {
+        self.rumble_requests.push(request);
+    }
+
+    /// Removes and returns the rumble requests since the last call.
+    pub fn take_rumble_requests(&mut self) -> Vec<RumbleRequest> {
+        std::mem::take(&mut self.rumble_requests)
+    }
+}
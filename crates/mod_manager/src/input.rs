@@ -0,0 +1,300 @@
+use crate::{
+    events::{Event, HotplugEvent, MouseEvent},
+    gamepad::{GamepadEvent, Gamepads},
+    storage::Storages,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A key, identified either by the symbol it produces under the current
+/// layout or by its physical position. Names follow SDL's key and scancode
+/// names and compare case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Key {
+    Keycode(String),
+    Scancode(String),
+}
+
+impl Key {
+    pub fn keycode(name: &str) -> Self {
+        Key::Keycode(name.to_lowercase())
+    }
+
+    pub fn scancode(name: &str) -> Self {
+        Key::Scancode(name.to_lowercase())
+    }
+
+    /// The same key with its name lowercased, for keys built by hand or
+    /// read from a file.
+    pub fn normalized(&self) -> Self {
+        match self {
+            Key::Keycode(name) => Key::keycode(name),
+            Key::Scancode(name) => Key::scancode(name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    X1,
+    X2,
+}
+
+impl MouseButton {
+    pub const ALL: [MouseButton; 5] = [
+        MouseButton::Left,
+        MouseButton::Middle,
+        MouseButton::Right,
+        MouseButton::X1,
+        MouseButton::X2,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MouseButton::Left => "left",
+            MouseButton::Middle => "middle",
+            MouseButton::Right => "right",
+            MouseButton::X1 => "x1",
+            MouseButton::X2 => "x2",
+        }
+    }
+}
+
+/// Input from the platform, already translated out of the windowing
+/// library's events. Everything mods can observe about input goes through
+/// these, which is what makes a run recordable, see `replay`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InputEvent {
+    /// Key going down or up, repeats left out. Either name may be missing
+    /// if the platform doesn't know it.
+    Key {
+        keycode: Option<String>,
+        scancode: Option<String>,
+        down: bool,
+    },
+    MouseMoved {
+        x: f32,
+        y: f32,
+    },
+    MouseButton {
+        button: MouseButton,
+        down: bool,
+    },
+    MouseWheel {
+        x: f32,
+        y: f32,
+    },
+    Text(String),
+    WindowResized {
+        width: u32,
+        height: u32,
+    },
+    FocusLost,
+    Gamepad(GamepadEvent),
+}
+
+impl InputEvent {
+    /// Feeds the event into the input state and queues the matching event
+    /// for mods that subscribed to it.
+    pub fn apply(&self, storages: &mut Storages) {
+        let input = &mut storages.input;
+        let event = match self {
+            InputEvent::Key {
+                keycode,
+                scancode,
+                down,
+            } => {
+                if *down {
+                    input.key_down(keycode.as_deref(), scancode.as_deref());
+                } else {
+                    input.key_up(keycode.as_deref(), scancode.as_deref());
+                }
+                Some(Event::Key {
+                    keycode: keycode.clone().unwrap_or_default(),
+                    scancode: scancode.clone().unwrap_or_default(),
+                    down: *down,
+                })
+            }
+            InputEvent::MouseMoved { x, y } => {
+                input.mouse_moved(*x, *y);
+                Some(Event::Mouse(MouseEvent::Moved { x: *x, y: *y }))
+            }
+            InputEvent::MouseButton { button, down } => {
+                if *down {
+                    input.mouse_button_down(*button);
+                    Some(Event::Mouse(MouseEvent::ButtonDown(*button)))
+                } else {
+                    input.mouse_button_up(*button);
+                    Some(Event::Mouse(MouseEvent::ButtonUp(*button)))
+                }
+            }
+            InputEvent::MouseWheel { x, y } => {
+                input.mouse_wheel(*x, *y);
+                Some(Event::Mouse(MouseEvent::Wheel { x: *x, y: *y }))
+            }
+            InputEvent::Text(text) => {
+                input.text_input(text);
+                None
+            }
+            InputEvent::WindowResized { width, height } => Some(Event::WindowResized {
+                width: *width,
+                height: *height,
+            }),
+            InputEvent::FocusLost => {
+                input.release_all();
+                Some(Event::FocusLost)
+            }
+            InputEvent::Gamepad(event) => {
+                input.gamepads.apply(event);
+                match event {
+                    GamepadEvent::Connected { id, .. } => {
+                        Some(Event::Gamepad(HotplugEvent::Connected(*id)))
+                    }
+                    GamepadEvent::Disconnected { id } => {
+                        Some(Event::Gamepad(HotplugEvent::Disconnected(*id)))
+                    }
+                    _ => None,
+                }
+            }
+        };
+        if let Some(event) = event {
+            storages.events.add(event);
+        }
+    }
+}
+
+/// Keyboard, mouse and gamepad state for the current step. The host feeds
+/// it window events every frame, and `end_frame` drops the per-step parts
+/// once a simulation step used them.
+#[derive(Debug, Default)]
+pub struct InputState {
+    keys_down: HashSet<Key>,
+    keys_pressed: HashSet<Key>,
+    keys_released: HashSet<Key>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    mouse_position: (f32, f32),
+    wheel: (f32, f32),
+    text: Vec<String>,
+    pub gamepads: Gamepads,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a key going down. Either name may be missing if the platform
+    /// doesn't know it. Key repeats should not be passed in.
+    pub fn key_down(&mut self, keycode: Option<&str>, scancode: Option<&str>) {
+        for key in Self::keys(keycode, scancode) {
+            if self.keys_down.insert(key.clone()) {
+                self.keys_pressed.insert(key);
+            }
+        }
+    }
+
+    pub fn key_up(&mut self, keycode: Option<&str>, scancode: Option<&str>) {
+        for key in Self::keys(keycode, scancode) {
+            if self.keys_down.remove(&key) {
+                self.keys_released.insert(key);
+            }
+        }
+    }
+
+    fn keys(keycode: Option<&str>, scancode: Option<&str>) -> impl Iterator<Item = Key> {
+        keycode
+            .map(Key::keycode)
+            .into_iter()
+            .chain(scancode.map(Key::scancode))
+    }
+
+    pub fn mouse_moved(&mut self, x: f32, y: f32) {
+        self.mouse_position = (x, y);
+    }
+
+    pub fn mouse_button_down(&mut self, button: MouseButton) {
+        if self.buttons_down.insert(button) {
+            self.buttons_pressed.insert(button);
+        }
+    }
+
+    pub fn mouse_button_up(&mut self, button: MouseButton) {
+        if self.buttons_down.remove(&button) {
+            self.buttons_released.insert(button);
+        }
+    }
+
+    pub fn mouse_wheel(&mut self, x: f32, y: f32) {
+        self.wheel.0 += x;
+        self.wheel.1 += y;
+    }
+
+    pub fn text_input(&mut self, text: &str) {
+        self.text.push(text.to_string());
+    }
+
+    /// Releases everything held, e.g. when the window loses focus and the
+    /// matching up events will never arrive.
+    pub fn release_all(&mut self) {
+        self.keys_released.extend(self.keys_down.drain());
+        self.buttons_released.extend(self.buttons_down.drain());
+        self.gamepads.release_all();
+    }
+
+    pub fn end_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.wheel = (0.0, 0.0);
+        self.text.clear();
+        self.gamepads.end_frame();
+    }
+
+    pub fn is_key_down(&self, key: &Key) -> bool {
+        self.keys_down.contains(key)
+    }
+
+    pub fn is_key_pressed(&self, key: &Key) -> bool {
+        self.keys_pressed.contains(key)
+    }
+
+    pub fn is_key_released(&self, key: &Key) -> bool {
+        self.keys_released.contains(key)
+    }
+
+    pub fn is_button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn is_button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn is_button_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    /// Mouse position in window pixels.
+    pub fn mouse_position(&self) -> (f32, f32) {
+        self.mouse_position
+    }
+
+    /// Wheel movement accumulated this frame.
+    pub fn wheel(&self) -> (f32, f32) {
+        self.wheel
+    }
+
+    /// Text entered this frame, one entry per input event.
+    pub fn text(&self) -> &[String] {
+        &self.text
+    }
+}
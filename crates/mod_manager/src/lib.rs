@@ -1,17 +1,52 @@
+pub mod actions;
+pub mod audio;
+pub mod bus;
+pub mod ecs;
+pub mod events;
 mod funcs;
+pub mod game_loop;
+pub mod gamepad;
+pub mod input;
 mod loader;
+mod manifest;
 mod mod_context;
+pub mod random;
 mod registry;
+pub mod render;
+pub mod replay;
+pub mod resources;
+pub mod saves;
+pub mod settings;
 mod storage;
+pub mod timers;
+pub mod vfs;
+pub mod world;
+pub use manifest::ModManifest;
 pub use mod_context::{ModContext, ModInfo, ModInterface};
+pub use registry::ComponentHash;
 
-use crate::storage::Storages;
+use crate::{
+    actions::ActionMap,
+    events::{Event, EventKind},
+    game_loop::{Frame, GameLoop},
+    gamepad::GamepadBackend,
+    input::InputEvent,
+    random::Random,
+    render::RenderState,
+    replay::{Divergence, Recorder, Recording, RecordingHeader, ReplayReport, RECORDING_VERSION},
+    resources::ResourceStack,
+    saves::{SaveSlot, SaveStore, Saved, SlotRequest},
+    settings::{SettingInfo, SettingValue, SettingsStore},
+    storage::Storages,
+    vfs::Vfs,
+};
 use anyhow::{Context, Error, Result};
 use loader::ModLoader;
 use registry::ModRegistry;
 use std::{
     path::Path,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 use tracing::{debug, debug_span, error_span, info, warn};
 use utils::logging::*;
@@ -22,12 +57,32 @@ pub struct ModManager {
     mods_dir: String,
     context: ModContext,
     storages: Arc<Mutex<Storages>>,
+    game_loop: GameLoop,
+    recorder: Option<Recorder>,
 }
 
 impl ModManager {
     pub fn new(mods_dir: &str, context: ModContext) -> Result<Self, Error> {
         let registry = Arc::new(Mutex::new(ModRegistry::new()));
-        let storages = Arc::new(Mutex::new(Storages::new()));
+        let mut storages = Storages::new();
+        let exe_dir = std::env::current_exe()
+            .with_context(|| "Failed to get current executable path")?
+            .parent()
+            .with_context(|| "Failed to get parent directory of executable")?
+            .to_path_buf();
+        storages.actions = ActionMap::load(&exe_dir.join("config").join("bindings.toml"));
+        storages.saves = SaveStore::new(&exe_dir.join("saves"));
+        storages.vfs = Vfs::new(&exe_dir.join("data"));
+        storages.settings = SettingsStore::new(&exe_dir.join("config").join("settings"));
+        // Logged so a run can be reproduced with `set_seed`.
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        info!("Random seed: {}", seed);
+        storages.random = Random::new(seed);
+        storages.resources =
+            ResourceStack::new(&exe_dir.join("resources"), &exe_dir.join("overrides"));
+        let storages = Arc::new(Mutex::new(storages));
         let loader = ModLoader::new(Arc::clone(&registry), storages.clone());
 
         Ok(Self {
@@ -36,6 +91,8 @@ impl ModManager {
             mods_dir: mods_dir.to_string(),
             context,
             storages,
+            game_loop: GameLoop::default(),
+            recorder: None,
         })
     }
 
@@ -55,12 +112,20 @@ impl ModManager {
             return Ok(());
         }
 
+        let mut paths = Vec::new();
         for entry in std::fs::read_dir(mods_path).log()? {
             let path = entry.log()?.path();
             if path.is_file() && path.extension().map_or(false, |ext| ext == "wasm") {
-                self.load_mod(&path)?;
+                paths.push(path);
             }
         }
+        paths.sort();
+        for path in paths {
+            self.load_mod(&path)?;
+        }
+        self.registry.lock().unwrap().sort_by_dependencies();
+        self.update_mod_order();
+        self.storages.lock().unwrap().resources.report_conflicts();
 
         info!(
             "Loaded {} mods in {}ms",
@@ -74,9 +139,18 @@ impl ModManager {
         let span = error_span!("load_mod", file = path.display().to_string());
         let _guard = span.enter();
 
-        self.loader
+        let mod_info = self
+            .loader
             .load_mod(path, &self.context)
-            .log_msg("Failed to load mod")
+            .log_msg("Failed to load mod")?;
+
+        self.update_mod_order();
+
+        // Queued so mods get it after `init`, once they had a chance to
+        // subscribe.
+        let mut storages = self.storages.lock().unwrap();
+        storages.events.add(Event::ModLoaded(mod_info.id.clone()));
+        Ok(mod_info)
     }
 
     pub fn unload_all_mods(&mut self) -> Result<()> {
@@ -84,11 +158,7 @@ impl ModManager {
         let _guard = span.enter();
 
         let registry = self.registry.lock().unwrap();
-        let mod_ids: Vec<String> = registry
-            .get_all_mods()
-            .into_iter()
-            .map(|(id, _)| id.clone())
-            .collect();
+        let mod_ids: Vec<String> = registry.order().iter().rev().cloned().collect();
         drop(registry);
 
         for id in mod_ids {
@@ -100,6 +170,377 @@ impl ModManager {
 
     pub fn unload_mod(&mut self, mod_id: &str) -> Result<()> {
         self.loader.unload_mod(mod_id)?;
+        self.dispatch_event(&Event::ModUnloaded(mod_id.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Delivers an event to the mods subscribed to its kind, in dependency
+    /// order, until one of them consumes it. Returns whether it was consumed.
+    /// A mod failing to handle it is logged and skipped.
+    pub fn dispatch_event(&mut self, event: &Event) -> Result<bool> {
+        let span = error_span!("dispatch_event", kind = event.kind().name());
+        let _guard = span.enter();
+
+        let mods = self.registry.lock().unwrap().mods();
+        for (id, mod_handle) in mods {
+            let subscribed = {
+                let storages = self.storages.lock().unwrap();
+                storages
+                    .subscriptions
+                    .get(&id)
+                    .is_some_and(|kinds| kinds.contains(&event.kind()))
+            };
+            if !subscribed {
+                continue;
+            }
+
+            self.set_current_mod(&id);
+            if mod_handle
+                .lock()
+                .on_event(event)
+                .log_msg("Failed to handle event")
+                .unwrap_or(false)
+            {
+                debug!("Event consumed by {}", id);
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Hands queued bus messages to the mods that handle them in `on-event`,
+    /// in dependency order. Mods that aren't subscribed to message events
+    /// keep them queued for `poll-message`. Messages published during the
+    /// dispatch are delivered on the next call.
+    pub fn dispatch_messages(&mut self) -> Result<()> {
+        let span = error_span!("dispatch_messages");
+        let _guard = span.enter();
+
+        let mods = self.registry.lock().unwrap().mods();
+        let mut pending = Vec::new();
+        {
+            let mut storages = self.storages.lock().unwrap();
+            for (id, mod_handle) in mods {
+                let handles_messages = storages
+                    .subscriptions
+                    .get(&id)
+                    .is_some_and(|kinds| kinds.contains(&EventKind::Message));
+                if handles_messages {
+                    let messages = storages.bus.drain(&id);
+                    pending.push((id, mod_handle, messages));
+                }
+            }
+        }
+
+        for (id, mod_handle, messages) in pending {
+            self.set_current_mod(&id);
+            let mut mod_instance = mod_handle.lock();
+            for message in messages {
+                mod_instance
+                    .on_event(&Event::Message(message))
+                    .log_msg("Failed to handle message")
+                    .ok();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Feeds platform input into the input state and the event queue, and
+    /// into the recording if one is running.
+    pub fn handle_input(&mut self, event: InputEvent) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record_input(&event);
+        }
+        event.apply(&mut self.storages.lock().unwrap());
+    }
+
+    /// Feeds the changes from a gamepad backend in like `handle_input` and
+    /// hands it the rumble requests of mods.
+    pub fn pump_gamepads(&mut self, backend: &mut dyn GamepadBackend) {
+        for event in backend.poll() {
+            self.handle_input(InputEvent::Gamepad(event));
+        }
+
+        let requests = self
+            .storages
+            .lock()
+            .unwrap()
+            .input
+            .gamepads
+            .take_rumble_requests();
+        for request in requests {
+            // Not every device can rumble, that shouldn't stop the game.
+            backend
+                .rumble(&request)
+                .log_msg("Failed to rumble gamepad")
+                .ok();
+        }
+    }
+
+    /// Queues a `ResourceChanged` event for every resource mods read that
+    /// changed on disk, so they can reload it.
+    pub fn poll_resources(&mut self) {
+        let mut storages = self.storages.lock().unwrap();
+        for path in storages.resources.poll_changes() {
+            storages.events.add(Event::ResourceChanged(path));
+        }
+    }
+
+    /// Changes a setting of a mod, the mod is told on the next
+    /// `dispatch_settings_changes`.
+    pub fn set_setting(&mut self, mod_id: &str, name: &str, value: SettingValue) -> Result<()> {
+        self.storages
+            .lock()
+            .unwrap()
+            .settings
+            .set(mod_id, name, value)
+            .anyhow()
+    }
+
+    pub fn get_settings(&self, mod_id: &str) -> Vec<SettingInfo> {
+        self.storages.lock().unwrap().settings.list(mod_id)
+    }
+
+    /// Rereads settings files the user edited and tells every mod subscribed
+    /// to `settings-changed` which of its settings changed.
+    pub fn dispatch_settings_changes(&mut self) -> Result<()> {
+        let span = error_span!("dispatch_settings_changes");
+        let _guard = span.enter();
+
+        let changes = {
+            let mut storages = self.storages.lock().unwrap();
+            storages.settings.poll_changes();
+            storages.settings.take_changes()
+        };
+        for (id, names) in changes {
+            let subscribed = {
+                let storages = self.storages.lock().unwrap();
+                storages
+                    .subscriptions
+                    .get(&id)
+                    .is_some_and(|kinds| kinds.contains(&EventKind::SettingsChanged))
+            };
+            if !subscribed {
+                continue;
+            }
+            let Some(mod_handle) = self.registry.lock().unwrap().get_mod(&id) else {
+                continue;
+            };
+
+            self.set_current_mod(&id);
+            mod_handle
+                .lock()
+                .on_event(&Event::SettingsChanged(names))
+                .log_msg("Failed to handle settings change")
+                .ok();
+        }
+
+        Ok(())
+    }
+
+    /// Advances the game clock by `game_delta` and hands every timer that
+    /// came due to the mod that set it. Timers are delivered whether the mod
+    /// subscribed to `timer` events or not.
+    pub fn update_timers(&mut self, game_delta: Duration) -> Result<()> {
+        let span = error_span!("update_timers");
+        let _guard = span.enter();
+
+        let fired = self.storages.lock().unwrap().timers.advance(game_delta);
+        for (id, timer) in fired {
+            let Some(mod_handle) = self.registry.lock().unwrap().get_mod(&id) else {
+                continue;
+            };
+            self.set_current_mod(&id);
+            mod_handle
+                .lock()
+                .on_event(&Event::Timer(timer))
+                .log_msg("Failed to handle timer")
+                .ok();
+        }
+
+        Ok(())
+    }
+
+    /// Restarts every mod's random stream from `seed`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.storages.lock().unwrap().random.set_seed(seed);
+    }
+
+    /// Sets the window size the camera maps through until the first
+    /// `end_frame`. Call it before `call_init`, so the first frame doesn't
+    /// draw through a 0x0 window.
+    pub fn set_window_size(&mut self, window_size: (u32, u32)) {
+        self.storages.lock().unwrap().window_size.set(window_size);
+    }
+
+    /// Pause, time scale and catch-up limit of the simulation.
+    pub fn game_loop(&mut self) -> &mut GameLoop {
+        &mut self.game_loop
+    }
+
+    /// Everything a frame does before drawing: queued events, messages and
+    /// settings changes are dispatched, then the simulation advances by
+    /// `real_delta`.
+    pub fn run_frame(&mut self, real_delta: Duration) -> Result<Frame> {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record_delta(real_delta);
+        }
+        self.dispatch_events()?;
+        self.dispatch_messages()?;
+        self.dispatch_settings_changes()?;
+        self.advance(real_delta)
+    }
+
+    /// Finishes a frame after it was rendered: the frame is written to the
+    /// recording if one is running and the per-frame storages are cleared.
+    /// A recording that fails to write is stopped, the game goes on.
+    pub fn end_frame(&mut self, window_size: (u32, u32)) {
+        let mut storages = self.storages.lock().unwrap();
+        if let Some(recorder) = &mut self.recorder {
+            if recorder
+                .end_frame(window_size, &storages.draw_list)
+                .log_msg("Failed to record frame")
+                .is_err()
+            {
+                self.recorder = None;
+            }
+        }
+        storages.clear(window_size);
+    }
+
+    /// Records every frame's input and real time from now on, and with
+    /// `hash_draws` a hash of what was drawn, so the run can be replayed
+    /// with `replay`. Start before `call_init`, so the recording covers
+    /// everything mods do.
+    pub fn start_recording(&mut self, path: &Path, hash_draws: bool) -> Result<()> {
+        let seed = self.storages.lock().unwrap().random.seed();
+        let header = RecordingHeader {
+            version: RECORDING_VERSION,
+            seed,
+            step: self.game_loop.step(),
+            max_steps: self.game_loop.max_steps(),
+            time_scale: self.game_loop.time_scale(),
+            paused: self.game_loop.paused(),
+            mods: self
+                .get_all_mod_info()
+                .into_iter()
+                .map(|info| (info.id, info.version))
+                .collect(),
+        };
+        self.recorder = Some(Recorder::create(path, &header, hash_draws)?);
+        info!("Recording to {}", path.display());
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    /// Runs a recording through the loaded mods without a window, starting
+    /// with `call_init`. Frames that were recorded with a draw hash are
+    /// checked against it and reported if they diverge.
+    ///
+    /// Only what goes through the host is reproduced: a mod reading real
+    /// time, or files and settings that changed since, can still diverge.
+    pub fn replay(&mut self, recording: &Recording) -> Result<ReplayReport> {
+        let span = error_span!("replay");
+        let _guard = span.enter();
+
+        let header = &recording.header;
+        let mods: Vec<(String, String)> = self
+            .get_all_mod_info()
+            .into_iter()
+            .map(|info| (info.id, info.version))
+            .collect();
+        if mods != header.mods {
+            warn!(
+                "Recorded with mods {:?}, replaying with {:?}",
+                header.mods, mods
+            );
+        }
+        self.set_seed(header.seed);
+        self.game_loop = GameLoop::new(header.step).with_max_steps(header.max_steps);
+        self.game_loop.set_time_scale(header.time_scale);
+        self.game_loop.set_paused(header.paused);
+        if let Some(first) = recording.frames.first() {
+            self.set_window_size(first.window_size);
+        }
+        self.call_init()?;
+
+        let mut report = ReplayReport::default();
+        for (index, frame) in recording.frames.iter().enumerate() {
+            for event in &frame.inputs {
+                self.handle_input(event.clone());
+            }
+            self.run_frame(frame.real_delta)?;
+            self.call_draw()?;
+
+            if let Some(expected) = frame.draw_hash {
+                let actual = replay::hash_draw_list(&self.storages.lock().unwrap().draw_list);
+                if actual != expected {
+                    warn!("Frame {} diverged from the recording", index);
+                    report.divergences.push(Divergence {
+                        frame: index,
+                        expected,
+                        actual,
+                    });
+                }
+            }
+            self.end_frame(frame.window_size);
+            report.frames += 1;
+        }
+
+        Ok(report)
+    }
+
+    /// Runs as many simulation steps as the real time since the last frame
+    /// calls for, see `GameLoop`, and records the interpolation alpha mods
+    /// read while drawing.
+    pub fn advance(&mut self, real_delta: Duration) -> Result<Frame> {
+        let frame = self.game_loop.advance(real_delta);
+        for _ in 0..frame.steps {
+            self.step(self.game_loop.step())?;
+        }
+        self.storages.lock().unwrap().timers.set_alpha(frame.alpha);
+
+        Ok(frame)
+    }
+
+    /// One simulation step: actions are sampled from the input collected
+    /// since the last step, then timers, mod updates, systems and save
+    /// requests run. Mods get the step length in milliseconds as delta.
+    pub fn step(&mut self, step: Duration) -> Result<()> {
+        let span = error_span!("step");
+        let _guard = span.enter();
+
+        {
+            let mut storages_guard = self.storages.lock().unwrap();
+            let storages = &mut *storages_guard;
+            storages.actions.update(&storages.input);
+        }
+
+        self.update_timers(step)?;
+        self.update_all_mods(step.as_secs_f32() * 1000.0)?;
+        self.run_systems()?;
+        self.process_slot_requests()?;
+
+        // Presses and releases count for one step, however many steps a
+        // frame runs.
+        let mut storages = self.storages.lock().unwrap();
+        storages.input.end_frame();
+        storages.world.commit();
+        Ok(())
+    }
+
+    /// Dispatches every event queued in the storages since the last call.
+    pub fn dispatch_events(&mut self) -> Result<()> {
+        let events = self.storages.lock().unwrap().events.take();
+        for event in events {
+            self.dispatch_event(&event)?;
+        }
 
         Ok(())
     }
@@ -108,9 +549,150 @@ impl ModManager {
         let span = error_span!("update_all_mods");
         let _guard = span.enter();
 
-        let mut registry = self.registry.lock().unwrap();
-        for (_id, mod_instance) in registry.get_all_mods_mut() {
-            mod_instance.update(delta_time).log()?;
+        let mods = self.registry.lock().unwrap().mods();
+        for (id, mod_handle) in mods {
+            self.set_current_mod(&id);
+            mod_handle
+                .lock()
+                .update(delta_time)
+                .log_msg("Failed to update mod")
+                .ok();
+        }
+
+        Ok(())
+    }
+
+    /// Runs the systems mods registered, stage by stage. World changes are
+    /// committed after every stage, so a system sees what the systems it
+    /// conflicts with wrote earlier in the step.
+    pub fn run_systems(&mut self) -> Result<()> {
+        let span = error_span!("run_systems");
+        let _guard = span.enter();
+
+        let order = self.registry.lock().unwrap().order().to_vec();
+        let stages = self.storages.lock().unwrap().ecs.schedule(&order);
+        for stage in stages {
+            for system in stage {
+                let Some(mod_handle) = self.registry.lock().unwrap().get_mod(&system.owner) else {
+                    continue;
+                };
+                self.set_current_mod(&system.owner);
+                let name = system.name.clone();
+                self.storages.lock().unwrap().ecs.begin_system(system);
+                let result = mod_handle.lock().run_system(&name);
+                self.storages.lock().unwrap().ecs.end_system();
+                result.log_msg("Failed to run system").ok();
+            }
+            self.storages.lock().unwrap().world.commit();
+        }
+
+        Ok(())
+    }
+
+    /// Writes a save slot with a section from every mod that has something
+    /// to save.
+    pub fn save_slot(&mut self, slot: &str) -> Result<()> {
+        let span = error_span!("save_slot", slot);
+        let _guard = span.enter();
+
+        let mods = self.registry.lock().unwrap().mods();
+        let mut save = SaveSlot::default();
+        for (id, mod_handle) in mods {
+            self.set_current_mod(&id);
+            let mut mod_instance = mod_handle.lock();
+            let Some(data) = mod_instance.save_section().log_msg("Failed to save mod")? else {
+                continue;
+            };
+            let section = Saved {
+                version: mod_instance.get_info().version,
+                data,
+            };
+            if let Err(e) = self
+                .storages
+                .lock()
+                .unwrap()
+                .saves
+                .check_section(&id, &section)
+            {
+                warn!("{}", e);
+                continue;
+            }
+            save.sections.insert(id, section);
+        }
+
+        let storages = self.storages.lock().unwrap();
+        save.timers = storages.timers.save();
+        save.random = Some(storages.random.save());
+        storages.saves.write_slot(slot, &save)
+    }
+
+    /// Hands every loaded mod its section of a save slot. Sections written by
+    /// another version of a mod go through its `migrate-section` first.
+    pub fn load_slot(&mut self, slot: &str) -> Result<()> {
+        let span = error_span!("load_slot", slot);
+        let _guard = span.enter();
+
+        let save = self.storages.lock().unwrap().saves.read_slot(slot)?;
+        let mods = self.registry.lock().unwrap().mods();
+        for id in save.sections.keys() {
+            if !mods.iter().any(|(loaded, _)| loaded == id) {
+                warn!("Skipping save section of {}, which isn't loaded", id);
+            }
+        }
+        let loaded: Vec<String> = mods.iter().map(|(id, _)| id.clone()).collect();
+        {
+            let mut storages = self.storages.lock().unwrap();
+            storages.timers.restore(&save.timers, &loaded);
+            if let Some(random) = &save.random {
+                storages.random.restore(random);
+            }
+        }
+
+        for (id, mod_handle) in mods {
+            let Some(section) = save.sections.get(&id) else {
+                continue;
+            };
+            self.set_current_mod(&id);
+            let mut mod_instance = mod_handle.lock();
+            let version = mod_instance.get_info().version;
+            let data = if section.version == version {
+                section.data.clone()
+            } else {
+                debug!(
+                    "Migrating save section of {} from {} to {}",
+                    id, section.version, version
+                );
+                match mod_instance
+                    .migrate_section(&section.version, &section.data)
+                    .log_msg("Failed to migrate save section")?
+                {
+                    Ok(data) => data,
+                    Err(e) => {
+                        warn!("{} can't migrate its save section: {}", id, e);
+                        continue;
+                    }
+                }
+            };
+            mod_instance
+                .load_section(&data)
+                .log_msg("Failed to load save section")?;
+        }
+
+        Ok(())
+    }
+
+    /// Saves and loads the slots mods asked for since the last call. A slot
+    /// that fails is logged, it doesn't stop the game.
+    pub fn process_slot_requests(&mut self) -> Result<()> {
+        let requests = std::mem::take(&mut self.storages.lock().unwrap().saves.requests);
+        for request in requests {
+            let result = match &request {
+                SlotRequest::Save(slot) => self.save_slot(slot),
+                SlotRequest::Load(slot) => self.load_slot(slot),
+            };
+            if let Err(e) = result {
+                warn!("{:?} failed: {}", request, e);
+            }
         }
 
         Ok(())
@@ -121,21 +703,30 @@ impl ModManager {
         let _guard = span.enter();
 
         let registry = self.registry.lock().unwrap();
-        let mod_instance: &Box<dyn ModInterface> =
-            registry.get_mod(mod_id).check_log("Mod not found")?;
-        Ok(mod_instance.as_ref().get_info())
+        let mod_handle = registry.get_mod(mod_id).check_log("Mod not found")?;
+        let info = mod_handle.lock().get_info();
+        Ok(info)
     }
 
     pub fn get_all_mod_info(&mut self) -> Vec<ModInfo> {
-        let mut registry = self.registry.lock().unwrap();
+        let mods = self.registry.lock().unwrap().mods();
         let mut mod_infos = Vec::new();
-        for (_, mod_instance) in registry.mods_mut_iter() {
-            let info = mod_instance.get_info();
+        for (_, mod_handle) in mods {
+            let info = mod_handle.lock().get_info();
             mod_infos.push(info);
         }
         mod_infos
     }
 
+    /// Hash of the component a mod was loaded from.
+    ///
+    /// Instance snapshots are not supported: `wasm_component_layer` keeps the
+    /// core instances of a component private, so the host can't read or
+    /// write their linear memory or globals.
+    pub fn component_hash(&self, mod_id: &str) -> Option<ComponentHash> {
+        self.registry.lock().unwrap().component_hash(mod_id)
+    }
+
     pub fn get_mod_count(&self) -> usize {
         let registry = self.registry.lock().unwrap();
         registry.get_all_mods().len()
@@ -145,9 +736,11 @@ impl ModManager {
         let span = error_span!("call_init");
         let _guard = span.enter();
 
-        let mut registry = self.registry.lock().unwrap();
-        for (_, mod_instance) in registry.mods_mut_iter() {
-            mod_instance
+        let mods = self.registry.lock().unwrap().mods();
+        for (id, mod_handle) in mods {
+            self.set_current_mod(&id);
+            mod_handle
+                .lock()
                 .init(self.context.clone())
                 .log_msg("Failed to init mod")?;
         }
@@ -157,14 +750,621 @@ impl ModManager {
     pub fn call_draw(&mut self) -> Result<()> {
         let span = error_span!("call_draw");
         let _guard = span.enter();
-        let mut registry = self.registry.lock().unwrap();
-        for (_, mod_instance) in registry.mods_mut_iter() {
-            mod_instance.draw().log_msg("Failed to draw mod")?;
+        let mods = self.registry.lock().unwrap().mods();
+        for (id, mod_handle) in mods {
+            self.set_current_mod(&id);
+            {
+                let mut storages = self.storages.lock().unwrap();
+                storages.render_states.set(&id, RenderState::default());
+            }
+            mod_handle.lock().draw().log_msg("Failed to draw mod").ok();
         }
+        ecs::draw_sprites(&mut self.storages.lock().unwrap());
         Ok(())
     }
 
     pub fn storages(&self) -> Arc<Mutex<Storages>> {
         self.storages.clone()
     }
+
+    /// Hands the mod order on to the parts of the host that depend on it.
+    fn update_mod_order(&self) {
+        let registry = self.registry.lock().unwrap();
+        let order = registry
+            .order()
+            .iter()
+            .map(|id| (id.clone(), registry.dependencies(id).to_vec()))
+            .collect();
+        let mut storages = self.storages.lock().unwrap();
+        storages.vfs.set_order(order);
+        storages.resources.set_order(registry.order().to_vec());
+    }
+
+    /// Records which mod host functions are called on behalf of.
+    fn set_current_mod(&self, mod_id: &str) {
+        let mut storages = self.storages.lock().unwrap();
+        storages.current_mod.set(mod_id.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        events::HotplugEvent,
+        gamepad::{GamepadAxis, GamepadButton, GamepadEvent, RumbleRequest, ScriptedGamepad},
+        render::{
+            BlendMode, DrawCommand, DrawItem, HeadlessRenderer, Layer, Rect, RenderState, Transform,
+        },
+    };
+    use std::collections::HashSet;
+
+    fn manager() -> ModManager {
+        let context = ModContext {
+            game_version: "0.1.0".to_string(),
+            api_version: "0.1.0".to_string(),
+        };
+        ModManager::new("mods", context).unwrap()
+    }
+
+    /// Host-side mod that walks with the left stick and a random stagger and
+    /// draws where it is, so what it draws depends on input and the seed.
+    struct Walker {
+        storages: Arc<Mutex<Storages>>,
+        x: f32,
+    }
+
+    impl ModInterface for Walker {
+        fn call_info(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_info(&self) -> ModInfo {
+            ModInfo {
+                id: "walker".to_string(),
+                version: "1.0.0".to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn init(&mut self, _context: ModContext) -> Result<(), Error> {
+            self.x = 0.0;
+            Ok(())
+        }
+
+        fn update(&mut self, delta_time: f32) -> Result<(), Error> {
+            let mut storages = self.storages.lock().unwrap();
+            let stick = storages.input.gamepads.axis_on_any(GamepadAxis::LeftX);
+            let stagger = storages.random.range("walker", -1, 2);
+            self.x += stick * delta_time + stagger as f32;
+            Ok(())
+        }
+
+        fn draw(&mut self) -> Result<(), Error> {
+            self.storages.lock().unwrap().draw_list.add(DrawItem {
+                layer: Layer::World,
+                z: 0,
+                mod_id: "walker".to_string(),
+                target: None,
+                command: DrawCommand::Rect {
+                    rect: Rect::new(self.x, 0.0, 1.0, 1.0),
+                    rotation: 0.0,
+                    color: (255, 255, 255, 255),
+                    blend_mode: Default::default(),
+                },
+            });
+            Ok(())
+        }
+
+        fn on_event(&mut self, event: &Event) -> Result<bool, Error> {
+            Ok(*event == Event::FocusLost)
+        }
+
+        fn handle_call(
+            &mut self,
+            _service: &str,
+            _method: &str,
+            _request: &[u8],
+        ) -> Result<Result<Vec<u8>, String>, Error> {
+            Ok(Err("No services".to_string()))
+        }
+
+        fn save_section(&mut self) -> Result<Option<Vec<u8>>, Error> {
+            Ok(None)
+        }
+
+        fn load_section(&mut self, _data: &[u8]) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn migrate_section(
+            &mut self,
+            _from_version: &str,
+            data: &[u8],
+        ) -> Result<Result<Vec<u8>, String>, Error> {
+            Ok(Ok(data.to_vec()))
+        }
+
+        fn run_system(&mut self, _name: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn shutdown(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    /// Host-side mod that fails whatever it is asked to do.
+    struct Broken;
+
+    impl ModInterface for Broken {
+        fn call_info(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_info(&self) -> ModInfo {
+            ModInfo {
+                id: "broken".to_string(),
+                version: "1.0.0".to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn init(&mut self, _context: ModContext) -> Result<(), Error> {
+            Err(Error::msg("Broken"))
+        }
+
+        fn update(&mut self, _delta_time: f32) -> Result<(), Error> {
+            Err(Error::msg("Broken"))
+        }
+
+        fn draw(&mut self) -> Result<(), Error> {
+            Err(Error::msg("Broken"))
+        }
+
+        fn on_event(&mut self, _event: &Event) -> Result<bool, Error> {
+            Err(Error::msg("Broken"))
+        }
+
+        fn handle_call(
+            &mut self,
+            _service: &str,
+            _method: &str,
+            _request: &[u8],
+        ) -> Result<Result<Vec<u8>, String>, Error> {
+            Err(Error::msg("Broken"))
+        }
+
+        fn save_section(&mut self) -> Result<Option<Vec<u8>>, Error> {
+            Err(Error::msg("Broken"))
+        }
+
+        fn load_section(&mut self, _data: &[u8]) -> Result<(), Error> {
+            Err(Error::msg("Broken"))
+        }
+
+        fn migrate_section(
+            &mut self,
+            _from_version: &str,
+            _data: &[u8],
+        ) -> Result<Result<Vec<u8>, String>, Error> {
+            Err(Error::msg("Broken"))
+        }
+
+        fn run_system(&mut self, _name: &str) -> Result<(), Error> {
+            Err(Error::msg("Broken"))
+        }
+
+        fn shutdown(&mut self) -> Result<(), Error> {
+            Err(Error::msg("Broken"))
+        }
+    }
+
+    /// Host-side mod that draws a unit square at the origin with its render
+    /// state, after setting whatever parts of the state it is given.
+    struct Painter {
+        id: &'static str,
+        storages: Arc<Mutex<Storages>>,
+        color: Option<(u8, u8, u8, u8)>,
+        blend_and_transform: Option<(BlendMode, Transform)>,
+    }
+
+    impl ModInterface for Painter {
+        fn call_info(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn get_info(&self) -> ModInfo {
+            ModInfo {
+                id: self.id.to_string(),
+                version: "1.0.0".to_string(),
+                ..Default::default()
+            }
+        }
+
+        fn init(&mut self, _context: ModContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn update(&mut self, _delta_time: f32) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn draw(&mut self) -> Result<(), Error> {
+            let mut storages = self.storages.lock().unwrap();
+            let state = storages.render_state();
+            if let Some(color) = self.color {
+                state.color = color;
+            }
+            if let Some((blend_mode, transform)) = self.blend_and_transform {
+                state.blend_mode = blend_mode;
+                state.transform = transform;
+            }
+            let (rect, rotation) = Rect::new(0.0, 0.0, 1.0, 1.0).transformed(&state.transform);
+            let item = DrawItem {
+                layer: state.layer,
+                z: state.z,
+                mod_id: self.id.to_string(),
+                target: state.target,
+                command: DrawCommand::Rect {
+                    rect,
+                    rotation,
+                    color: state.color,
+                    blend_mode: state.blend_mode,
+                },
+            };
+            storages.draw_list.add(item);
+            Ok(())
+        }
+
+        fn on_event(&mut self, _event: &Event) -> Result<bool, Error> {
+            Ok(false)
+        }
+
+        fn handle_call(
+            &mut self,
+            _service: &str,
+            _method: &str,
+            _request: &[u8],
+        ) -> Result<Result<Vec<u8>, String>, Error> {
+            Ok(Err("No services".to_string()))
+        }
+
+        fn save_section(&mut self) -> Result<Option<Vec<u8>>, Error> {
+            Ok(None)
+        }
+
+        fn load_section(&mut self, _data: &[u8]) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn migrate_section(
+            &mut self,
+            _from_version: &str,
+            data: &[u8],
+        ) -> Result<Result<Vec<u8>, String>, Error> {
+            Ok(Ok(data.to_vec()))
+        }
+
+        fn run_system(&mut self, _name: &str) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn shutdown(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn manager_with_walker() -> ModManager {
+        let manager = manager();
+        add_walker(&manager);
+        manager
+    }
+
+    fn add_walker(manager: &ModManager) {
+        let walker = Walker {
+            storages: manager.storages(),
+            x: 0.0,
+        };
+        manager
+            .registry
+            .lock()
+            .unwrap()
+            .register_mod("walker", Box::new(walker), ModManifest::default(), [0; 32])
+            .unwrap();
+    }
+
+    #[test]
+    fn failing_mods_dont_stop_the_others() {
+        let mut manager = manager();
+        manager
+            .registry
+            .lock()
+            .unwrap()
+            .register_mod("broken", Box::new(Broken), ModManifest::default(), [1; 32])
+            .unwrap();
+        add_walker(&manager);
+        {
+            let mut storages = manager.storages.lock().unwrap();
+            for id in ["broken", "walker"] {
+                storages
+                    .subscriptions
+                    .set(id, HashSet::from([EventKind::FocusLost]));
+            }
+        }
+
+        assert!(manager.dispatch_event(&Event::FocusLost).unwrap());
+        manager.update_all_mods(16.0).unwrap();
+        manager.call_draw().unwrap();
+        let storages = manager.storages.lock().unwrap();
+        assert!(storages
+            .draw_list
+            .iter()
+            .any(|item| item.mod_id == "walker"));
+    }
+
+    /// Registers "styled", which sets `styled_color`, blend mode None and a
+    /// move by 2 pixels, then "plain", which only sets `plain_color`.
+    fn add_painters(
+        manager: &ModManager,
+        styled_color: (u8, u8, u8, u8),
+        plain_color: Option<(u8, u8, u8, u8)>,
+    ) {
+        let moved = (BlendMode::None, Transform::translation(2.0, 0.0));
+        for (id, color, blend_and_transform) in [
+            ("styled", Some(styled_color), Some(moved)),
+            ("plain", plain_color, None),
+        ] {
+            let painter = Painter {
+                id,
+                storages: manager.storages(),
+                color,
+                blend_and_transform,
+            };
+            manager
+                .registry
+                .lock()
+                .unwrap()
+                .register_mod(id, Box::new(painter), ModManifest::default(), [0; 32])
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn draw_state_doesnt_leak_between_mods() {
+        let mut manager = manager();
+        add_painters(&manager, (255, 0, 0, 255), None);
+
+        manager.call_draw().unwrap();
+        let storages = manager.storages.lock().unwrap();
+        let plain = storages
+            .draw_list
+            .iter()
+            .find(|item| item.mod_id == "plain")
+            .unwrap();
+        assert_eq!(
+            plain.command,
+            DrawCommand::Rect {
+                rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+                rotation: 0.0,
+                color: RenderState::default().color,
+                blend_mode: BlendMode::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn draw_state_doesnt_leak_into_pixels() {
+        let mut manager = manager();
+        let red = (255, 0, 0, 255);
+        add_painters(&manager, red, Some((255, 255, 255, 128)));
+
+        manager.call_draw().unwrap();
+        let mut renderer = HeadlessRenderer::new(4, 1).unwrap();
+        {
+            let mut storages = manager.storages.lock().unwrap();
+            let storages = &mut *storages;
+            render::render(
+                &mut renderer,
+                &storages.draw_list,
+                &mut storages.render_targets,
+            )
+            .unwrap();
+        }
+
+        // The plain mod's half transparent white is alpha blended where it
+        // drew, not moved or copied like the styled mod's red.
+        assert_eq!(renderer.pixel(0, 0), Some((128, 128, 128, 128)));
+        assert_eq!(renderer.pixel(1, 0), Some((0, 0, 0, 0)));
+        assert_eq!(renderer.pixel(2, 0), Some(red));
+    }
+
+    fn replay(recording: &Recording) -> ReplayReport {
+        let mut manager = manager_with_walker();
+        // The seed comes from the recording.
+        manager.set_seed(0);
+        manager.replay(recording).unwrap()
+    }
+
+    #[test]
+    fn replay_uses_the_recorded_game_loop() {
+        let path = std::env::temp_dir().join(format!("replay-loop-{}.rec", std::process::id()));
+        let mut manager = manager_with_walker();
+        manager.game_loop().set_time_scale(2.5);
+        manager.game_loop().set_max_steps(3);
+        manager.start_recording(&path, true).unwrap();
+        manager.call_init().unwrap();
+        for _ in 0..8 {
+            manager.run_frame(Duration::from_millis(40)).unwrap();
+            manager.call_draw().unwrap();
+            manager.end_frame((800, 600));
+        }
+        manager.stop_recording();
+
+        let recording = Recording::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(recording.header.time_scale, 2.5);
+        assert_eq!(recording.header.max_steps, 3);
+        assert!(!recording.header.paused);
+
+        let report = replay(&recording);
+        assert_eq!(report.divergences, []);
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let path = std::env::temp_dir().join(format!("replay-{}.rec", std::process::id()));
+        let mut manager = manager_with_walker();
+        manager.set_seed(5);
+        manager.start_recording(&path, true).unwrap();
+        manager.call_init().unwrap();
+        for frame in 0..12u64 {
+            let input = match frame {
+                2 => Some(GamepadEvent::Connected {
+                    id: 0,
+                    name: "Pad".to_string(),
+                }),
+                4 => Some(GamepadEvent::AxisMoved {
+                    id: 0,
+                    axis: GamepadAxis::LeftX,
+                    value: 1.0,
+                }),
+                8 => Some(GamepadEvent::AxisMoved {
+                    id: 0,
+                    axis: GamepadAxis::LeftX,
+                    value: -0.5,
+                }),
+                _ => None,
+            };
+            if let Some(input) = input {
+                manager.handle_input(InputEvent::Gamepad(input));
+            }
+            manager
+                .run_frame(Duration::from_millis(10 + frame * 3))
+                .unwrap();
+            manager.call_draw().unwrap();
+            manager.end_frame((800, 600));
+        }
+        manager.stop_recording();
+
+        let recording = Recording::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(recording.header.seed, 5);
+        assert_eq!(
+            recording.header.mods,
+            [("walker".to_string(), "1.0.0".to_string())]
+        );
+        assert_eq!(recording.frames.len(), 12);
+        assert_eq!(recording.frames[4].inputs.len(), 1);
+        assert_eq!(recording.frames[4].real_delta, Duration::from_millis(22));
+        let hashes: HashSet<Option<u64>> = recording
+            .frames
+            .iter()
+            .map(|frame| frame.draw_hash)
+            .collect();
+        assert!(!hashes.contains(&None));
+        assert!(hashes.len() > 2, "the walker should move");
+
+        let report = replay(&recording);
+        assert_eq!(report.frames, 12);
+        assert_eq!(report.divergences, []);
+
+        let mut tampered = recording.clone();
+        tampered.frames[6].draw_hash = Some(0);
+        let report = replay(&tampered);
+        assert_eq!(report.divergences.len(), 1);
+        assert_eq!(report.divergences[0].frame, 6);
+        assert_eq!(report.divergences[0].expected, 0);
+        assert_eq!(
+            Some(report.divergences[0].actual),
+            recording.frames[6].draw_hash
+        );
+
+        let mut other_input = recording.clone();
+        other_input.frames[8].inputs.clear();
+        let report = replay(&other_input);
+        assert!(!report.divergences.is_empty());
+        assert!(report
+            .divergences
+            .iter()
+            .all(|divergence| divergence.frame >= 8));
+
+        let mut other_seed = recording;
+        other_seed.header.seed = 6;
+        assert!(!replay(&other_seed).divergences.is_empty());
+    }
+
+    #[test]
+    fn pump_gamepads() {
+        let mut manager = manager();
+        let mut gamepad = ScriptedGamepad::new([
+            vec![
+                GamepadEvent::Connected {
+                    id: 3,
+                    name: "Pad".to_string(),
+                },
+                GamepadEvent::ButtonDown {
+                    id: 3,
+                    button: GamepadButton::A,
+                },
+                GamepadEvent::AxisMoved {
+                    id: 3,
+                    axis: GamepadAxis::LeftX,
+                    value: 1.0,
+                },
+                GamepadEvent::AxisMoved {
+                    id: 3,
+                    axis: GamepadAxis::LeftY,
+                    value: 0.1,
+                },
+            ],
+            vec![GamepadEvent::ButtonUp {
+                id: 3,
+                button: GamepadButton::A,
+            }],
+        ]);
+
+        manager.pump_gamepads(&mut gamepad);
+        {
+            let storages = manager.storages.lock().unwrap();
+            let gamepads = &storages.input.gamepads;
+            assert_eq!(gamepads.connected(), vec![3]);
+            assert_eq!(gamepads.name(3), Some("Pad"));
+            assert!(gamepads.is_button_pressed(3, GamepadButton::A));
+            assert_eq!(gamepads.axis(3, GamepadAxis::LeftX), 1.0);
+            assert_eq!(gamepads.axis(3, GamepadAxis::LeftY), 0.0);
+            assert!(storages
+                .events
+                .iter()
+                .any(|event| *event == Event::Gamepad(HotplugEvent::Connected(3))));
+        }
+        manager.end_frame((800, 600));
+
+        let rumble = RumbleRequest {
+            id: 3,
+            low_frequency: 1.0,
+            high_frequency: 0.5,
+            duration_ms: 100,
+        };
+        manager
+            .storages
+            .lock()
+            .unwrap()
+            .input
+            .gamepads
+            .request_rumble(rumble.clone());
+        manager.pump_gamepads(&mut gamepad);
+        let storages = manager.storages.lock().unwrap();
+        assert!(!storages.input.gamepads.is_button_down(3, GamepadButton::A));
+        assert!(storages
+            .input
+            .gamepads
+            .is_button_released(3, GamepadButton::A));
+        assert_eq!(gamepad.rumbles(), [rumble]);
+
+        // Rumble requests are handed on once.
+        drop(storages);
+        manager.pump_gamepads(&mut gamepad);
+        assert_eq!(gamepad.rumbles().len(), 1);
+    }
 }
@@ -1,5 +1,11 @@
-use super::{funcs, ModContext, ModInfo, ModInterface, ModRegistry, Storages};
+use super::{
+    events::{Event, HotplugEvent, MouseEvent},
+    funcs::{self, bus::message_value},
+    registry::ComponentHash,
+    ModContext, ModInfo, ModInterface, ModManifest, ModRegistry, Storages,
+};
 use anyhow::{Error, Result};
+use sha2::{Digest, Sha256};
 use std::{
     cell::RefCell,
     path::Path,
@@ -43,19 +49,45 @@ impl ModLoader {
         debug!("Loading mod: {}", path.display());
 
         let bytes = std::fs::read(path).log_msg("Failed to read file")?;
+        let component_hash: ComponentHash = Sha256::digest(&bytes).into();
+        let manifest = ModManifest::load(path)?;
 
         let mut store = Store::new(&self.engine, ());
         let component =
             Component::new(&self.engine, bytes.as_slice()).log_msg("Failed to create component")?;
         let mut linker = Linker::default();
-        funcs::register(&mut linker, &mut store, self.storages.clone()).log()?;
+        funcs::register(
+            &mut linker,
+            &mut store,
+            self.storages.clone(),
+            self.registry.clone(),
+        )
+        .log()?;
 
         let instance = linker.instantiate(&mut store, &component).log()?;
-        let mod_info = ModInfo::default();
-        let mut mod_wrapper = WasmModWrapper::new(store, instance, mod_info.clone());
+        let mut mod_wrapper = WasmModWrapper::new(store, instance, ModInfo::default());
         mod_wrapper.call_info().log()?;
+        let mut mod_info = mod_wrapper.get_info();
+        let settings = manifest.settings.clone();
         let mut registry = self.registry.lock().unwrap();
-        registry.register_mod(&mod_info.id, Box::new(mod_wrapper))?;
+        mod_info.id = registry.register_mod(
+            &mod_info.id,
+            Box::new(mod_wrapper),
+            manifest,
+            component_hash,
+        )?;
+        drop(registry);
+        let mut storages = self.storages.lock().unwrap();
+        storages.saves.set_version(&mod_info.id, &mod_info.version);
+        // Before `init`, so the mod can read its settings there.
+        storages.settings.add_mod(&mod_info.id, settings);
+        // Assets and resources ship next to the wasm, in `<mod>/assets` and
+        // `<mod>/resources`.
+        let mod_dir = path.with_extension("");
+        storages.vfs.add_mod(&mod_info.id, &mod_dir.join("assets"));
+        storages
+            .resources
+            .add_mod(&mod_info.id, &mod_dir.join("resources"));
 
         Ok(mod_info)
     }
@@ -64,16 +96,40 @@ impl ModLoader {
         let span = error_span!("unload_mod", mod_id = mod_id);
         let _guard = span.enter();
 
-        let mut registry = self
-            .registry
-            .lock()
-            .log_msg("Failed to lock registry")
-            .unwrap();
-
-        if let Some(mod_instance) = registry.get_mut_mod(mod_id) {
-            mod_instance.shutdown().log_msg("Failed to shutdown mod")?;
+        // Not kept locked while the mod shuts down, it may still call
+        // services.
+        let mod_handle = self.registry.lock().unwrap().get_mod(mod_id);
+        if let Some(mod_handle) = mod_handle {
+            {
+                let mut storages = self.storages.lock().unwrap();
+                storages.current_mod.set(mod_id.to_string());
+            }
+            mod_handle
+                .lock()
+                .shutdown()
+                .log_msg("Failed to shutdown mod")?;
+        }
+        self.registry.lock().unwrap().unregister_mod(mod_id);
+        {
+            let mut storages = self.storages.lock().unwrap();
+            storages.render_states.remove(mod_id);
+            storages.render_targets.release_mod(mod_id);
+            storages.subscriptions.remove(mod_id);
+            storages.actions.release_mod(mod_id);
+            storages.bus.release_mod(mod_id);
+            storages.saves.release_mod(mod_id);
+            storages.vfs.release_mod(mod_id);
+            storages.resources.release_mod(mod_id);
+            storages.settings.release_mod(mod_id);
+            storages.audio.release_mod(mod_id);
+            storages.timers.release_mod(mod_id);
+            storages.random.release_mod(mod_id);
+            // Despawns are staged by the mod itself, so they come after its
+            // staged changes are dropped.
+            storages.world.release_mod(mod_id);
+            let storages = &mut *storages;
+            storages.ecs.release_mod(&mut storages.world, mod_id);
         }
-        registry.unregister_mod(mod_id);
 
         Ok(())
     }
@@ -232,6 +288,169 @@ impl<'a> ModInterface for WasmModWrapper<'a> {
         Ok(())
     }
 
+    fn on_event(&mut self, event: &Event) -> Result<bool, Error> {
+        let span = error_span!("on_event", mod_id = self.info.id.clone());
+        let _guard = span.enter();
+
+        // Mods built against an older API don't export the handler.
+        let Some(method_on_event) = self.get_interface().func("[method]main.on-event") else {
+            return Ok(false);
+        };
+        let event_type = method_on_event
+            .ty()
+            .params()
+            .get(1)
+            .cloned()
+            .check_log("Unexpected \"main.on-event\" signature")?;
+        let mut arguments = self.arguments.clone();
+        // Nor does it know kinds of event added after it was built.
+        let Some(event) = event_value(event, &event_type).log()? else {
+            return Ok(false);
+        };
+        arguments.push(event);
+        let mut results = vec![Value::Bool(false)];
+        method_on_event
+            .call(&mut self.store, &arguments, &mut results)
+            .log()?;
+
+        match results[0] {
+            Value::Bool(consumed) => Ok(consumed),
+            _ => Err(Error::msg("Unexpected result type")).log(),
+        }
+    }
+
+    fn save_section(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let span = error_span!("save_section", mod_id = self.info.id.clone());
+        let _guard = span.enter();
+
+        let Some(method_save_section) = self.get_interface().func("[method]main.save-section")
+        else {
+            return Ok(None);
+        };
+        let mut results = vec![Value::Bool(false)];
+        method_save_section
+            .call(&mut self.store, &self.arguments, &mut results)
+            .log()?;
+
+        match &results[0] {
+            Value::Option(section) => match &**section {
+                Some(Value::List(data)) => Ok(Some(data.typed::<u8>()?.to_vec())),
+                None => Ok(None),
+                _ => Err(Error::msg("Unexpected result type")).log(),
+            },
+            _ => Err(Error::msg("Unexpected result type")).log(),
+        }
+    }
+
+    fn load_section(&mut self, data: &[u8]) -> Result<(), Error> {
+        let span = error_span!("load_section", mod_id = self.info.id.clone());
+        let _guard = span.enter();
+
+        let Some(method_load_section) = self.get_interface().func("[method]main.load-section")
+        else {
+            return Ok(());
+        };
+        let mut arguments = self.arguments.clone();
+        arguments.push(Value::List(List::from(data)));
+        method_load_section
+            .call(&mut self.store, &arguments, &mut [])
+            .log()?;
+
+        Ok(())
+    }
+
+    fn migrate_section(
+        &mut self,
+        from_version: &str,
+        data: &[u8],
+    ) -> Result<Result<Vec<u8>, String>, Error> {
+        let span = error_span!(
+            "migrate_section",
+            mod_id = self.info.id.clone(),
+            from_version
+        );
+        let _guard = span.enter();
+
+        // Without a hook the data is assumed to still fit.
+        let Some(method_migrate_section) =
+            self.get_interface().func("[method]main.migrate-section")
+        else {
+            return Ok(Ok(data.to_vec()));
+        };
+        let mut arguments = self.arguments.clone();
+        arguments.push(Value::String(from_version.into()));
+        arguments.push(Value::List(List::from(data)));
+        let mut results = vec![Value::Bool(false)];
+        method_migrate_section
+            .call(&mut self.store, &arguments, &mut results)
+            .log()?;
+
+        let Value::Result(result) = &results[0] else {
+            return Err(Error::msg("Unexpected result type")).log();
+        };
+        match &**result {
+            Ok(Some(Value::List(data))) => Ok(Ok(data.typed::<u8>()?.to_vec())),
+            Err(Some(Value::String(error))) => Ok(Err(error.to_string())),
+            _ => Err(Error::msg("Unexpected result type")).log(),
+        }
+    }
+
+    fn run_system(&mut self, name: &str) -> Result<(), Error> {
+        let span = error_span!("run_system", mod_id = self.info.id.clone(), name);
+        let _guard = span.enter();
+
+        let method_run_system = self
+            .get_interface()
+            .func("[method]main.run-system")
+            .check_log("Unable to get \"main.run-system\" from mod")?;
+        let mut arguments = self.arguments.clone();
+        arguments.push(Value::String(name.into()));
+        method_run_system
+            .call(&mut self.store, &arguments, &mut [])
+            .log()?;
+
+        Ok(())
+    }
+
+    fn handle_call(
+        &mut self,
+        service: &str,
+        method: &str,
+        request: &[u8],
+    ) -> Result<Result<Vec<u8>, String>, Error> {
+        let span = error_span!(
+            "handle_call",
+            mod_id = self.info.id.clone(),
+            service,
+            method
+        );
+        let _guard = span.enter();
+
+        let Some(method_handle_call) = self.get_interface().func("[method]main.handle-call") else {
+            return Ok(Err(format!(
+                "{} doesn't handle service calls",
+                self.info.id
+            )));
+        };
+        let mut arguments = self.arguments.clone();
+        arguments.push(Value::String(service.into()));
+        arguments.push(Value::String(method.into()));
+        arguments.push(Value::List(List::from(request)));
+        let mut results = vec![Value::Bool(false)];
+        method_handle_call
+            .call(&mut self.store, &arguments, &mut results)
+            .log()?;
+
+        let Value::Result(result) = &results[0] else {
+            return Err(Error::msg("Unexpected result type")).log();
+        };
+        match &**result {
+            Ok(Some(Value::List(response))) => Ok(Ok(response.typed::<u8>()?.to_vec())),
+            Err(Some(Value::String(error))) => Ok(Err(error.to_string())),
+            _ => Err(Error::msg("Unexpected result type")).log(),
+        }
+    }
+
     fn shutdown(&mut self) -> Result<(), Error> {
         let span = error_span!("shutdown", mod_id = self.info.id.clone());
         let _guard = span.enter();
@@ -247,3 +466,109 @@ impl<'a> ModInterface for WasmModWrapper<'a> {
         Ok(())
     }
 }
+
+/// Payload type of a variant case, by case index.
+fn case_type(ty: &VariantType, case: usize) -> Result<Option<ValueType>, Error> {
+    Ok(ty.cases().get(case).check_log("Missing variant case")?.ty())
+}
+
+/// Index of the case called `name`. Cases are looked up by name, so a
+/// guest built against another version of the WIT still gets the case it
+/// knows under that name.
+fn case_index(ty: &VariantType, name: &str) -> Option<usize> {
+    ty.cases().iter().position(|case| case.name() == name)
+}
+
+fn mouse_event_value(event: &MouseEvent, ty: VariantType) -> Result<Value, Error> {
+    let name = match event {
+        MouseEvent::Moved { .. } => "moved",
+        MouseEvent::ButtonDown(_) => "button-down",
+        MouseEvent::ButtonUp(_) => "button-up",
+        MouseEvent::Wheel { .. } => "wheel",
+    };
+    let case = case_index(&ty, name).check_log("Missing mouse event case")?;
+    let payload = match (event, case_type(&ty, case)?) {
+        (
+            MouseEvent::Moved { x, y } | MouseEvent::Wheel { x, y },
+            Some(ValueType::Tuple(tuple_type)),
+        ) => Value::Tuple(Tuple::new(tuple_type, [Value::F32(*x), Value::F32(*y)])?),
+        (
+            MouseEvent::ButtonDown(button) | MouseEvent::ButtonUp(button),
+            Some(ValueType::Variant(button_type)),
+        ) => Value::Variant(Variant::new(button_type, *button as usize, None)?),
+        _ => return Err(Error::msg("Unexpected mouse event type")),
+    };
+
+    Ok(Value::Variant(Variant::new(ty, case, Some(payload))?))
+}
+
+/// Builds the guest value of `event`, or `None` if the guest's event type
+/// has no case for its kind. Types come from the guest's function signature
+/// so they match its WIT exactly.
+fn event_value(event: &Event, ty: &ValueType) -> Result<Option<Value>, Error> {
+    let ValueType::Variant(ty) = ty else {
+        return Err(Error::msg("Event type is not a variant"));
+    };
+    let Some(case) = case_index(ty, event.kind().name()) else {
+        return Ok(None);
+    };
+    let payload = match (event, case_type(ty, case)?) {
+        (
+            Event::Key {
+                keycode,
+                scancode,
+                down,
+            },
+            Some(ValueType::Record(record_type)),
+        ) => Some(Value::Record(Record::new(
+            record_type,
+            [
+                ("keycode", Value::String(keycode.as_str().into())),
+                ("scancode", Value::String(scancode.as_str().into())),
+                ("down", Value::Bool(*down)),
+            ],
+        )?)),
+        (Event::Mouse(mouse_event), Some(ValueType::Variant(mouse_type))) => {
+            Some(mouse_event_value(mouse_event, mouse_type)?)
+        }
+        (Event::WindowResized { width, height }, Some(ValueType::Tuple(tuple_type))) => {
+            Some(Value::Tuple(Tuple::new(
+                tuple_type,
+                [Value::U32(*width), Value::U32(*height)],
+            )?))
+        }
+        (Event::FocusLost, None) => None,
+        (Event::Message(message), Some(ValueType::Record(_))) => Some(message_value(message)?),
+        (Event::Gamepad(hotplug), Some(ValueType::Variant(hotplug_type))) => {
+            let (name, id) = match hotplug {
+                HotplugEvent::Connected(id) => ("connected", *id),
+                HotplugEvent::Disconnected(id) => ("disconnected", *id),
+            };
+            let hotplug_case =
+                case_index(&hotplug_type, name).check_log("Missing hotplug event case")?;
+            Some(Value::Variant(Variant::new(
+                hotplug_type,
+                hotplug_case,
+                Some(Value::U32(id)),
+            )?))
+        }
+        (
+            Event::ModLoaded(name) | Event::ModUnloaded(name) | Event::ResourceChanged(name),
+            Some(ValueType::String),
+        ) => Some(Value::String(name.as_str().into())),
+        (Event::Timer(timer), Some(ValueType::U64)) => Some(Value::U64(*timer)),
+        (Event::SettingsChanged(names), Some(ValueType::List(list_type))) => {
+            Some(Value::List(List::new(
+                list_type,
+                names.iter().map(|name| Value::String(name.as_str().into())),
+            )?))
+        }
+        _ => return Err(Error::msg("Event doesn't match the mod's event type")),
+    };
+
+    Ok(Some(Value::Variant(Variant::new(
+        ty.clone(),
+        case,
+        payload,
+    )?)))
+}
@@ -0,0 +1,36 @@
+use crate::settings::SettingSchema;
+use anyhow::{Error, Result};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path};
+use utils::logging::*;
+
+/// Mod metadata the host needs before running any mod code. Read from a
+/// `<mod>.toml` next to the mod's `.wasm`; a mod without one gets the
+/// defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ModManifest {
+    /// Ids of mods that have to come before this one.
+    pub dependencies: Vec<String>,
+    /// Settings the user can change, by name.
+    pub settings: BTreeMap<String, SettingSchema>,
+}
+
+impl ModManifest {
+    pub fn load(wasm_path: &Path) -> Result<Self, Error> {
+        let path = wasm_path.with_extension("toml");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let text = std::fs::read_to_string(&path).log_msg("Failed to read manifest")?;
+        let manifest: Self = toml::from_str(&text).log_msg("Failed to parse manifest")?;
+        for (name, schema) in &manifest.settings {
+            schema
+                .default_value()
+                .map_err(|e| Error::msg(format!("Setting {}: {}", name, e)))
+                .log()?;
+        }
+        Ok(manifest)
+    }
+}
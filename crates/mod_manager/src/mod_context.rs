@@ -1,3 +1,4 @@
+use crate::events::Event;
 use anyhow::Error;
 
 #[derive(Debug, Clone)]
@@ -33,5 +34,27 @@ pub trait ModInterface {
     fn init(&mut self, context: ModContext) -> Result<(), Error>;
     fn update(&mut self, delta_time: f32) -> Result<(), Error>;
     fn draw(&mut self) -> Result<(), Error>;
+    /// Hands an event to the mod. Returns whether the mod consumed it.
+    fn on_event(&mut self, event: &Event) -> Result<bool, Error>;
+    /// Handles a call another mod made to a service this mod provides. The
+    /// inner result is the answer of the mod itself.
+    fn handle_call(
+        &mut self,
+        service: &str,
+        method: &str,
+        request: &[u8],
+    ) -> Result<Result<Vec<u8>, String>, Error>;
+    /// Section of the mod for a save slot, `None` if it has nothing to save.
+    fn save_section(&mut self) -> Result<Option<Vec<u8>>, Error>;
+    /// Restores the mod from its section of a save slot.
+    fn load_section(&mut self, data: &[u8]) -> Result<(), Error>;
+    /// Converts a save section written by another version of the mod.
+    fn migrate_section(
+        &mut self,
+        from_version: &str,
+        data: &[u8],
+    ) -> Result<Result<Vec<u8>, String>, Error>;
+    /// Runs one of the systems the mod registered.
+    fn run_system(&mut self, name: &str) -> Result<(), Error>;
     fn shutdown(&mut self) -> Result<(), Error>;
 }
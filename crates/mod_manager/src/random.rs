@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Global seed and how far every mod's stream got, for a save slot.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RandomState {
+    pub seed: u64,
+    pub streams: BTreeMap<String, u64>,
+}
+
+/// Deterministic random numbers for mods. Every mod draws from its own
+/// SplitMix64 stream, derived from the global seed and the mod id, so what
+/// one mod draws doesn't change what another one gets and a run can be
+/// reproduced from the seed.
+#[derive(Debug, Default)]
+pub struct Random {
+    seed: u64,
+    streams: HashMap<String, u64>,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: HashMap::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Changes the global seed, every stream starts over.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.streams.clear();
+    }
+
+    /// Drops the stream of a mod, it starts over if the mod is loaded again.
+    pub fn release_mod(&mut self, mod_id: &str) {
+        self.streams.remove(mod_id);
+    }
+
+    pub fn next_u64(&mut self, mod_id: &str) -> u64 {
+        let seed = self.seed;
+        let state = self
+            .streams
+            .entry(mod_id.to_string())
+            .or_insert_with(|| stream_seed(seed, mod_id));
+        splitmix64(state)
+    }
+
+    pub fn next_u32(&mut self, mod_id: &str) -> u32 {
+        (self.next_u64(mod_id) >> 32) as u32
+    }
+
+    /// Uniform in `0.0..1.0`.
+    pub fn next_f32(&mut self, mod_id: &str) -> f32 {
+        (self.next_u64(mod_id) >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in `min..max`, `min` if the range is empty.
+    pub fn range(&mut self, mod_id: &str, min: i32, max: i32) -> i32 {
+        if max <= min {
+            return min;
+        }
+        let span = (max as i64 - min as i64) as u64;
+        // Rejects the top end that doesn't divide evenly, so every value is
+        // equally likely.
+        let limit = u64::MAX - u64::MAX % span;
+        loop {
+            let value = self.next_u64(mod_id);
+            if value < limit {
+                return (min as i64 + (value % span) as i64) as i32;
+            }
+        }
+    }
+
+    pub fn save(&self) -> RandomState {
+        RandomState {
+            seed: self.seed,
+            streams: self
+                .streams
+                .iter()
+                .map(|(mod_id, state)| (mod_id.clone(), *state))
+                .collect(),
+        }
+    }
+
+    /// Continues from a save slot. Mods that had no stream then start a
+    /// fresh one from the saved seed.
+    pub fn restore(&mut self, state: &RandomState) {
+        self.seed = state.seed;
+        self.streams = state
+            .streams
+            .iter()
+            .map(|(mod_id, state)| (mod_id.clone(), *state))
+            .collect();
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// FNV-1a. Unlike the std hasher it is the same across runs and platforms,
+/// so it can be stored and compared later.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Start of a mod's stream.
+fn stream_seed(seed: u64, mod_id: &str) -> u64 {
+    let mut state = seed ^ stable_hash(mod_id.as_bytes());
+    splitmix64(&mut state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draws(random: &mut Random, mod_id: &str) -> Vec<u64> {
+        (0..8).map(|_| random.next_u64(mod_id)).collect()
+    }
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let mut a = Random::new(42);
+        let mut b = Random::new(42);
+        assert_eq!(draws(&mut a, "a"), draws(&mut b, "a"));
+        assert_ne!(draws(&mut a, "a"), draws(&mut Random::new(43), "a"));
+    }
+
+    #[test]
+    fn streams_are_independent() {
+        let mut a = Random::new(42);
+        let mut b = Random::new(42);
+        b.next_u64("other");
+        assert_eq!(draws(&mut a, "a"), draws(&mut b, "a"));
+        assert_ne!(draws(&mut a, "a"), draws(&mut a, "b"));
+    }
+
+    #[test]
+    fn save_and_restore() {
+        let mut random = Random::new(7);
+        random.next_u64("a");
+        let state = random.save();
+        let expected = draws(&mut random, "a");
+
+        let mut restored = Random::new(0);
+        restored.restore(&state);
+        assert_eq!(draws(&mut restored, "a"), expected);
+
+        random.set_seed(7);
+        assert_eq!(random.next_u64("a"), Random::new(7).next_u64("a"));
+    }
+
+    #[test]
+    fn range_bounds() {
+        let mut random = Random::new(1);
+        for _ in 0..1000 {
+            assert!((-3..5).contains(&random.range("a", -3, 5)));
+            let value = random.range("a", i32::MIN, i32::MAX);
+            assert!(value < i32::MAX);
+        }
+        assert_eq!(random.range("a", 4, 4), 4);
+        assert_eq!(random.range("a", 4, -4), 4);
+        assert_eq!(random.range("a", i32::MAX, i32::MIN), i32::MAX);
+
+        let mut seen = [false; 3];
+        for _ in 0..100 {
+            seen[random.range("a", 0, 3) as usize] = true;
+        }
+        assert_eq!(seen, [true; 3]);
+    }
+
+    #[test]
+    fn floats_are_below_one() {
+        let mut random = Random::new(1);
+        for _ in 0..1000 {
+            assert!((0.0..1.0).contains(&random.next_f32("a")));
+        }
+    }
+}
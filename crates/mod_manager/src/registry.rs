@@ -1,10 +1,51 @@
-use super::ModInterface;
+use super::{ModInterface, ModManifest};
 use anyhow::Error;
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+};
 use tracing::{warn, warn_span};
 
+/// Shared handle to a loaded mod. The registry is only locked to look mods
+/// up, so host functions can call into another mod while the manager is
+/// running one. A mod that is already running can't be borrowed again,
+/// which is how reentrant calls are caught.
+///
+/// Mods aren't `Send`, so neither are handles, but the registry they are
+/// cloned from can be reached from any thread through the storages lock.
+/// The count and the lock are atomic so those clones can't race.
+#[derive(Clone)]
+pub struct ModHandle(Arc<Mutex<Box<dyn ModInterface>>>);
+
+impl ModHandle {
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn new(mod_instance: Box<dyn ModInterface>) -> Self {
+        Self(Arc::new(Mutex::new(mod_instance)))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, Box<dyn ModInterface>> {
+        self.0.lock().unwrap()
+    }
+
+    /// Locks the mod unless it is running already.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, Box<dyn ModInterface>>> {
+        self.0.try_lock().ok()
+    }
+}
+
+/// SHA-256 of the component bytes a mod was loaded from.
+pub type ComponentHash = [u8; 32];
+
 pub struct ModRegistry {
-    mods: HashMap<String, Box<dyn ModInterface>>,
+    mods: HashMap<String, ModHandle>,
+    manifests: HashMap<String, ModManifest>,
+    component_hashes: HashMap<String, ComponentHash>,
+    /// Provider mod id by service name.
+    services: BTreeMap<String, String>,
+    /// Mod ids in load order, or in dependency order once sorted. Every
+    /// per-mod loop goes through this so draw submission order does not
+    /// depend on hashing.
+    order: Vec<String>,
 }
 
 unsafe impl Send for ModRegistry {}
@@ -14,14 +55,22 @@ impl ModRegistry {
     pub fn new() -> Self {
         Self {
             mods: HashMap::new(),
+            manifests: HashMap::new(),
+            component_hashes: HashMap::new(),
+            services: BTreeMap::new(),
+            order: Vec::new(),
         }
     }
 
+    /// Registers a mod and returns the id it ended up under, which differs
+    /// from `mod_id` if that was already taken.
     pub fn register_mod(
         &mut self,
         mod_id: &str,
         mod_instance: Box<dyn ModInterface>,
-    ) -> Result<(), Error> {
+        manifest: ModManifest,
+        component_hash: ComponentHash,
+    ) -> Result<String, Error> {
         let mut current_mod_id = mod_id.to_string();
         if self.mods.contains_key(mod_id) {
             while self.mods.contains_key(&current_mod_id) {
@@ -37,31 +86,107 @@ impl ModRegistry {
             );
         }
 
-        self.mods.insert(current_mod_id, mod_instance);
-        Ok(())
+        self.order.push(current_mod_id.clone());
+        self.manifests.insert(current_mod_id.clone(), manifest);
+        self.component_hashes
+            .insert(current_mod_id.clone(), component_hash);
+        self.mods
+            .insert(current_mod_id.clone(), ModHandle::new(mod_instance));
+        Ok(current_mod_id)
     }
 
-    pub fn unregister_mod(&mut self, mod_id: &str) -> Option<Box<dyn ModInterface>> {
+    pub fn unregister_mod(&mut self, mod_id: &str) -> Option<ModHandle> {
+        self.order.retain(|id| id != mod_id);
+        self.manifests.remove(mod_id);
+        self.component_hashes.remove(mod_id);
+        self.services.retain(|_, provider| provider != mod_id);
         self.mods.remove(mod_id)
     }
 
-    pub fn get_mod(&self, mod_id: &str) -> Option<&Box<dyn ModInterface>> {
-        self.mods.get(mod_id)
+    pub fn component_hash(&self, mod_id: &str) -> Option<ComponentHash> {
+        self.component_hashes.get(mod_id).copied()
     }
 
-    pub fn get_mut_mod(&mut self, mod_id: &str) -> Option<&mut Box<dyn ModInterface>> {
-        self.mods.get_mut(mod_id)
+    pub fn dependencies(&self, mod_id: &str) -> &[String] {
+        self.manifests
+            .get(mod_id)
+            .map(|manifest| manifest.dependencies.as_slice())
+            .unwrap_or_default()
     }
 
-    pub fn get_all_mods(&self) -> &HashMap<String, Box<dyn ModInterface>> {
-        &self.mods
+    /// Reorders mods so every mod comes after its dependencies, keeping load
+    /// order where dependencies don't decide. Missing dependencies are
+    /// ignored, mods in a cycle keep their load order.
+    pub fn sort_by_dependencies(&mut self) {
+        let span = warn_span!("sort_by_dependencies");
+        let _guard = span.enter();
+
+        for id in &self.order {
+            for dependency in self.dependencies(id) {
+                if !self.mods.contains_key(dependency) {
+                    warn!("Mod {} depends on {}, which isn't loaded", id, dependency);
+                }
+            }
+        }
+
+        let mut sorted: Vec<String> = Vec::with_capacity(self.order.len());
+        let mut remaining = self.order.clone();
+        while !remaining.is_empty() {
+            let ready = remaining.iter().position(|id| {
+                self.dependencies(id).iter().all(|dependency| {
+                    sorted.contains(dependency) || !self.mods.contains_key(dependency)
+                })
+            });
+            match ready {
+                Some(index) => sorted.push(remaining.remove(index)),
+                None => {
+                    warn!("Dependency cycle between mods: {}", remaining.join(", "));
+                    sorted.append(&mut remaining);
+                }
+            }
+        }
+
+        self.order = sorted;
+    }
+
+    pub fn order(&self) -> &[String] {
+        &self.order
+    }
+
+    /// Registers `service` as provided by `mod_id`. A service name can only
+    /// have one provider.
+    pub fn provide_service(&mut self, mod_id: &str, service: &str) -> Result<(), String> {
+        match self.services.get(service) {
+            Some(provider) if provider != mod_id => Err(format!(
+                "Service {} is already provided by {}",
+                service, provider
+            )),
+            _ => {
+                self.services
+                    .insert(service.to_string(), mod_id.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn service_provider(&self, service: &str) -> Option<&str> {
+        self.services.get(service).map(String::as_str)
     }
 
-    pub fn get_all_mods_mut(&mut self) -> &mut HashMap<String, Box<dyn ModInterface>> {
-        &mut self.mods
+    pub fn get_mod(&self, mod_id: &str) -> Option<ModHandle> {
+        self.mods.get(mod_id).cloned()
+    }
+
+    pub fn get_all_mods(&self) -> &HashMap<String, ModHandle> {
+        &self.mods
     }
 
-    pub fn mods_mut_iter(&mut self) -> impl Iterator<Item = (&String, &mut Box<dyn ModInterface>)> {
-        self.mods.iter_mut()
+    /// Every mod with its id, in order. The handles are cloned so the
+    /// registry doesn't have to stay locked while the mods run.
+    pub fn mods(&self) -> Vec<(String, ModHandle)> {
+        self.order
+            .iter()
+            .filter_map(|id| Some((id.clone(), self.mods.get(id)?.clone())))
+            .collect()
     }
 }
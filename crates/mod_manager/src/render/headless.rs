@@ -0,0 +1,303 @@
+use super::{BlendMode, Rect, Renderer};
+use anyhow::{Error, Result};
+use std::collections::HashMap;
+
+/// RGBA8 pixel buffer.
+struct Surface {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Surface {
+    fn new(width: u32, height: u32) -> Result<Self> {
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|len| len.checked_mul(4))
+            .ok_or_else(|| Error::msg(format!("Surface of {}x{} is too big", width, height)))?;
+        Ok(Self {
+            width,
+            height,
+            pixels: vec![0; len],
+        })
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> Option<(u8, u8, u8, u8)> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let i = self.index(x, y);
+        Some((
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ))
+    }
+
+    /// Blends one pixel using the same equations as SDL's blend modes.
+    fn blend_pixel(&mut self, x: u32, y: u32, color: (u8, u8, u8, u8), blend_mode: BlendMode) {
+        let i = self.index(x, y);
+        let alpha = color.3 as u32;
+        let inv = 255 - alpha;
+        let src = [color.0, color.1, color.2];
+        for (c, s) in src.iter().enumerate() {
+            let s = *s as u32;
+            let d = self.pixels[i + c] as u32;
+            self.pixels[i + c] = match blend_mode {
+                BlendMode::Alpha => (s * alpha + d * inv) / 255,
+                BlendMode::Additive => (d + s * alpha / 255).min(255),
+                BlendMode::Multiply => ((s * d + d * inv) / 255).min(255),
+                BlendMode::None => s,
+            } as u8;
+        }
+        let d = self.pixels[i + 3] as u32;
+        self.pixels[i + 3] = match blend_mode {
+            BlendMode::Alpha => alpha + d * inv / 255,
+            BlendMode::Additive | BlendMode::Multiply => d,
+            BlendMode::None => alpha,
+        } as u8;
+    }
+
+    /// Calls `f` with every pixel whose center lies inside `rect` rotated by
+    /// `rotation` around its center, along with the pixel's position in the
+    /// unrotated rect normalized to `0.0..=1.0`.
+    fn for_each_covered(
+        &mut self,
+        rect: Rect,
+        rotation: f32,
+        mut f: impl FnMut(&mut Self, u32, u32, (f32, f32)),
+    ) {
+        let bounds = rect.bounds(rotation);
+        let (x0, x1) = pixel_span(bounds.x, bounds.w, self.width);
+        let (y0, y1) = pixel_span(bounds.y, bounds.h, self.height);
+        let (cx, cy) = rect.center();
+        let (sin, cos) = (-rotation).sin_cos();
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                let lx = dx * cos - dy * sin;
+                let ly = dx * sin + dy * cos;
+                if lx.abs() * 2.0 <= rect.w && ly.abs() * 2.0 <= rect.h {
+                    let uv = (lx / rect.w + 0.5, ly / rect.h + 0.5);
+                    f(self, x, y, uv);
+                }
+            }
+        }
+    }
+}
+
+/// Software renderer writing into an RGBA8 buffer. Used where no window is
+/// available, e.g. in tests and tooling.
+pub struct HeadlessRenderer {
+    screen: Surface,
+    targets: HashMap<u32, Surface>,
+    current_target: Option<u32>,
+    blend_mode: BlendMode,
+}
+
+impl HeadlessRenderer {
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        Ok(Self {
+            screen: Surface::new(width, height)?,
+            targets: HashMap::new(),
+            current_target: None,
+            blend_mode: BlendMode::default(),
+        })
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<()> {
+        self.screen = Surface::new(width, height)?;
+        Ok(())
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.screen.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Option<(u8, u8, u8, u8)> {
+        self.screen.pixel(x, y)
+    }
+
+    /// Pixel of a render target, for inspecting offscreen output.
+    pub fn target_pixel(&self, handle: u32, x: u32, y: u32) -> Option<(u8, u8, u8, u8)> {
+        self.targets.get(&handle)?.pixel(x, y)
+    }
+
+    fn surface(&mut self) -> Result<&mut Surface> {
+        match self.current_target {
+            Some(handle) => self
+                .targets
+                .get_mut(&handle)
+                .ok_or_else(|| Error::msg(format!("Unknown render target {}", handle))),
+            None => Ok(&mut self.screen),
+        }
+    }
+}
+
+/// Converts a span in pixel space to the range of pixels whose centers it
+/// covers.
+fn pixel_span(start: f32, len: f32, max: u32) -> (u32, u32) {
+    let from = (start - 0.5).ceil().max(0.0) as u32;
+    let to = ((start + len - 0.5).ceil().max(0.0) as u32).min(max);
+    (from, to)
+}
+
+impl Renderer for HeadlessRenderer {
+    fn viewport(&self) -> (u32, u32) {
+        match self
+            .current_target
+            .and_then(|handle| self.targets.get(&handle))
+        {
+            Some(target) => (target.width, target.height),
+            None => (self.screen.width, self.screen.height),
+        }
+    }
+
+    fn set_blend_mode(&mut self, blend_mode: BlendMode) -> Result<()> {
+        self.blend_mode = blend_mode;
+        Ok(())
+    }
+
+    fn clear(&mut self, color: (u8, u8, u8, u8)) -> Result<()> {
+        for pixel in self.surface()?.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[color.0, color.1, color.2, color.3]);
+        }
+        Ok(())
+    }
+
+    fn fill_rect(&mut self, rect: Rect, color: (u8, u8, u8, u8)) -> Result<()> {
+        let blend_mode = self.blend_mode;
+        let surface = self.surface()?;
+        let (x0, x1) = pixel_span(rect.x, rect.w, surface.width);
+        let (y0, y1) = pixel_span(rect.y, rect.h, surface.height);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                surface.blend_pixel(x, y, color, blend_mode);
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_rotated_rect(
+        &mut self,
+        rect: Rect,
+        rotation: f32,
+        color: (u8, u8, u8, u8),
+    ) -> Result<()> {
+        let blend_mode = self.blend_mode;
+        self.surface()?
+            .for_each_covered(rect, rotation, |surface, x, y, _| {
+                surface.blend_pixel(x, y, color, blend_mode);
+            });
+        Ok(())
+    }
+
+    fn create_target(&mut self, handle: u32, width: u32, height: u32) -> Result<()> {
+        self.targets.insert(handle, Surface::new(width, height)?);
+        Ok(())
+    }
+
+    fn destroy_target(&mut self, handle: u32) -> Result<()> {
+        if self.current_target == Some(handle) {
+            self.current_target = None;
+        }
+        self.targets.remove(&handle);
+        Ok(())
+    }
+
+    fn set_target(&mut self, target: Option<u32>) -> Result<()> {
+        if let Some(handle) = target {
+            if !self.targets.contains_key(&handle) {
+                return Err(Error::msg(format!("Unknown render target {}", handle)));
+            }
+        }
+        self.current_target = target;
+        Ok(())
+    }
+
+    fn draw_target(&mut self, handle: u32, rect: Rect, rotation: f32) -> Result<()> {
+        let blend_mode = self.blend_mode;
+        // Taken out of the map so it can be read while another surface is
+        // written.
+        let source = self
+            .targets
+            .remove(&handle)
+            .ok_or_else(|| Error::msg(format!("Unknown render target {}", handle)))?;
+        let result = self.surface().map(|surface| {
+            surface.for_each_covered(rect, rotation, |surface, x, y, (u, v)| {
+                let sx = ((u * source.width as f32) as u32).min(source.width.saturating_sub(1));
+                let sy = ((v * source.height as f32) as u32).min(source.height.saturating_sub(1));
+                if let Some(color) = source.pixel(sx, sy) {
+                    surface.blend_pixel(x, y, color, blend_mode);
+                }
+            });
+        });
+        self.targets.insert(handle, source);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: (u8, u8, u8, u8) = (255, 0, 0, 255);
+    const CLEAR: (u8, u8, u8, u8) = (0, 0, 0, 0);
+
+    fn is_empty((from, to): (u32, u32)) -> bool {
+        from >= to
+    }
+
+    #[test]
+    fn pixel_span_covers_pixel_centers() {
+        assert_eq!(pixel_span(0.0, 4.0, 10), (0, 4));
+        assert_eq!(pixel_span(0.4, 1.0, 10), (0, 1));
+        assert_eq!(pixel_span(0.6, 1.0, 10), (1, 2));
+        assert_eq!(pixel_span(2.5, 1.0, 10), (2, 3));
+        // Narrower than a pixel and between two centers.
+        assert!(is_empty(pixel_span(0.6, 0.3, 10)));
+    }
+
+    #[test]
+    fn pixel_span_stops_at_the_edges() {
+        assert_eq!(pixel_span(-2.0, 4.0, 10), (0, 2));
+        assert_eq!(pixel_span(8.0, 5.0, 10), (8, 10));
+        assert_eq!(pixel_span(-5.0, 20.0, 10), (0, 10));
+        assert!(is_empty(pixel_span(-5.0, 2.0, 10)));
+        assert!(is_empty(pixel_span(12.0, 2.0, 10)));
+    }
+
+    #[test]
+    fn fill_rect_partly_off_screen() {
+        let mut renderer = HeadlessRenderer::new(4, 4).unwrap();
+        renderer
+            .fill_rect(Rect::new(-2.0, 2.6, 4.0, 10.0), RED)
+            .unwrap();
+
+        assert_eq!(renderer.pixel(0, 3), Some(RED));
+        assert_eq!(renderer.pixel(1, 3), Some(RED));
+        assert_eq!(renderer.pixel(2, 3), Some(CLEAR));
+        assert_eq!(renderer.pixel(0, 2), Some(CLEAR));
+    }
+
+    #[test]
+    fn fill_rect_fully_off_screen() {
+        let mut renderer = HeadlessRenderer::new(4, 4).unwrap();
+        renderer
+            .fill_rect(Rect::new(-8.0, 0.0, 4.0, 4.0), RED)
+            .unwrap();
+        renderer
+            .fill_rect(Rect::new(5.0, 5.0, 4.0, 4.0), RED)
+            .unwrap();
+
+        assert!(renderer
+            .pixels()
+            .chunks_exact(4)
+            .all(|pixel| pixel == [0, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,525 @@
+mod headless;
+mod targets;
+mod transform;
+pub use headless::HeadlessRenderer;
+pub use targets::{RenderTarget, RenderTargets, TargetEvent, MAX_TARGET_SIZE};
+pub use transform::{Camera, Transform};
+
+use crate::storage::VecStorage;
+use anyhow::Result;
+use tracing::warn;
+
+/// Axis-aligned rectangle in window pixels. Coordinates may be negative or
+/// fractional, the renderer decides how to rasterize them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Self { x, y, w, h }
+    }
+
+    /// Intersects the rectangle with a viewport starting at the origin.
+    /// Returns `None` if nothing of the rectangle is visible.
+    pub fn clip(&self, viewport: (u32, u32)) -> Option<Rect> {
+        if !(self.x.is_finite() && self.y.is_finite() && self.w.is_finite() && self.h.is_finite()) {
+            return None;
+        }
+
+        let x0 = self.x.max(0.0);
+        let y0 = self.y.max(0.0);
+        let x1 = (self.x + self.w).min(viewport.0 as f32);
+        let y1 = (self.y + self.h).min(viewport.1 as f32);
+        if x1 <= x0 || y1 <= y0 {
+            return None;
+        }
+
+        Some(Rect::new(x0, y0, x1 - x0, y1 - y0))
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.w / 2.0, self.y + self.h / 2.0)
+    }
+
+    /// Maps the rectangle through `transform`. The result is the transformed
+    /// rectangle and its rotation around its center, in radians. Shear is not
+    /// representable and gets dropped.
+    pub fn transformed(&self, transform: &Transform) -> (Rect, f32) {
+        let center = transform.apply(self.center());
+        let (sx, sy) = transform.scale_factors();
+        let (w, h) = (self.w * sx, self.h * sy);
+        (
+            Rect::new(center.0 - w / 2.0, center.1 - h / 2.0, w, h),
+            transform.rotation_angle(),
+        )
+    }
+
+    /// Axis-aligned bounding box of the rectangle rotated around its center.
+    pub fn bounds(&self, rotation: f32) -> Rect {
+        let (sin, cos) = rotation.sin_cos();
+        let w = (self.w * cos).abs() + (self.h * sin).abs();
+        let h = (self.w * sin).abs() + (self.h * cos).abs();
+        let (cx, cy) = self.center();
+        Rect::new(cx - w / 2.0, cy - h / 2.0, w, h)
+    }
+}
+
+/// Draw layers, rendered back to front in declaration order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Layer {
+    Background,
+    #[default]
+    World,
+    Ui,
+    Overlay,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 4] = [Layer::Background, Layer::World, Layer::Ui, Layer::Overlay];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Background => "background",
+            Layer::World => "world",
+            Layer::Ui => "ui",
+            Layer::Overlay => "overlay",
+        }
+    }
+
+    /// Whether geometry on this layer is positioned through the camera.
+    /// UI and overlay layers stay in screen space.
+    pub fn uses_camera(&self) -> bool {
+        matches!(self, Layer::Background | Layer::World)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlendMode {
+    #[default]
+    Alpha,
+    Additive,
+    Multiply,
+    None,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 4] = [
+        BlendMode::Alpha,
+        BlendMode::Additive,
+        BlendMode::Multiply,
+        BlendMode::None,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlendMode::Alpha => "alpha",
+            BlendMode::Additive => "additive",
+            BlendMode::Multiply => "multiply",
+            BlendMode::None => "none",
+        }
+    }
+}
+
+/// Draw state a mod sets up through the graphics interface. Each mod has its
+/// own, reset before the mod's `draw`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderState {
+    pub color: (u8, u8, u8, u8),
+    pub blend_mode: BlendMode,
+    pub line_width: f32,
+    pub layer: Layer,
+    pub z: u32,
+    pub transform: Transform,
+    pub transform_stack: Vec<Transform>,
+    /// Render target between `begin-target` and `end-target`.
+    pub target: Option<u32>,
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+        Self {
+            color: (0, 0, 0, 0),
+            blend_mode: BlendMode::default(),
+            line_width: 1.0,
+            layer: Layer::default(),
+            z: 0,
+            transform: Transform::IDENTITY,
+            transform_stack: Vec::new(),
+            target: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+    Rect {
+        rect: Rect,
+        rotation: f32,
+        color: (u8, u8, u8, u8),
+        blend_mode: BlendMode,
+    },
+    Target {
+        handle: u32,
+        rect: Rect,
+        rotation: f32,
+        blend_mode: BlendMode,
+    },
+}
+
+/// A draw command together with where it sorts in the frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawItem {
+    pub layer: Layer,
+    pub z: u32,
+    pub mod_id: String,
+    /// Render target the item is drawn into, `None` for the screen.
+    pub target: Option<u32>,
+    pub command: DrawCommand,
+}
+
+/// Backend that turns draw commands into pixels. Geometry handed to a
+/// renderer is already clipped against its viewport.
+pub trait Renderer {
+    /// Size of the current target, or of the screen if no target is set.
+    fn viewport(&self) -> (u32, u32);
+    fn set_blend_mode(&mut self, blend_mode: BlendMode) -> Result<()>;
+    fn clear(&mut self, color: (u8, u8, u8, u8)) -> Result<()>;
+    fn fill_rect(&mut self, rect: Rect, color: (u8, u8, u8, u8)) -> Result<()>;
+    /// Fills `rect` rotated by `rotation` radians around its center. The
+    /// rectangle is only culled, not clipped.
+    fn fill_rotated_rect(
+        &mut self,
+        rect: Rect,
+        rotation: f32,
+        color: (u8, u8, u8, u8),
+    ) -> Result<()>;
+    fn create_target(&mut self, handle: u32, width: u32, height: u32) -> Result<()>;
+    fn destroy_target(&mut self, handle: u32) -> Result<()>;
+    /// Redirects drawing into a render target, or back to the screen.
+    fn set_target(&mut self, target: Option<u32>) -> Result<()>;
+    /// Draws the contents of a render target stretched over `rect`.
+    fn draw_target(&mut self, handle: u32, rect: Rect, rotation: f32) -> Result<()>;
+}
+
+/// Renders a frame. Pending target changes are applied first, then every
+/// target begun this frame is cleared and redrawn, then the screen. Targets
+/// are redrawn after the begun targets they draw, see `target_order`. A
+/// target the renderer fails to create is logged and destroyed, drawing into
+/// it or with it does nothing. Items are sorted by layer, then z; items that
+/// compare equal keep their submission order.
+pub fn render(
+    renderer: &mut dyn Renderer,
+    draw_list: &VecStorage<DrawItem>,
+    targets: &mut RenderTargets,
+) -> Result<()> {
+    for event in targets.take_events() {
+        match event {
+            TargetEvent::Created {
+                handle,
+                width,
+                height,
+            } => {
+                if let Err(e) = renderer.create_target(handle, width, height) {
+                    warn!(
+                        "Failed to create render target {} of {}x{}: {}",
+                        handle, width, height, e
+                    );
+                    targets.destroy(handle);
+                }
+            }
+            TargetEvent::Destroyed { handle } => renderer.destroy_target(handle)?,
+        }
+    }
+
+    let mut items: Vec<&DrawItem> = draw_list.iter().collect();
+    items.sort_by_key(|item| (item.layer, item.z));
+
+    for handle in target_order(targets.begun(), &items) {
+        renderer.set_target(Some(handle))?;
+        renderer.set_blend_mode(BlendMode::None)?;
+        renderer.clear((0, 0, 0, 0))?;
+        render_items(
+            renderer,
+            targets,
+            items.iter().filter(|item| item.target == Some(handle)),
+        )?;
+    }
+
+    renderer.set_target(None)?;
+    render_items(
+        renderer,
+        targets,
+        items.iter().filter(|item| item.target.is_none()),
+    )
+}
+
+/// Orders the begun targets so each one comes after the begun targets drawn
+/// into it, otherwise in begin order. When only targets that draw each other
+/// in a cycle are left, the first begun goes next, so it shows the other's
+/// previous frame.
+fn target_order(begun: &[u32], items: &[&DrawItem]) -> Vec<u32> {
+    let dependencies = |handle: u32| {
+        items.iter().filter_map(move |item| match item.command {
+            DrawCommand::Target { handle: drawn, .. }
+                if item.target == Some(handle) && drawn != handle && begun.contains(&drawn) =>
+            {
+                Some(drawn)
+            }
+            _ => None,
+        })
+    };
+
+    let mut order = Vec::with_capacity(begun.len());
+    let mut remaining = begun.to_vec();
+    while !remaining.is_empty() {
+        let next = remaining
+            .iter()
+            .position(|handle| dependencies(*handle).all(|drawn| order.contains(&drawn)))
+            .unwrap_or(0);
+        order.push(remaining.remove(next));
+    }
+    order
+}
+
+fn render_items<'a>(
+    renderer: &mut dyn Renderer,
+    targets: &RenderTargets,
+    items: impl Iterator<Item = &'a &'a DrawItem>,
+) -> Result<()> {
+    let viewport = renderer.viewport();
+    let mut current_blend_mode = None;
+    for item in items {
+        let blend_mode = match &item.command {
+            DrawCommand::Rect { blend_mode, .. } | DrawCommand::Target { blend_mode, .. } => {
+                *blend_mode
+            }
+        };
+        if current_blend_mode != Some(blend_mode) {
+            renderer.set_blend_mode(blend_mode)?;
+            current_blend_mode = Some(blend_mode);
+        }
+
+        match &item.command {
+            DrawCommand::Rect {
+                rect,
+                rotation,
+                color,
+                ..
+            } => {
+                if *rotation == 0.0 {
+                    if let Some(rect) = rect.clip(viewport) {
+                        renderer.fill_rect(rect, *color)?;
+                    }
+                } else if rect.bounds(*rotation).clip(viewport).is_some() {
+                    renderer.fill_rotated_rect(*rect, *rotation, *color)?;
+                }
+            }
+            DrawCommand::Target {
+                handle,
+                rect,
+                rotation,
+                ..
+            } => {
+                // A target can't be sampled while it is being drawn into,
+                // and may have been released since the item was submitted.
+                if item.target != Some(*handle)
+                    && targets.get(*handle).is_some()
+                    && rect.bounds(*rotation).clip(viewport).is_some()
+                {
+                    renderer.draw_target(*handle, *rect, *rotation)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: (u8, u8, u8, u8) = (255, 0, 0, 255);
+
+    fn item(target: Option<u32>, command: DrawCommand) -> DrawItem {
+        DrawItem {
+            layer: Layer::World,
+            z: 0,
+            mod_id: "mod".to_string(),
+            target,
+            command,
+        }
+    }
+
+    fn fill(target: Option<u32>, rect: Rect, color: (u8, u8, u8, u8)) -> DrawItem {
+        item(
+            target,
+            DrawCommand::Rect {
+                rect,
+                rotation: 0.0,
+                color,
+                blend_mode: BlendMode::None,
+            },
+        )
+    }
+
+    fn draw_target(target: Option<u32>, handle: u32, rect: Rect) -> DrawItem {
+        item(
+            target,
+            DrawCommand::Target {
+                handle,
+                rect,
+                rotation: 0.0,
+                blend_mode: BlendMode::None,
+            },
+        )
+    }
+
+    fn render_frame(
+        renderer: &mut HeadlessRenderer,
+        targets: &mut RenderTargets,
+        items: Vec<DrawItem>,
+    ) {
+        let mut draw_list = VecStorage::new();
+        for item in items {
+            draw_list.add(item);
+        }
+        render(renderer, &draw_list, targets).unwrap();
+        targets.end_frame();
+    }
+
+    #[test]
+    fn clip_keeps_the_visible_part() {
+        let viewport = (100, 50);
+        let inside = Rect::new(10.0, 10.0, 20.0, 20.0);
+        assert_eq!(inside.clip(viewport), Some(inside));
+        assert_eq!(
+            Rect::new(-10.0, 40.0, 30.0, 20.0).clip(viewport),
+            Some(Rect::new(0.0, 40.0, 20.0, 10.0))
+        );
+        assert_eq!(
+            Rect::new(-10.0, -10.0, 200.0, 200.0).clip(viewport),
+            Some(Rect::new(0.0, 0.0, 100.0, 50.0))
+        );
+        assert_eq!(
+            Rect::new(99.5, 0.25, 10.0, 0.5).clip(viewport),
+            Some(Rect::new(99.5, 0.25, 0.5, 0.5))
+        );
+    }
+
+    #[test]
+    fn clip_drops_what_is_off_screen_or_empty() {
+        let viewport = (100, 50);
+        assert_eq!(Rect::new(-20.0, 10.0, 20.0, 10.0).clip(viewport), None);
+        assert_eq!(Rect::new(100.0, 10.0, 5.0, 5.0).clip(viewport), None);
+        assert_eq!(Rect::new(10.0, 60.0, 5.0, 5.0).clip(viewport), None);
+        assert_eq!(Rect::new(10.0, 10.0, 0.0, 5.0).clip(viewport), None);
+        assert_eq!(Rect::new(10.0, 10.0, -5.0, 5.0).clip(viewport), None);
+        assert_eq!(Rect::new(f32::NAN, 10.0, 5.0, 5.0).clip(viewport), None);
+        assert_eq!(Rect::new(0.0, 0.0, f32::INFINITY, 5.0).clip(viewport), None);
+    }
+
+    #[test]
+    fn target_contents_are_drawn_to_the_screen() {
+        let mut renderer = HeadlessRenderer::new(4, 4).unwrap();
+        let mut targets = RenderTargets::new();
+        let handle = targets.create("mod", 2, 2).unwrap();
+        targets.begin(handle);
+
+        render_frame(
+            &mut renderer,
+            &mut targets,
+            vec![
+                fill(Some(handle), Rect::new(0.0, 0.0, 2.0, 2.0), RED),
+                draw_target(None, handle, Rect::new(2.0, 2.0, 2.0, 2.0)),
+            ],
+        );
+
+        assert_eq!(renderer.target_pixel(handle, 1, 1), Some(RED));
+        assert_eq!(renderer.pixel(3, 3), Some(RED));
+        assert_eq!(renderer.pixel(1, 1), Some((0, 0, 0, 0)));
+    }
+
+    #[test]
+    fn targets_keep_their_contents_until_begun_again() {
+        let mut renderer = HeadlessRenderer::new(4, 4).unwrap();
+        let mut targets = RenderTargets::new();
+        let handle = targets.create("mod", 2, 2).unwrap();
+        targets.begin(handle);
+        render_frame(
+            &mut renderer,
+            &mut targets,
+            vec![fill(Some(handle), Rect::new(0.0, 0.0, 2.0, 2.0), RED)],
+        );
+
+        render_frame(&mut renderer, &mut targets, Vec::new());
+        assert_eq!(renderer.target_pixel(handle, 0, 0), Some(RED));
+
+        targets.begin(handle);
+        render_frame(&mut renderer, &mut targets, Vec::new());
+        assert_eq!(renderer.target_pixel(handle, 0, 0), Some((0, 0, 0, 0)));
+    }
+
+    #[test]
+    fn targets_are_drawn_after_the_targets_they_use() {
+        let mut renderer = HeadlessRenderer::new(4, 4).unwrap();
+        let mut targets = RenderTargets::new();
+        let outer = targets.create("mod", 2, 2).unwrap();
+        let inner = targets.create("mod", 2, 2).unwrap();
+        // Begun in the wrong order on purpose.
+        targets.begin(outer);
+        targets.begin(inner);
+
+        render_frame(
+            &mut renderer,
+            &mut targets,
+            vec![
+                draw_target(Some(outer), inner, Rect::new(0.0, 0.0, 2.0, 2.0)),
+                fill(Some(inner), Rect::new(0.0, 0.0, 2.0, 2.0), RED),
+            ],
+        );
+
+        assert_eq!(renderer.target_pixel(outer, 0, 0), Some(RED));
+    }
+
+    #[test]
+    fn target_order_keeps_begin_order_otherwise() {
+        let a = draw_target(Some(1), 2, Rect::new(0.0, 0.0, 1.0, 1.0));
+        let b = draw_target(Some(2), 1, Rect::new(0.0, 0.0, 1.0, 1.0));
+        let c = draw_target(Some(3), 4, Rect::new(0.0, 0.0, 1.0, 1.0));
+
+        assert_eq!(target_order(&[1, 2, 3], &[]), [1, 2, 3]);
+        // 4 isn't begun, so it doesn't hold 3 back.
+        assert_eq!(target_order(&[3, 1], &[&c]), [3, 1]);
+        // 3 is free to go first, the cycle between 1 and 2 keeps begin order.
+        assert_eq!(target_order(&[2, 1, 3], &[&a, &b]), [3, 2, 1]);
+    }
+
+    #[test]
+    fn destroyed_targets_are_not_drawn() {
+        let mut renderer = HeadlessRenderer::new(4, 4).unwrap();
+        let mut targets = RenderTargets::new();
+        let handle = targets.create("mod", 2, 2).unwrap();
+        targets.begin(handle);
+        render_frame(
+            &mut renderer,
+            &mut targets,
+            vec![fill(Some(handle), Rect::new(0.0, 0.0, 2.0, 2.0), RED)],
+        );
+
+        targets.release_mod("mod");
+        render_frame(
+            &mut renderer,
+            &mut targets,
+            vec![draw_target(None, handle, Rect::new(0.0, 0.0, 2.0, 2.0))],
+        );
+
+        assert_eq!(renderer.target_pixel(handle, 0, 0), None);
+        assert_eq!(renderer.pixel(0, 0), Some((0, 0, 0, 0)));
+    }
+}
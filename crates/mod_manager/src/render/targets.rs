@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+/// Largest width or height of a render target, in pixels.
+pub const MAX_TARGET_SIZE: u32 = 4096;
+
+#[derive(Debug, Clone)]
+pub struct RenderTarget {
+    pub owner: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Changes to the set of render targets the renderer has not applied yet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TargetEvent {
+    Created {
+        handle: u32,
+        width: u32,
+        height: u32,
+    },
+    Destroyed {
+        handle: u32,
+    },
+}
+
+/// Host-side table of offscreen render targets. Handles are owned by the mod
+/// that created them and released when it unloads.
+#[derive(Debug, Default)]
+pub struct RenderTargets {
+    next_handle: u32,
+    targets: HashMap<u32, RenderTarget>,
+    events: Vec<TargetEvent>,
+    begun: Vec<u32>,
+}
+
+impl RenderTargets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a target of `width` by `height` pixels. Returns `None` if
+    /// either is 0 or above `MAX_TARGET_SIZE`.
+    pub fn create(&mut self, owner: &str, width: u32, height: u32) -> Option<u32> {
+        if !(1..=MAX_TARGET_SIZE).contains(&width) || !(1..=MAX_TARGET_SIZE).contains(&height) {
+            return None;
+        }
+        self.next_handle += 1;
+        let handle = self.next_handle;
+        self.targets.insert(
+            handle,
+            RenderTarget {
+                owner: owner.to_string(),
+                width,
+                height,
+            },
+        );
+        self.events.push(TargetEvent::Created {
+            handle,
+            width,
+            height,
+        });
+        Some(handle)
+    }
+
+    pub fn destroy(&mut self, handle: u32) {
+        if self.targets.remove(&handle).is_some() {
+            self.begun.retain(|begun| *begun != handle);
+            self.events.push(TargetEvent::Destroyed { handle });
+        }
+    }
+
+    pub fn get(&self, handle: u32) -> Option<&RenderTarget> {
+        self.targets.get(&handle)
+    }
+
+    pub fn is_owned_by(&self, handle: u32, owner: &str) -> bool {
+        self.get(handle).is_some_and(|target| target.owner == owner)
+    }
+
+    /// Destroys every target owned by `owner`.
+    pub fn release_mod(&mut self, owner: &str) {
+        let mut handles: Vec<u32> = self
+            .targets
+            .iter()
+            .filter(|(_, target)| target.owner == owner)
+            .map(|(handle, _)| *handle)
+            .collect();
+        handles.sort();
+        for handle in handles {
+            self.destroy(handle);
+        }
+    }
+
+    /// Marks a target as redrawn this frame, so it is cleared before its
+    /// draw items are rendered. Targets that are not begun keep their
+    /// contents from earlier frames.
+    pub fn begin(&mut self, handle: u32) {
+        if !self.begun.contains(&handle) {
+            self.begun.push(handle);
+        }
+    }
+
+    pub fn begun(&self) -> &[u32] {
+        &self.begun
+    }
+
+    pub fn take_events(&mut self) -> Vec<TargetEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn end_frame(&mut self) {
+        self.begun.clear();
+    }
+}
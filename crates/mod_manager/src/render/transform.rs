@@ -0,0 +1,265 @@
+use std::ops::Mul;
+
+/// 2D affine transform. Maps `(x, y)` to
+/// `(a * x + c * y + tx, b * x + d * y + ty)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub tx: f32,
+    pub ty: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        tx: 0.0,
+        ty: 0.0,
+    };
+
+    pub fn translation(x: f32, y: f32) -> Self {
+        Self {
+            tx: x,
+            ty: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn rotation(radians: f32) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        Self {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn scaling(x: f32, y: f32) -> Self {
+        Self {
+            a: x,
+            d: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn apply(&self, point: (f32, f32)) -> (f32, f32) {
+        (
+            self.a * point.0 + self.c * point.1 + self.tx,
+            self.b * point.0 + self.d * point.1 + self.ty,
+        )
+    }
+
+    pub fn inverse(&self) -> Option<Transform> {
+        let det = self.a * self.d - self.b * self.c;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+
+        let a = self.d / det;
+        let b = -self.b / det;
+        let c = -self.c / det;
+        let d = self.a / det;
+        Some(Transform {
+            a,
+            b,
+            c,
+            d,
+            tx: -(a * self.tx + c * self.ty),
+            ty: -(b * self.tx + d * self.ty),
+        })
+    }
+
+    /// Angle of the transformed x axis, in radians.
+    pub fn rotation_angle(&self) -> f32 {
+        self.b.atan2(self.a)
+    }
+
+    /// Lengths of the transformed x and y axes.
+    pub fn scale_factors(&self) -> (f32, f32) {
+        (self.a.hypot(self.b), self.c.hypot(self.d))
+    }
+}
+
+/// `lhs * rhs` applies `rhs` first, then `lhs`.
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform {
+            a: self.a * rhs.a + self.c * rhs.b,
+            b: self.b * rhs.a + self.d * rhs.b,
+            c: self.a * rhs.c + self.c * rhs.d,
+            d: self.b * rhs.c + self.d * rhs.d,
+            tx: self.a * rhs.tx + self.c * rhs.ty + self.tx,
+            ty: self.b * rhs.tx + self.d * rhs.ty + self.ty,
+        }
+    }
+}
+
+/// Host-managed view onto the world. `x` and `y` scroll the world, zoom and
+/// rotation pivot around the center of the viewport. The default camera maps
+/// world coordinates 1:1 to window pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub x: f32,
+    pub y: f32,
+    pub zoom: f32,
+    pub rotation: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            zoom: 1.0,
+            rotation: 0.0,
+        }
+    }
+}
+
+impl Camera {
+    /// World to screen transform for a viewport of the given size.
+    pub fn view(&self, viewport: (u32, u32)) -> Transform {
+        let center = (viewport.0 as f32 / 2.0, viewport.1 as f32 / 2.0);
+        Transform::translation(center.0, center.1)
+            * Transform::rotation(-self.rotation)
+            * Transform::scaling(self.zoom, self.zoom)
+            * Transform::translation(-center.0 - self.x, -center.1 - self.y)
+    }
+
+    pub fn world_to_screen(&self, viewport: (u32, u32), point: (f32, f32)) -> (f32, f32) {
+        self.view(viewport).apply(point)
+    }
+
+    pub fn screen_to_world(&self, viewport: (u32, u32), point: (f32, f32)) -> (f32, f32) {
+        match self.view(viewport).inverse() {
+            Some(inverse) => inverse.apply(point),
+            None => point,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Rect;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_near(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3,
+            "{:?} isn't near {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Transform::translation(10.0, -5.0)
+            * Transform::rotation(0.7)
+            * Transform::scaling(2.0, 0.5);
+        let inverse = transform.inverse().unwrap();
+        for point in [(0.0, 0.0), (3.0, 4.0), (-12.5, 7.25)] {
+            assert_near(inverse.apply(transform.apply(point)), point);
+        }
+        assert_eq!(Transform::scaling(0.0, 1.0).inverse(), None);
+    }
+
+    #[test]
+    fn default_camera_is_the_identity() {
+        let view = Camera::default().view((800, 600));
+        assert_near(view.apply((12.0, 34.0)), (12.0, 34.0));
+    }
+
+    #[test]
+    fn camera_scrolls_zooms_and_rotates_around_the_center() {
+        let viewport = (800, 600);
+        let scrolled = Camera {
+            x: 100.0,
+            y: 50.0,
+            ..Camera::default()
+        };
+        assert_near(
+            scrolled.world_to_screen(viewport, (100.0, 50.0)),
+            (0.0, 0.0),
+        );
+
+        let zoomed = Camera {
+            zoom: 2.0,
+            ..Camera::default()
+        };
+        assert_near(
+            zoomed.world_to_screen(viewport, (400.0, 300.0)),
+            (400.0, 300.0),
+        );
+        assert_near(
+            zoomed.world_to_screen(viewport, (410.0, 300.0)),
+            (420.0, 300.0),
+        );
+
+        let rotated = Camera {
+            rotation: FRAC_PI_2,
+            ..Camera::default()
+        };
+        assert_near(
+            rotated.world_to_screen(viewport, (410.0, 300.0)),
+            (400.0, 290.0),
+        );
+    }
+
+    #[test]
+    fn world_and_screen_round_trip() {
+        let viewport = (640, 480);
+        let camera = Camera {
+            x: -30.0,
+            y: 12.0,
+            zoom: 1.5,
+            rotation: 0.4,
+        };
+        for point in [(0.0, 0.0), (320.0, 240.0), (-50.0, 900.0)] {
+            let screen = camera.world_to_screen(viewport, point);
+            assert_near(camera.screen_to_world(viewport, screen), point);
+        }
+
+        // A camera zoomed to nothing can't be inverted and maps 1:1 back.
+        let flat = Camera {
+            zoom: 0.0,
+            ..Camera::default()
+        };
+        assert_eq!(flat.screen_to_world(viewport, (5.0, 6.0)), (5.0, 6.0));
+    }
+
+    #[test]
+    fn transformed_rects_keep_their_center_scale_and_rotation() {
+        let rect = Rect::new(10.0, 20.0, 4.0, 2.0);
+
+        let (moved, rotation) = rect.transformed(&Transform::translation(5.0, -5.0));
+        assert_eq!(moved, Rect::new(15.0, 15.0, 4.0, 2.0));
+        assert_eq!(rotation, 0.0);
+
+        let (scaled, _) = rect.transformed(&Transform::scaling(2.0, 3.0));
+        assert_eq!(scaled, Rect::new(20.0, 60.0, 8.0, 6.0));
+
+        // Rotation turns around the origin and is handed on for the
+        // renderer, the rectangle itself stays axis aligned.
+        let (rotated, rotation) = rect.transformed(&Transform::rotation(FRAC_PI_2));
+        assert_near(rotated.center(), (-21.0, 12.0));
+        assert_near((rotated.w, rotated.h), (4.0, 2.0));
+        assert_near((rotation, 0.0), (FRAC_PI_2, 0.0));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{input::InputEvent, random::stable_hash, render::DrawItem, storage::VecStorage};
+use anyhow::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Write},
+    path::Path,
+    time::Duration,
+};
+use tracing::warn;
+
+/// Bumped whenever recordings written before can't be replayed anymore.
+pub const RECORDING_VERSION: u32 = 2;
+
+/// What a run started from, including how the game loop was set up. Mods
+/// are by id and version, replaying with other mods or other versions isn't
+/// expected to match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub version: u32,
+    pub seed: u64,
+    pub step: Duration,
+    pub max_steps: u32,
+    pub time_scale: f64,
+    pub paused: bool,
+    pub mods: Vec<(String, String)>,
+}
+
+/// One frame of a run: the input that came in, the real time that passed
+/// and, if asked for, a hash of what the mods drew.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    pub real_delta: Duration,
+    pub window_size: (u32, u32),
+    pub inputs: Vec<InputEvent>,
+    pub draw_hash: Option<u64>,
+}
+
+/// Writes a run to a file as it happens: the header, then one MessagePack
+/// value per frame, flushed after every frame so a crash keeps everything
+/// up to it.
+#[derive(Debug)]
+pub struct Recorder {
+    writer: BufWriter<File>,
+    hash_draws: bool,
+    frame: RecordedFrame,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, header: &RecordingHeader, hash_draws: bool) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create recording {}", path.display()))?;
+        let mut recorder = Self {
+            writer: BufWriter::new(file),
+            hash_draws,
+            frame: RecordedFrame {
+                real_delta: Duration::ZERO,
+                window_size: (0, 0),
+                inputs: Vec::new(),
+                draw_hash: None,
+            },
+        };
+        recorder.write(header)?;
+        Ok(recorder)
+    }
+
+    pub fn record_input(&mut self, event: &InputEvent) {
+        self.frame.inputs.push(event.clone());
+    }
+
+    pub fn record_delta(&mut self, real_delta: Duration) {
+        self.frame.real_delta += real_delta;
+    }
+
+    /// Writes the frame recorded since the last call.
+    pub fn end_frame(
+        &mut self,
+        window_size: (u32, u32),
+        draw_list: &VecStorage<DrawItem>,
+    ) -> Result<()> {
+        self.frame.window_size = window_size;
+        self.frame.draw_hash = self.hash_draws.then(|| hash_draw_list(draw_list));
+        let frame = std::mem::replace(
+            &mut self.frame,
+            RecordedFrame {
+                real_delta: Duration::ZERO,
+                window_size,
+                inputs: Vec::new(),
+                draw_hash: None,
+            },
+        );
+        self.write(&frame)
+    }
+
+    fn write<T: Serialize>(&mut self, value: &T) -> Result<()> {
+        rmp_serde::encode::write(&mut self.writer, value).map_err(|e| Error::msg(e.to_string()))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A run read back from a file written by `Recorder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl Recording {
+    /// Reads a recording. A frame cut off at the end, as left by a crash,
+    /// is dropped.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open recording {}", path.display()))?;
+        let mut deserializer = rmp_serde::Deserializer::new(BufReader::new(file));
+
+        let header = RecordingHeader::deserialize(&mut deserializer)
+            .map_err(|e| Error::msg(format!("Corrupt recording header: {}", e)))?;
+        if header.version != RECORDING_VERSION {
+            return Err(Error::msg(format!(
+                "Recording has version {}, expected {}",
+                header.version, RECORDING_VERSION
+            )));
+        }
+
+        let mut frames = Vec::new();
+        loop {
+            match RecordedFrame::deserialize(&mut deserializer) {
+                Ok(frame) => frames.push(frame),
+                Err(rmp_serde::decode::Error::InvalidMarkerRead(e))
+                    if e.kind() == ErrorKind::UnexpectedEof =>
+                {
+                    break
+                }
+                Err(e) => {
+                    warn!("Dropping the rest of the recording: {}", e);
+                    break;
+                }
+            }
+        }
+
+        Ok(Self { header, frames })
+    }
+}
+
+/// Frame whose draw list hashed differently on replay than when recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub frame: usize,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplayReport {
+    pub frames: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+/// Hash of everything drawn in a frame, in draw order. Floats are hashed
+/// through their debug formatting, which is exact and stable.
+pub fn hash_draw_list(draw_list: &VecStorage<DrawItem>) -> u64 {
+    let mut text = String::new();
+    for item in draw_list.iter() {
+        text.push_str(&format!("{:?}\n", item));
+    }
+    stable_hash(text.as_bytes())
+}
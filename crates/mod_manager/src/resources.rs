@@ -0,0 +1,361 @@
+use crate::vfs::{check_inside, relative_path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{debug, info};
+
+/// How often `ResourceStack::poll_changes` looks at the files on disk.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where a resource came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layer {
+    /// The game's own `resources/` directory.
+    Base,
+    /// The `resources/` directory a mod ships.
+    Mod(String),
+    /// The user's `overrides/` directory.
+    User,
+}
+
+impl Layer {
+    pub fn name(&self) -> String {
+        match self {
+            Layer::Base => "base".to_string(),
+            Layer::Mod(mod_id) => format!("mod:{}", mod_id),
+            Layer::User => "user".to_string(),
+        }
+    }
+}
+
+/// A resource path provided by more than one mod.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub path: String,
+    /// Every layer that has the path, lowest first; the last one wins.
+    pub layers: Vec<Layer>,
+}
+
+/// Version of a resource that was handed out, to notice when it changes.
+#[derive(Debug, Clone, PartialEq)]
+struct Seen {
+    layer: Layer,
+    modified: Option<SystemTime>,
+}
+
+/// Shared resources, resolved through a stack of layers: the base game,
+/// then mods in load order, then the user's overrides. The topmost layer
+/// that has a path supplies it.
+#[derive(Debug)]
+pub struct ResourceStack {
+    base: PathBuf,
+    user: PathBuf,
+    /// `resources/` directory of every loaded mod.
+    mod_roots: HashMap<String, PathBuf>,
+    /// Mod ids in load order.
+    order: Vec<String>,
+    seen: BTreeMap<String, Seen>,
+    last_poll: Instant,
+}
+
+impl Default for ResourceStack {
+    fn default() -> Self {
+        Self::new(Path::new("resources"), Path::new("overrides"))
+    }
+}
+
+impl ResourceStack {
+    pub fn new(base: &Path, user: &Path) -> Self {
+        Self {
+            base: base.to_path_buf(),
+            user: user.to_path_buf(),
+            mod_roots: HashMap::new(),
+            order: Vec::new(),
+            seen: BTreeMap::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn add_mod(&mut self, mod_id: &str, root: &Path) {
+        self.mod_roots
+            .insert(mod_id.to_string(), root.to_path_buf());
+    }
+
+    pub fn set_order(&mut self, order: Vec<String>) {
+        self.order = order;
+    }
+
+    pub fn release_mod(&mut self, mod_id: &str) {
+        self.mod_roots.remove(mod_id);
+        self.order.retain(|id| id != mod_id);
+    }
+
+    /// Layers with their directories, lowest first.
+    fn layers(&self) -> Vec<(Layer, &Path)> {
+        let mut layers = vec![(Layer::Base, self.base.as_path())];
+        for mod_id in &self.order {
+            if let Some(root) = self.mod_roots.get(mod_id) {
+                layers.push((Layer::Mod(mod_id.clone()), root.as_path()));
+            }
+        }
+        layers.push((Layer::User, self.user.as_path()));
+        layers
+    }
+
+    /// The layer that supplies `path` and the file it resolves to.
+    pub fn resolve(&self, path: &str) -> Result<(Layer, PathBuf), String> {
+        let relative = relative_path(path)?;
+        for (layer, root) in self.layers().into_iter().rev() {
+            let file = root.join(&relative);
+            if file.is_file() {
+                check_inside(root, &file)?;
+                return Ok((layer, file));
+            }
+        }
+        Err(format!("Resource {} not found", path))
+    }
+
+    /// Name of the layer that supplies `path`.
+    pub fn provider(&self, path: &str) -> Option<String> {
+        self.resolve(path).ok().map(|(layer, _)| layer.name())
+    }
+
+    /// Reads a resource and remembers it for `poll_changes`.
+    pub fn read(&mut self, path: &str) -> Result<Vec<u8>, String> {
+        let (layer, file) = self.resolve(path)?;
+        let data = std::fs::read(&file).map_err(|e| e.to_string())?;
+        self.seen.insert(
+            path.to_string(),
+            Seen {
+                layer,
+                modified: modified(&file),
+            },
+        );
+        Ok(data)
+    }
+
+    /// Paths of resources read earlier that changed on disk or now come from
+    /// another layer. Only looks at the disk once per `POLL_INTERVAL`.
+    pub fn poll_changes(&mut self) -> Vec<String> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = Vec::new();
+        let paths: Vec<String> = self.seen.keys().cloned().collect();
+        for path in paths {
+            let current = self.resolve(&path).ok().map(|(layer, file)| Seen {
+                layer,
+                modified: modified(&file),
+            });
+            let Some(current) = current else {
+                // Removed everywhere, there is nothing to reload.
+                self.seen.remove(&path);
+                continue;
+            };
+            if self.seen.get(&path) != Some(&current) {
+                debug!(
+                    "Resource {} changed, now from {}",
+                    path,
+                    current.layer.name()
+                );
+                self.seen.insert(path.clone(), current);
+                changed.push(path);
+            }
+        }
+        changed
+    }
+
+    /// Paths that more than one mod provides, sorted.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut providers: BTreeMap<String, Vec<Layer>> = BTreeMap::new();
+        for (layer, root) in self.layers() {
+            let mut files = Vec::new();
+            list_files(root, root, &mut files);
+            for file in files {
+                providers.entry(file).or_default().push(layer.clone());
+            }
+        }
+
+        providers
+            .into_iter()
+            .filter(|(_, layers)| {
+                layers
+                    .iter()
+                    .filter(|layer| matches!(layer, Layer::Mod(_)))
+                    .count()
+                    > 1
+            })
+            .map(|(path, layers)| Conflict { path, layers })
+            .collect()
+    }
+
+    /// Logs the conflict report.
+    pub fn report_conflicts(&self) {
+        for conflict in self.conflicts() {
+            let layers: Vec<String> = conflict.layers.iter().map(Layer::name).collect();
+            info!(
+                "Resource {} is provided by {}",
+                conflict.path,
+                layers.join(", ")
+            );
+        }
+    }
+}
+
+fn modified(file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Collects the files below `dir` as `/`-separated paths relative to `root`.
+/// Symlinked directories aren't followed, so a link can't lead the walk
+/// out of `root` or around in a loop.
+fn list_files(root: &Path, dir: &Path, files: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            list_files(root, &path, files);
+            continue;
+        }
+        // Links count if they lead to a file `resolve` would hand out.
+        if !path.is_file() || check_inside(root, &path).is_err() {
+            continue;
+        }
+        if let Ok(relative) = path.strip_prefix(root) {
+            let components: Vec<_> = relative
+                .components()
+                .filter_map(|component| component.as_os_str().to_str())
+                .collect();
+            files.push(components.join("/"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("resources-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(root: &Path, path: &str, data: &str) {
+        let file = root.join(path);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(file, data).unwrap();
+    }
+
+    /// Stack with the base game, mods `a` and `b` in that order and the
+    /// user's overrides, each in its own directory below `dir`.
+    fn stack(dir: &Path) -> ResourceStack {
+        let mut stack = ResourceStack::new(&dir.join("base"), &dir.join("user"));
+        for mod_id in ["a", "b"] {
+            stack.add_mod(mod_id, &dir.join(mod_id));
+        }
+        stack.set_order(vec!["a".to_string(), "b".to_string()]);
+        stack
+    }
+
+    #[test]
+    fn topmost_layer_wins() {
+        let dir = temp_dir("layers");
+        let mut stack = stack(&dir);
+        write(&dir.join("base"), "text/greeting.txt", "base");
+        assert_eq!(stack.read("text/greeting.txt").unwrap(), b"base");
+        assert_eq!(stack.provider("text/greeting.txt").unwrap(), "base");
+
+        write(&dir.join("a"), "text/greeting.txt", "a");
+        write(&dir.join("b"), "text/greeting.txt", "b");
+        assert_eq!(stack.read("text/greeting.txt").unwrap(), b"b");
+        assert_eq!(stack.provider("text/greeting.txt").unwrap(), "mod:b");
+
+        write(&dir.join("user"), "text/greeting.txt", "user");
+        assert_eq!(stack.read("text/greeting.txt").unwrap(), b"user");
+        assert_eq!(stack.provider("text/greeting.txt").unwrap(), "user");
+
+        stack.release_mod("b");
+        std::fs::remove_file(dir.join("user").join("text/greeting.txt")).unwrap();
+        assert_eq!(stack.provider("text/greeting.txt").unwrap(), "mod:a");
+        assert!(stack.provider("missing.txt").is_none());
+        assert!(stack.read("../base/text/greeting.txt").is_err());
+    }
+
+    #[test]
+    fn conflicts_are_between_mods() {
+        let dir = temp_dir("conflicts");
+        let stack = stack(&dir);
+        write(&dir.join("base"), "shared.txt", "base");
+        write(&dir.join("a"), "shared.txt", "a");
+        write(&dir.join("user"), "shared.txt", "user");
+        write(&dir.join("a"), "deep/both.txt", "a");
+        write(&dir.join("b"), "deep/both.txt", "b");
+        write(&dir.join("b"), "only-b.txt", "b");
+
+        assert_eq!(
+            stack.conflicts(),
+            [Conflict {
+                path: "deep/both.txt".to_string(),
+                layers: vec![Layer::Mod("a".to_string()), Layer::Mod("b".to_string())],
+            }]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn conflicts_dont_follow_symlinked_directories() {
+        let dir = temp_dir("symlinks");
+        let stack = stack(&dir);
+        write(&dir.join("a"), "file.txt", "a");
+        write(&dir.join("b"), "file.txt", "b");
+        std::fs::create_dir_all(dir.join("a").join("nested")).unwrap();
+        std::os::unix::fs::symlink(dir.join("a"), dir.join("a").join("nested").join("loop"))
+            .unwrap();
+        std::os::unix::fs::symlink(dir.join("b"), dir.join("a").join("outside")).unwrap();
+
+        let paths: Vec<String> = stack
+            .conflicts()
+            .into_iter()
+            .map(|conflict| conflict.path)
+            .collect();
+        assert_eq!(paths, ["file.txt"]);
+    }
+
+    #[test]
+    fn poll_changes_reports_resources_read_before() {
+        let dir = temp_dir("poll");
+        let mut stack = stack(&dir);
+        write(&dir.join("base"), "read.txt", "base");
+        write(&dir.join("base"), "unread.txt", "base");
+        stack.read("read.txt").unwrap();
+        stack.last_poll = Instant::now() - POLL_INTERVAL;
+        assert!(stack.poll_changes().is_empty());
+
+        write(&dir.join("a"), "read.txt", "a");
+        write(&dir.join("a"), "unread.txt", "a");
+        // Polled too early, the disk isn't looked at.
+        assert!(stack.poll_changes().is_empty());
+        stack.last_poll = Instant::now() - POLL_INTERVAL;
+        assert_eq!(stack.poll_changes(), ["read.txt"]);
+        stack.last_poll = Instant::now() - POLL_INTERVAL;
+        assert!(stack.poll_changes().is_empty());
+
+        std::fs::remove_file(dir.join("a").join("read.txt")).unwrap();
+        std::fs::remove_file(dir.join("base").join("read.txt")).unwrap();
+        stack.last_poll = Instant::now() - POLL_INTERVAL;
+        assert!(stack.poll_changes().is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+use crate::{random::RandomState, timers::SavedTimer};
+use anyhow::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+use tracing::{debug, warn};
+use utils::logging::*;
+
+/// Default limit for what one mod may keep in its namespace, and for its
+/// section of a save slot.
+pub const DEFAULT_QUOTA: u64 = 4 * 1024 * 1024;
+
+/// Data a mod saved, with the mod version that saved it so the mod can
+/// migrate it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Saved {
+    pub version: String,
+    pub data: Vec<u8>,
+}
+
+/// A game-wide save: one section per mod, by mod id, the mods' pending
+/// timers and their random streams.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SaveSlot {
+    pub sections: BTreeMap<String, Saved>,
+    #[serde(default)]
+    pub timers: Vec<SavedTimer>,
+    #[serde(default)]
+    pub random: Option<RandomState>,
+}
+
+/// A save or load of a slot a mod asked for, handled by
+/// `ModManager::process_slot_requests` after the mods updated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlotRequest {
+    Save(String),
+    Load(String),
+}
+
+/// Save data on disk, under `root`:
+/// - `mods/<mod id>/<key>.sav`: per-mod key/value data,
+/// - `slots/<slot>.sav`: save slots.
+///
+/// Files are MessagePack and replaced atomically by writing a temporary file
+/// and renaming it over the old one.
+#[derive(Debug)]
+pub struct SaveStore {
+    root: PathBuf,
+    quota: u64,
+    /// Versions of the loaded mods, recorded with what they save.
+    versions: HashMap<String, String>,
+    pub requests: Vec<SlotRequest>,
+}
+
+impl Default for SaveStore {
+    fn default() -> Self {
+        Self::new(Path::new("saves"))
+    }
+}
+
+impl SaveStore {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            quota: DEFAULT_QUOTA,
+            versions: HashMap::new(),
+            requests: Vec::new(),
+        }
+    }
+
+    pub fn with_quota(mut self, quota: u64) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    pub fn set_version(&mut self, mod_id: &str, version: &str) {
+        self.versions
+            .insert(mod_id.to_string(), version.to_string());
+    }
+
+    pub fn version(&self, mod_id: &str) -> &str {
+        self.versions.get(mod_id).map_or("", String::as_str)
+    }
+
+    pub fn release_mod(&mut self, mod_id: &str) {
+        self.versions.remove(mod_id);
+    }
+
+    fn namespace(&self, mod_id: &str) -> PathBuf {
+        self.root.join("mods").join(mod_id)
+    }
+
+    fn entry_path(&self, mod_id: &str, key: &str) -> Result<PathBuf, String> {
+        check_name(key)?;
+        Ok(self.namespace(mod_id).join(format!("{}.sav", key)))
+    }
+
+    fn slot_path(&self, slot: &str) -> Result<PathBuf, String> {
+        check_name(slot)?;
+        Ok(self.root.join("slots").join(format!("{}.sav", slot)))
+    }
+
+    /// Saves `data` under `key` in the namespace of `mod_id`, unless that
+    /// would take the namespace over the quota.
+    pub fn save(&self, mod_id: &str, key: &str, data: Vec<u8>) -> Result<(), String> {
+        let path = self.entry_path(mod_id, key)?;
+        let bytes = encode(&Saved {
+            version: self.version(mod_id).to_string(),
+            data,
+        })?;
+
+        let used = self.used(mod_id) - file_size(&path);
+        if used + bytes.len() as u64 > self.quota {
+            return Err(format!(
+                "Saving {} would use {} of {} bytes",
+                key,
+                used + bytes.len() as u64,
+                self.quota
+            ));
+        }
+
+        write_atomic(&path, &bytes).map_err(|e| e.to_string())?;
+        debug!("{} saved {} ({} bytes)", mod_id, key, bytes.len());
+        Ok(())
+    }
+
+    pub fn load(&self, mod_id: &str, key: &str) -> Result<Option<Saved>, String> {
+        let path = self.entry_path(mod_id, key)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+        decode(&bytes).map(Some)
+    }
+
+    pub fn delete(&self, mod_id: &str, key: &str) -> Result<(), String> {
+        let path = self.entry_path(mod_id, key)?;
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Keys in the namespace of `mod_id`, sorted.
+    pub fn keys(&self, mod_id: &str) -> Vec<String> {
+        let mut keys: Vec<String> = std::fs::read_dir(self.namespace(mod_id))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "sav" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_string())
+            })
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Bytes used by the saved keys of `mod_id`. Leftover temporary files
+    /// and anything else in the directory don't count.
+    fn used(&self, mod_id: &str) -> u64 {
+        std::fs::read_dir(self.namespace(mod_id))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                if entry.path().extension()? != "sav" {
+                    return None;
+                }
+                let metadata = entry.metadata().ok()?;
+                metadata.is_file().then_some(metadata.len())
+            })
+            .sum()
+    }
+
+    /// Checks a section a mod contributed to a slot against the quota.
+    pub fn check_section(&self, mod_id: &str, section: &Saved) -> Result<(), String> {
+        if section.data.len() as u64 > self.quota {
+            return Err(format!(
+                "Save section of {} has {} bytes, at most {} are allowed",
+                mod_id,
+                section.data.len(),
+                self.quota
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn write_slot(&self, slot: &str, save: &SaveSlot) -> Result<(), Error> {
+        let path = self.slot_path(slot).anyhow()?;
+        let bytes = encode(save).anyhow()?;
+        write_atomic(&path, &bytes).log_msg("Failed to write save slot")?;
+        debug!("Saved slot {} ({} bytes)", slot, bytes.len());
+        Ok(())
+    }
+
+    pub fn read_slot(&self, slot: &str) -> Result<SaveSlot, Error> {
+        let path = self.slot_path(slot).anyhow()?;
+        let bytes = std::fs::read(&path).log_msg("Failed to read save slot")?;
+        decode(&bytes).anyhow()
+    }
+
+    /// Names of the existing save slots, sorted.
+    pub fn slots(&self) -> Vec<String> {
+        let mut slots: Vec<String> = std::fs::read_dir(self.root.join("slots"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "sav" {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_string())
+            })
+            .collect();
+        slots.sort();
+        slots
+    }
+}
+
+/// Keys and slot names become file names, so they are limited to ASCII
+/// letters, digits, `-` and `_`.
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid save name: {:?}", name))
+    }
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |metadata| metadata.len())
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    rmp_serde::to_vec(value).map_err(|e| e.to_string())
+}
+
+fn decode<T: for<'a> Deserialize<'a>>(bytes: &[u8]) -> Result<T, String> {
+    rmp_serde::from_slice(bytes).map_err(|e| format!("Corrupt save data: {}", e))
+}
+
+/// Writes next to `path` first and renames over it, so a crash never leaves
+/// a half-written file behind.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, bytes)?;
+    if let Err(e) = std::fs::rename(&tmp_path, path) {
+        warn!("Failed to replace {}: {}", path.display(), e);
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("saves-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn encoded_len(store: &SaveStore, mod_id: &str, data: &[u8]) -> u64 {
+        encode(&Saved {
+            version: store.version(mod_id).to_string(),
+            data: data.to_vec(),
+        })
+        .unwrap()
+        .len() as u64
+    }
+
+    #[test]
+    fn names_are_plain_file_names() {
+        for name in ["slot", "Slot-2", "a_b", &"x".repeat(64)] {
+            assert!(check_name(name).is_ok(), "{}", name);
+        }
+        for name in ["", "../up", "a/b", "a.b", "space d", "ü", &"x".repeat(65)] {
+            assert!(check_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn keys_round_trip_with_the_mod_version() {
+        let root = temp_dir("keys");
+        let mut store = SaveStore::new(&root);
+        store.set_version("a", "1.2.0");
+
+        store.save("a", "progress", vec![1, 2, 3]).unwrap();
+        store.save("a", "options", vec![4]).unwrap();
+        assert_eq!(
+            store.load("a", "progress").unwrap(),
+            Some(Saved {
+                version: "1.2.0".to_string(),
+                data: vec![1, 2, 3],
+            })
+        );
+        assert_eq!(store.load("b", "progress").unwrap(), None);
+        assert_eq!(store.keys("a"), ["options", "progress"]);
+        assert!(store.load("a", "../progress").is_err());
+
+        store.delete("a", "options").unwrap();
+        assert_eq!(store.keys("a"), ["progress"]);
+        // The write went through a temporary file that was renamed.
+        assert!(!root.join("mods/a/progress.tmp").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn quota_counts_what_a_key_replaces() {
+        let root = temp_dir("quota");
+        let probe = SaveStore::new(&root);
+        let size = encoded_len(&probe, "a", &[0; 100]);
+        let store = SaveStore::new(&root).with_quota(size * 2);
+
+        store.save("a", "one", vec![0; 100]).unwrap();
+        store.save("a", "two", vec![0; 100]).unwrap();
+        assert!(store.save("a", "three", vec![0; 100]).is_err());
+        // Overwriting a key only needs room for the difference.
+        store.save("a", "two", vec![1; 100]).unwrap();
+        assert!(store.save("a", "two", vec![1; 101]).is_err());
+        // Other mods have their own quota.
+        store.save("b", "one", vec![0; 100]).unwrap();
+
+        // Leftovers next to the keys don't count.
+        store.delete("a", "two").unwrap();
+        std::fs::write(root.join("mods/a/two.tmp"), vec![0; 1000]).unwrap();
+        std::fs::create_dir(root.join("mods/a/nested.sav")).unwrap();
+        store.save("a", "two", vec![0; 100]).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn slots_round_trip() {
+        let root = temp_dir("slots");
+        let store = SaveStore::new(&root);
+        let mut slot = SaveSlot::default();
+        slot.sections.insert(
+            "a".to_string(),
+            Saved {
+                version: "1.0.0".to_string(),
+                data: vec![7; 16],
+            },
+        );
+
+        store.write_slot("quick", &slot).unwrap();
+        store.write_slot("auto-1", &SaveSlot::default()).unwrap();
+        assert_eq!(store.read_slot("quick").unwrap(), slot);
+        assert_eq!(store.slots(), ["auto-1", "quick"]);
+        assert!(store.read_slot("missing").is_err());
+        assert!(store.write_slot("../quick", &slot).is_err());
+
+        std::fs::write(root.join("slots/quick.sav"), [0xc1]).unwrap();
+        assert!(store.read_slot("quick").is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
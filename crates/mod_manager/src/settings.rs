@@ -0,0 +1,538 @@
+use crate::saves::write_atomic;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{debug, warn};
+
+/// How often `SettingsStore::poll_changes` looks at the files on disk.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SettingKind {
+    Boolean,
+    Integer,
+    Float,
+    Text,
+}
+
+impl SettingKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SettingKind::Boolean => "boolean",
+            SettingKind::Integer => "integer",
+            SettingKind::Float => "float",
+            SettingKind::Text => "text",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingValue {
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl SettingValue {
+    pub fn kind(&self) -> SettingKind {
+        match self {
+            SettingValue::Boolean(_) => SettingKind::Boolean,
+            SettingValue::Integer(_) => SettingKind::Integer,
+            SettingValue::Float(_) => SettingKind::Float,
+            SettingValue::Text(_) => SettingKind::Text,
+        }
+    }
+
+    fn from_toml(value: &toml::Value) -> Option<Self> {
+        match value {
+            toml::Value::Boolean(value) => Some(SettingValue::Boolean(*value)),
+            toml::Value::Integer(value) => Some(SettingValue::Integer(*value)),
+            toml::Value::Float(value) => Some(SettingValue::Float(*value)),
+            toml::Value::String(value) => Some(SettingValue::Text(value.clone())),
+            _ => None,
+        }
+    }
+
+    fn to_toml(&self) -> toml::Value {
+        match self {
+            SettingValue::Boolean(value) => toml::Value::Boolean(*value),
+            SettingValue::Integer(value) => toml::Value::Integer(*value),
+            SettingValue::Float(value) => toml::Value::Float(*value),
+            SettingValue::Text(value) => toml::Value::String(value.clone()),
+        }
+    }
+}
+
+/// A setting as a mod declares it in its manifest:
+///
+/// ```toml
+/// [settings.ghost_amount]
+/// type = "integer"
+/// default = 10
+/// min = 0
+/// max = 100
+/// description = "Number of ghosts following the player"
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SettingSchema {
+    #[serde(rename = "type")]
+    pub kind: SettingKind,
+    pub default: toml::Value,
+    /// Bounds of numeric settings, inclusive.
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl SettingSchema {
+    pub fn default_value(&self) -> Result<SettingValue, String> {
+        let value = SettingValue::from_toml(&self.default)
+            .ok_or_else(|| format!("Unsupported default {}", self.default))?;
+        self.validate(value)
+            .map_err(|e| format!("Invalid default: {}", e))
+    }
+
+    /// Checks `value` against the type and bounds. Integers are accepted
+    /// for float settings.
+    pub fn validate(&self, value: SettingValue) -> Result<SettingValue, String> {
+        let value = match (self.kind, value) {
+            (SettingKind::Float, SettingValue::Integer(value)) => SettingValue::Float(value as f64),
+            (kind, value) if value.kind() == kind => value,
+            (kind, value) => {
+                return Err(format!(
+                    "Expected {}, got {}",
+                    kind.name(),
+                    value.kind().name()
+                ))
+            }
+        };
+
+        let number = match value {
+            SettingValue::Integer(value) => value as f64,
+            SettingValue::Float(value) => value,
+            _ => return Ok(value),
+        };
+        if self.min.is_some_and(|min| number < min) || self.max.is_some_and(|max| number > max) {
+            return Err(format!(
+                "{} is outside {}..={}",
+                number,
+                self.min.map_or(String::new(), |min| min.to_string()),
+                self.max.map_or(String::new(), |max| max.to_string())
+            ));
+        }
+        Ok(value)
+    }
+}
+
+/// A setting with its current value, for listing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingInfo {
+    pub name: String,
+    pub description: String,
+    pub value: SettingValue,
+    pub default: SettingValue,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[derive(Debug)]
+struct ModSettings {
+    schema: BTreeMap<String, SettingSchema>,
+    values: BTreeMap<String, SettingValue>,
+    /// Modification time of the file when it was last read or written.
+    modified: Option<SystemTime>,
+}
+
+/// Settings of the loaded mods. What the user changed is kept in
+/// `<root>/<mod id>.toml`; settings missing there have their default.
+/// Values are validated against the schema from the mod's manifest, and
+/// invalid ones fall back to the default.
+#[derive(Debug)]
+pub struct SettingsStore {
+    root: PathBuf,
+    mods: HashMap<String, ModSettings>,
+    /// Names of changed settings by mod, until `take_changes`.
+    changes: BTreeMap<String, BTreeSet<String>>,
+    last_poll: Instant,
+}
+
+impl Default for SettingsStore {
+    fn default() -> Self {
+        Self::new(Path::new("config/settings"))
+    }
+}
+
+impl SettingsStore {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            mods: HashMap::new(),
+            changes: BTreeMap::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    fn path(&self, mod_id: &str) -> PathBuf {
+        self.root.join(format!("{}.toml", mod_id))
+    }
+
+    /// Sets up the settings of a mod and reads what the user changed. The
+    /// defaults in `schema` have to be valid, see `ModManifest::load`.
+    pub fn add_mod(&mut self, mod_id: &str, schema: BTreeMap<String, SettingSchema>) {
+        let path = self.path(mod_id);
+        let values = read_values(&path, &schema);
+        self.mods.insert(
+            mod_id.to_string(),
+            ModSettings {
+                schema,
+                values,
+                modified: modified(&path),
+            },
+        );
+    }
+
+    pub fn release_mod(&mut self, mod_id: &str) {
+        self.mods.remove(mod_id);
+        self.changes.remove(mod_id);
+    }
+
+    pub fn get(&self, mod_id: &str, name: &str) -> Option<&SettingValue> {
+        self.mods.get(mod_id)?.values.get(name)
+    }
+
+    /// Changes a setting and writes the mod's settings back to disk.
+    pub fn set(&mut self, mod_id: &str, name: &str, value: SettingValue) -> Result<(), String> {
+        let path = self.path(mod_id);
+        let settings = self
+            .mods
+            .get_mut(mod_id)
+            .ok_or_else(|| format!("Mod {} is not loaded", mod_id))?;
+        let schema = settings
+            .schema
+            .get(name)
+            .ok_or_else(|| format!("Unknown setting {}", name))?;
+        let value = schema.validate(value)?;
+        if settings.values.get(name) == Some(&value) {
+            return Ok(());
+        }
+
+        settings.values.insert(name.to_string(), value);
+        write_values(&path, settings)?;
+        settings.modified = modified(&path);
+        self.changes
+            .entry(mod_id.to_string())
+            .or_default()
+            .insert(name.to_string());
+        Ok(())
+    }
+
+    /// Every setting of a mod, sorted by name.
+    pub fn list(&self, mod_id: &str) -> Vec<SettingInfo> {
+        let Some(settings) = self.mods.get(mod_id) else {
+            return Vec::new();
+        };
+        settings
+            .schema
+            .iter()
+            .filter_map(|(name, schema)| {
+                Some(SettingInfo {
+                    name: name.clone(),
+                    description: schema.description.clone(),
+                    value: settings.values.get(name)?.clone(),
+                    default: schema.default_value().ok()?,
+                    min: schema.min,
+                    max: schema.max,
+                })
+            })
+            .collect()
+    }
+
+    /// Rereads the files the user edited since they were last read. Only
+    /// looks at the disk once per `POLL_INTERVAL`.
+    pub fn poll_changes(&mut self) {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = Instant::now();
+
+        let paths: Vec<(String, PathBuf)> = self
+            .mods
+            .keys()
+            .map(|mod_id| (mod_id.clone(), self.path(mod_id)))
+            .collect();
+        for (mod_id, path) in paths {
+            let settings = self.mods.get_mut(&mod_id).unwrap();
+            let current = modified(&path);
+            if current == settings.modified {
+                continue;
+            }
+            settings.modified = current;
+
+            debug!("Reloading settings of {}", mod_id);
+            let values = read_values(&path, &settings.schema);
+            for (name, value) in &values {
+                if settings.values.get(name) != Some(value) {
+                    self.changes
+                        .entry(mod_id.clone())
+                        .or_default()
+                        .insert(name.clone());
+                }
+            }
+            settings.values = values;
+        }
+    }
+
+    /// Mods with the names of their settings that changed since the last
+    /// call.
+    pub fn take_changes(&mut self) -> Vec<(String, Vec<String>)> {
+        std::mem::take(&mut self.changes)
+            .into_iter()
+            .map(|(mod_id, names)| (mod_id, names.into_iter().collect()))
+            .collect()
+    }
+}
+
+/// Values from the file at `path` on top of the defaults. Problems are
+/// logged and the affected settings keep their default.
+fn read_values(
+    path: &Path,
+    schema: &BTreeMap<String, SettingSchema>,
+) -> BTreeMap<String, SettingValue> {
+    let mut values: BTreeMap<String, SettingValue> = schema
+        .iter()
+        .filter_map(|(name, schema)| Some((name.clone(), schema.default_value().ok()?)))
+        .collect();
+    if !path.exists() {
+        return values;
+    }
+
+    let table = match std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| toml::from_str::<toml::Table>(&text).map_err(|e| e.to_string()))
+    {
+        Ok(table) => table,
+        Err(e) => {
+            warn!("Failed to read settings {}: {}", path.display(), e);
+            return values;
+        }
+    };
+    for (name, value) in table {
+        let Some(schema) = schema.get(&name) else {
+            warn!("Unknown setting {} in {}", name, path.display());
+            continue;
+        };
+        let value = SettingValue::from_toml(&value)
+            .ok_or_else(|| format!("Unsupported value {}", value))
+            .and_then(|value| schema.validate(value));
+        match value {
+            Ok(value) => {
+                values.insert(name, value);
+            }
+            Err(e) => warn!("Ignoring setting {} in {}: {}", name, path.display(), e),
+        }
+    }
+    values
+}
+
+/// Writes the settings that differ from their default.
+fn write_values(path: &Path, settings: &ModSettings) -> Result<(), String> {
+    let table: toml::Table = settings
+        .values
+        .iter()
+        .filter(|(name, value)| {
+            settings
+                .schema
+                .get(*name)
+                .and_then(|schema| schema.default_value().ok())
+                .as_ref()
+                != Some(*value)
+        })
+        .map(|(name, value)| (name.clone(), value.to_toml()))
+        .collect();
+
+    let text = toml::to_string(&table).map_err(|e| e.to_string())?;
+    write_atomic(path, text.as_bytes()).map_err(|e| e.to_string())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("settings-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn schema(kind: SettingKind, default: toml::Value) -> SettingSchema {
+        SettingSchema {
+            kind,
+            default,
+            min: None,
+            max: None,
+            description: String::new(),
+        }
+    }
+
+    fn ghosts() -> BTreeMap<String, SettingSchema> {
+        BTreeMap::from([
+            (
+                "amount".to_string(),
+                SettingSchema {
+                    min: Some(0.0),
+                    max: Some(100.0),
+                    ..schema(SettingKind::Integer, toml::Value::Integer(10))
+                },
+            ),
+            (
+                "speed".to_string(),
+                schema(SettingKind::Float, toml::Value::Float(1.5)),
+            ),
+        ])
+    }
+
+    #[test]
+    fn validate_promotes_integers_and_checks_bounds() {
+        let float = SettingSchema {
+            min: Some(0.0),
+            max: Some(1.0),
+            ..schema(SettingKind::Float, toml::Value::Float(0.5))
+        };
+        assert_eq!(
+            float.validate(SettingValue::Integer(1)),
+            Ok(SettingValue::Float(1.0))
+        );
+        assert_eq!(
+            float.validate(SettingValue::Float(0.0)),
+            Ok(SettingValue::Float(0.0))
+        );
+        assert!(float.validate(SettingValue::Integer(2)).is_err());
+        assert!(float.validate(SettingValue::Float(-0.1)).is_err());
+        assert!(float.validate(SettingValue::Boolean(true)).is_err());
+
+        // Floats aren't narrowed to integers.
+        let integer = schema(SettingKind::Integer, toml::Value::Integer(0));
+        assert!(integer.validate(SettingValue::Float(1.0)).is_err());
+        assert_eq!(
+            integer.validate(SettingValue::Integer(-5)),
+            Ok(SettingValue::Integer(-5))
+        );
+    }
+
+    #[test]
+    fn bad_files_fall_back_to_defaults() {
+        let root = temp_dir("bad");
+        std::fs::write(root.join("broken.toml"), "amount = ").unwrap();
+        std::fs::write(
+            root.join("partly.toml"),
+            "amount = 500\nspeed = 3\nunknown = true\n",
+        )
+        .unwrap();
+        let mut store = SettingsStore::new(&root);
+        store.add_mod("broken", ghosts());
+        store.add_mod("partly", ghosts());
+
+        assert_eq!(
+            store.get("broken", "amount"),
+            Some(&SettingValue::Integer(10))
+        );
+        assert_eq!(
+            store.get("broken", "speed"),
+            Some(&SettingValue::Float(1.5))
+        );
+        // Out of bounds keeps the default, the rest is still read.
+        assert_eq!(
+            store.get("partly", "amount"),
+            Some(&SettingValue::Integer(10))
+        );
+        assert_eq!(
+            store.get("partly", "speed"),
+            Some(&SettingValue::Float(3.0))
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn only_changed_settings_are_written() {
+        let root = temp_dir("write");
+        let mut store = SettingsStore::new(&root);
+        store.add_mod("ghosts", ghosts());
+
+        store
+            .set("ghosts", "amount", SettingValue::Integer(20))
+            .unwrap();
+        store
+            .set("ghosts", "speed", SettingValue::Integer(1))
+            .unwrap();
+        store
+            .set("ghosts", "speed", SettingValue::Float(1.5))
+            .unwrap();
+        assert!(store
+            .set("ghosts", "amount", SettingValue::Integer(-1))
+            .is_err());
+
+        let text = std::fs::read_to_string(root.join("ghosts.toml")).unwrap();
+        assert_eq!(text.trim(), "amount = 20");
+        assert!(!root.join("ghosts.tmp").exists());
+
+        let mut reloaded = SettingsStore::new(&root);
+        reloaded.add_mod("ghosts", ghosts());
+        assert_eq!(
+            reloaded.get("ghosts", "amount"),
+            Some(&SettingValue::Integer(20))
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn poll_changes_reports_edited_settings() {
+        let root = temp_dir("poll");
+        let path = root.join("ghosts.toml");
+        std::fs::write(&path, "amount = 20\n").unwrap();
+        let mut store = SettingsStore::new(&root);
+        store.add_mod("ghosts", ghosts());
+        store
+            .set("ghosts", "speed", SettingValue::Float(2.0))
+            .unwrap();
+        assert_eq!(
+            store.take_changes(),
+            [("ghosts".to_string(), vec!["speed".to_string()])]
+        );
+
+        std::fs::write(&path, "amount = 30\nspeed = 2.0\n").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+
+        // Too soon after the last poll.
+        store.poll_changes();
+        assert!(store.take_changes().is_empty());
+
+        store.last_poll = Instant::now() - POLL_INTERVAL;
+        store.poll_changes();
+        assert_eq!(
+            store.take_changes(),
+            [("ghosts".to_string(), vec!["amount".to_string()])]
+        );
+        assert_eq!(
+            store.get("ghosts", "amount"),
+            Some(&SettingValue::Integer(30))
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
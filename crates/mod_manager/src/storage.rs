@@ -1,3 +1,21 @@
+use crate::{
+    actions::ActionMap,
+    audio::Mixer,
+    bus::MessageBus,
+    ecs::Ecs,
+    events::{Event, EventKind},
+    input::InputState,
+    random::Random,
+    render::{Camera, DrawItem, RenderState, RenderTargets},
+    resources::ResourceStack,
+    saves::SaveStore,
+    settings::SettingsStore,
+    timers::Timers,
+    vfs::Vfs,
+    world::WorldStore,
+};
+use std::collections::{HashMap, HashSet};
+
 #[derive(Debug)]
 pub struct ScalStorage<T: Default> {
     value: T,
@@ -45,6 +63,15 @@ impl<T> VecStorage<T> {
         self.values.clear();
     }
 
+    pub fn pop(&mut self) -> Option<T> {
+        self.values.pop()
+    }
+
+    /// Removes and returns all values.
+    pub fn take(&mut self) -> Vec<T> {
+        std::mem::take(&mut self.values)
+    }
+
     pub fn len(&self) -> usize {
         self.values.len()
     }
@@ -58,25 +85,105 @@ impl<T> VecStorage<T> {
     }
 }
 
+#[derive(Debug)]
+pub struct MapStorage<T: Default> {
+    values: HashMap<String, T>,
+}
+
+impl<T: Default> MapStorage<T> {
+    pub fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: T) {
+        self.values.insert(key.to_string(), value);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&T> {
+        self.values.get(key)
+    }
+
+    /// Returns the value for `key`, inserting the default first if missing.
+    pub fn entry(&mut self, key: &str) -> &mut T {
+        self.values.entry(key.to_string()).or_default()
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<T> {
+        self.values.remove(key)
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, String, T> {
+        self.values.iter()
+    }
+}
+
 #[derive(Debug)]
 pub struct Storages {
-    pub textures: VecStorage<((u32, u32, u32, u32), (u8, u8, u8, u8))>,
-    pub color: ScalStorage<(u8, u8, u8, u8)>,
+    pub draw_list: VecStorage<DrawItem>,
+    pub current_mod: ScalStorage<String>,
+    pub render_states: MapStorage<RenderState>,
+    pub render_targets: RenderTargets,
+    pub camera: ScalStorage<Camera>,
     pub window_size: ScalStorage<(u32, u32)>,
+    pub input: InputState,
+    pub actions: ActionMap,
+    /// Events waiting for `ModManager::dispatch_events`.
+    pub events: VecStorage<Event>,
+    pub subscriptions: MapStorage<HashSet<EventKind>>,
+    pub bus: MessageBus,
+    pub world: WorldStore,
+    pub ecs: Ecs,
+    pub saves: SaveStore,
+    pub vfs: Vfs,
+    pub resources: ResourceStack,
+    pub settings: SettingsStore,
+    pub audio: Mixer,
+    pub timers: Timers,
+    pub random: Random,
 }
 
 impl Storages {
     pub fn new() -> Self {
         Self {
-            textures: VecStorage::new(),
-            color: ScalStorage::new(),
+            draw_list: VecStorage::new(),
+            current_mod: ScalStorage::new(),
+            render_states: MapStorage::new(),
+            render_targets: RenderTargets::new(),
+            camera: ScalStorage::new(),
             window_size: ScalStorage::new(),
+            input: InputState::new(),
+            actions: ActionMap::new(),
+            events: VecStorage::new(),
+            subscriptions: MapStorage::new(),
+            bus: MessageBus::new(),
+            world: WorldStore::new(),
+            ecs: Ecs::new(),
+            saves: SaveStore::default(),
+            vfs: Vfs::default(),
+            resources: ResourceStack::default(),
+            settings: SettingsStore::default(),
+            audio: Mixer::default(),
+            timers: Timers::default(),
+            random: Random::default(),
         }
     }
 
     pub fn clear(&mut self, window_size: (u32, u32)) {
-        self.textures.clear();
-        self.color = ScalStorage::new();
+        self.draw_list.clear();
+        self.render_targets.end_frame();
+        self.world.commit();
         self.window_size.set(window_size);
     }
+
+    /// Draw state of the mod currently calling into the host.
+    pub fn render_state(&mut self) -> &mut RenderState {
+        let mod_id = self.current_mod.get().clone();
+        self.render_states.entry(&mod_id)
+    }
 }
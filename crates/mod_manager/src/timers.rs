@@ -0,0 +1,288 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Shortest interval, so an interval timer can't fire in a tight loop.
+const MIN_INTERVAL_MS: f64 = 1.0;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Timer {
+    owner: String,
+    /// Game time the timer fires at, in milliseconds.
+    due: f64,
+    interval: Option<f64>,
+}
+
+/// A pending timer in a save slot. Times are relative, so the timer fires
+/// as long after loading as it had left when saving.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedTimer {
+    pub owner: String,
+    pub id: u64,
+    pub remaining: f64,
+    pub interval: Option<f64>,
+}
+
+/// Game clock and the timers mods set on it. Game time advances with the
+/// simulation steps, see `GameLoop`; real time is the wall clock since
+/// startup. Both are in milliseconds.
+///
+/// A timer that came due is reported once per `advance`, even if an
+/// interval fell due several times since the last one.
+#[derive(Debug)]
+pub struct Timers {
+    start: Instant,
+    game_time: f64,
+    /// How far drawing is between the last step and the next, see `Frame`.
+    alpha: f32,
+    next_id: u64,
+    timers: BTreeMap<u64, Timer>,
+}
+
+impl Default for Timers {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            game_time: 0.0,
+            alpha: 0.0,
+            next_id: 1,
+            timers: BTreeMap::new(),
+        }
+    }
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn game_time(&self) -> f64 {
+        self.game_time
+    }
+
+    pub fn real_time(&self) -> f64 {
+        self.start.elapsed().as_secs_f64() * 1000.0
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    pub fn set_alpha(&mut self, alpha: f32) {
+        self.alpha = alpha;
+    }
+
+    fn add(&mut self, owner: &str, delay: f64, interval: Option<f64>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.timers.insert(
+            id,
+            Timer {
+                owner: owner.to_string(),
+                due: self.game_time + delay,
+                interval,
+            },
+        );
+        id
+    }
+
+    /// Fires once after `ms` milliseconds of game time.
+    pub fn set_timeout(&mut self, owner: &str, ms: u32) -> u64 {
+        self.add(owner, ms as f64, None)
+    }
+
+    /// Fires every `ms` milliseconds of game time until cancelled.
+    pub fn set_interval(&mut self, owner: &str, ms: u32) -> u64 {
+        let interval = (ms as f64).max(MIN_INTERVAL_MS);
+        self.add(owner, interval, Some(interval))
+    }
+
+    /// Cancels a timer of `owner`. Returns whether it was pending.
+    pub fn cancel(&mut self, owner: &str, id: u64) -> bool {
+        if self
+            .timers
+            .get(&id)
+            .is_some_and(|timer| timer.owner == owner)
+        {
+            self.timers.remove(&id);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn release_mod(&mut self, owner: &str) {
+        self.timers.retain(|_, timer| timer.owner != owner);
+    }
+
+    /// Advances game time by `game_delta` and returns the timers that came
+    /// due, with their owners, in the order they came due.
+    pub fn advance(&mut self, game_delta: Duration) -> Vec<(String, u64)> {
+        self.game_time += game_delta.as_secs_f64() * 1000.0;
+
+        let mut due: Vec<(f64, u64)> = self
+            .timers
+            .iter()
+            .filter(|(_, timer)| timer.due <= self.game_time)
+            .map(|(id, timer)| (timer.due, *id))
+            .collect();
+        due.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let mut fired = Vec::new();
+        for (_, id) in due {
+            let Some(timer) = self.timers.get_mut(&id) else {
+                continue;
+            };
+            fired.push((timer.owner.clone(), id));
+            match timer.interval {
+                Some(interval) => {
+                    while timer.due <= self.game_time {
+                        timer.due += interval;
+                    }
+                }
+                None => {
+                    self.timers.remove(&id);
+                }
+            }
+        }
+        fired
+    }
+
+    /// Pending timers, for a save slot.
+    pub fn save(&self) -> Vec<SavedTimer> {
+        self.timers
+            .iter()
+            .map(|(id, timer)| SavedTimer {
+                owner: timer.owner.clone(),
+                id: *id,
+                remaining: (timer.due - self.game_time).max(0.0),
+                interval: timer.interval,
+            })
+            .collect()
+    }
+
+    /// Replaces the timers of `mods` with the ones from a save slot. Ids are
+    /// kept, mods may have saved them, so a saved timer whose id is taken by
+    /// a timer of another mod is dropped.
+    pub fn restore(&mut self, saved: &[SavedTimer], mods: &[String]) {
+        self.timers.retain(|_, timer| !mods.contains(&timer.owner));
+        for timer in saved {
+            if !mods.contains(&timer.owner) {
+                continue;
+            }
+            if let Some(other) = self.timers.get(&timer.id) {
+                warn!(
+                    "Dropping saved timer {} of {}, the id is taken by {}",
+                    timer.id, timer.owner, other.owner
+                );
+                continue;
+            }
+            self.timers.insert(
+                timer.id,
+                Timer {
+                    owner: timer.owner.clone(),
+                    due: self.game_time + timer.remaining,
+                    interval: timer.interval.map(|interval| interval.max(MIN_INTERVAL_MS)),
+                },
+            );
+            self.next_id = self.next_id.max(timer.id + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn fired(timers: &mut Timers, delta: u64) -> Vec<u64> {
+        timers
+            .advance(ms(delta))
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect()
+    }
+
+    #[test]
+    fn timers_fire_in_the_order_they_came_due() {
+        let mut timers = Timers::new();
+        let late = timers.set_timeout("a", 30);
+        let early = timers.set_timeout("b", 10);
+        let same = timers.set_timeout("a", 10);
+
+        assert!(fired(&mut timers, 5).is_empty());
+        assert_eq!(fired(&mut timers, 30), [early, same, late]);
+        assert!(fired(&mut timers, 100).is_empty());
+        assert_eq!(timers.game_time(), 135.0);
+    }
+
+    #[test]
+    fn intervals_fire_once_per_advance_and_catch_up() {
+        let mut timers = Timers::new();
+        let interval = timers.set_interval("a", 10);
+
+        // Fell due three times, reported once.
+        assert_eq!(fired(&mut timers, 35), [interval]);
+        // Next due at 40, not 20 or 30.
+        assert!(fired(&mut timers, 4).is_empty());
+        assert_eq!(fired(&mut timers, 1), [interval]);
+        assert!(fired(&mut timers, 9).is_empty());
+        assert_eq!(fired(&mut timers, 1), [interval]);
+
+        let fast = timers.set_interval("a", 0);
+        assert_eq!(fired(&mut timers, 1), [fast]);
+    }
+
+    #[test]
+    fn only_the_owner_cancels() {
+        let mut timers = Timers::new();
+        let timeout = timers.set_timeout("a", 10);
+        let interval = timers.set_interval("a", 10);
+
+        assert!(!timers.cancel("b", timeout));
+        assert!(timers.cancel("a", timeout));
+        assert!(!timers.cancel("a", timeout));
+        assert!(timers.cancel("a", interval));
+        assert!(fired(&mut timers, 100).is_empty());
+    }
+
+    #[test]
+    fn save_and_restore_keep_what_is_left() {
+        let mut timers = Timers::new();
+        let timeout = timers.set_timeout("a", 50);
+        let interval = timers.set_interval("a", 20);
+        timers.set_timeout("b", 10);
+        timers.advance(ms(30));
+        let saved = timers.save();
+
+        let mut restored = Timers::new();
+        restored.advance(ms(1000));
+        restored.restore(&saved, &["a".to_string()]);
+        assert_eq!(fired(&mut restored, 10), [interval]);
+        assert_eq!(fired(&mut restored, 10), [timeout]);
+        assert_eq!(fired(&mut restored, 10), [interval]);
+        // New timers don't reuse restored ids.
+        assert!(restored.set_timeout("a", 10) > interval);
+    }
+
+    #[test]
+    fn restore_drops_timers_whose_id_is_taken() {
+        let mut saving = Timers::new();
+        let saved_id = saving.set_timeout("a", 10);
+        let saved = saving.save();
+
+        let mut timers = Timers::new();
+        let other = timers.set_timeout("b", 20);
+        assert_eq!(other, saved_id);
+        timers.restore(&saved, &["a".to_string()]);
+
+        // "b" keeps its timer, "a"'s is dropped instead of replacing it.
+        assert_eq!(timers.advance(ms(20)), [("b".to_string(), other)]);
+    }
+}
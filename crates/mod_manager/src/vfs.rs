@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+use tracing::trace;
+
+/// Top-level directory of a virtual path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Root {
+    /// The mod's own assets, read-only.
+    Assets,
+    /// The mod's data directory, read-write.
+    Data,
+}
+
+/// A virtual path split into its root and the relative path below it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualPath {
+    pub root: Root,
+    pub relative: PathBuf,
+}
+
+impl VirtualPath {
+    /// Parses `assets/...` or `data/...`, see `relative_path` for what the
+    /// rest may contain.
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let (root, rest) = path.split_once('/').unwrap_or((path, ""));
+        let root = match root {
+            "assets" => Root::Assets,
+            "data" => Root::Data,
+            _ => return Err(format!("Path {:?} is outside assets/ and data/", path)),
+        };
+        let relative = if rest.is_empty() {
+            PathBuf::new()
+        } else {
+            relative_path(rest)?
+        };
+
+        Ok(Self { root, relative })
+    }
+}
+
+/// Turns a `/`-separated path from a mod into a relative path. Empty, `.`
+/// and `..` components, backslashes and anything that could name a drive or
+/// a device are rejected.
+pub(crate) fn relative_path(path: &str) -> Result<PathBuf, String> {
+    let mut relative = PathBuf::new();
+    for component in path.split('/') {
+        let valid = !component.is_empty()
+            && component != "."
+            && component != ".."
+            && !component.contains(['\\', ':', '\0']);
+        if !valid {
+            return Err(format!("Invalid path {:?}", path));
+        }
+        relative.push(component);
+    }
+    // Belt and braces: whatever got through has to be plain names.
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(format!("Invalid path {:?}", path));
+    }
+
+    Ok(relative)
+}
+
+/// Sandboxed filesystem for mods. A mod sees its own assets under `assets/`
+/// and a data directory under `data/`, nothing else.
+///
+/// Assets can be overlaid: a mod that depends on another mod may ship
+/// `assets/overrides/<other mod>/<path>` to replace `<path>` in the other
+/// mod's assets. The last such mod in dependency order wins.
+#[derive(Debug, Default)]
+pub struct Vfs {
+    data_root: PathBuf,
+    /// Asset directory of every loaded mod.
+    assets: HashMap<String, PathBuf>,
+    /// Mods in dependency order, with their dependencies.
+    order: Vec<(String, Vec<String>)>,
+}
+
+impl Vfs {
+    pub fn new(data_root: &Path) -> Self {
+        Self {
+            data_root: data_root.to_path_buf(),
+            ..Self::default()
+        }
+    }
+
+    pub fn add_mod(&mut self, mod_id: &str, assets_dir: &Path) {
+        self.assets
+            .insert(mod_id.to_string(), assets_dir.to_path_buf());
+    }
+
+    /// Sets the order overrides are applied in, mods with their dependencies.
+    pub fn set_order(&mut self, order: Vec<(String, Vec<String>)>) {
+        self.order = order;
+    }
+
+    pub fn release_mod(&mut self, mod_id: &str) {
+        self.assets.remove(mod_id);
+        self.order.retain(|(id, _)| id != mod_id);
+    }
+
+    fn data_dir(&self, mod_id: &str) -> PathBuf {
+        self.data_root.join(mod_id)
+    }
+
+    /// Asset directories that can provide `mod_id`'s assets, the ones that
+    /// win first: overrides from mods depending on it, latest first, then
+    /// the mod's own.
+    fn asset_dirs(&self, mod_id: &str) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self
+            .order
+            .iter()
+            .rev()
+            .filter(|(_, dependencies)| dependencies.iter().any(|id| id == mod_id))
+            .filter_map(|(id, _)| self.assets.get(id))
+            .map(|dir| dir.join("overrides").join(mod_id))
+            .collect();
+        dirs.extend(self.assets.get(mod_id).cloned());
+        dirs
+    }
+
+    /// Real path of a virtual path. Assets resolve through overrides to the
+    /// first directory that has the file.
+    pub fn resolve(&self, mod_id: &str, path: &VirtualPath) -> Result<PathBuf, String> {
+        match path.root {
+            Root::Data => Ok(self.data_dir(mod_id).join(&path.relative)),
+            Root::Assets => {
+                let dirs = self.asset_dirs(mod_id);
+                let resolved = dirs
+                    .iter()
+                    .map(|dir| (dir, dir.join(&path.relative)))
+                    .find(|(_, candidate)| candidate.exists());
+                match resolved {
+                    Some((dir, resolved)) => {
+                        check_inside(dir, &resolved)?;
+                        trace!(
+                            "{} resolved to {}",
+                            path.relative.display(),
+                            resolved.display()
+                        );
+                        Ok(resolved)
+                    }
+                    None => Err(format!("Asset {} not found", path.relative.display())),
+                }
+            }
+        }
+    }
+
+    pub fn read(&self, mod_id: &str, path: &str) -> Result<Vec<u8>, String> {
+        let path = VirtualPath::parse(path)?;
+        let resolved = self.resolve(mod_id, &path)?;
+        if path.root == Root::Data {
+            check_inside(&self.data_dir(mod_id), &resolved)?;
+        }
+        std::fs::read(&resolved).map_err(|e| e.to_string())
+    }
+
+    pub fn write(&self, mod_id: &str, path: &str, data: &[u8]) -> Result<(), String> {
+        let path = VirtualPath::parse(path)?;
+        if path.root != Root::Data {
+            return Err("Assets are read-only".to_string());
+        }
+        let resolved = self.resolve(mod_id, &path)?;
+        check_inside(&self.data_dir(mod_id), &resolved)?;
+        if let Some(parent) = resolved.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            check_inside(&self.data_dir(mod_id), &resolved)?;
+        }
+        std::fs::write(&resolved, data).map_err(|e| e.to_string())
+    }
+
+    pub fn remove(&self, mod_id: &str, path: &str) -> Result<(), String> {
+        let path = VirtualPath::parse(path)?;
+        if path.root != Root::Data {
+            return Err("Assets are read-only".to_string());
+        }
+        let resolved = self.resolve(mod_id, &path)?;
+        check_inside(&self.data_dir(mod_id), &resolved)?;
+        std::fs::remove_file(&resolved).map_err(|e| e.to_string())
+    }
+
+    pub fn exists(&self, mod_id: &str, path: &str) -> bool {
+        VirtualPath::parse(path)
+            .and_then(|path| self.resolve(mod_id, &path))
+            .is_ok_and(|resolved| resolved.exists())
+    }
+
+    /// Names of the entries in a directory, sorted. Asset directories list
+    /// what all overlays provide.
+    pub fn list(&self, mod_id: &str, path: &str) -> Result<Vec<String>, String> {
+        let path = VirtualPath::parse(path.strip_suffix('/').unwrap_or(path))?;
+        let dirs = match path.root {
+            Root::Data => vec![self.data_dir(mod_id)],
+            Root::Assets => self.asset_dirs(mod_id),
+        };
+
+        let mut names = Vec::new();
+        for dir in dirs {
+            let entries = match std::fs::read_dir(dir.join(&path.relative)) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                if let Some(name) = entry.file_name().to_str() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+}
+
+/// Rejects paths that leave `dir` through symlinks. The deepest part of
+/// `path` that exists is checked, so a dangling link can't be followed
+/// later by a write.
+pub(crate) fn check_inside(dir: &Path, path: &Path) -> Result<(), String> {
+    let leaves = || "Path leaves the mod's directory".to_string();
+    if dir.symlink_metadata().is_err() {
+        // Nothing to follow if the directory doesn't exist yet.
+        return Ok(());
+    }
+    let dir = dir.canonicalize().map_err(|_| leaves())?;
+
+    let mut existing = path;
+    while existing.symlink_metadata().is_err() {
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => return Ok(()),
+        }
+    }
+    match existing.canonicalize() {
+        Ok(existing) if existing.starts_with(&dir) => Ok(()),
+        _ => Err(leaves()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vfs-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn writes_stay_in_the_data_directory() {
+        let root = temp_dir("write");
+        let vfs = Vfs::new(&root.join("data"));
+        vfs.write("a", "data/saves/one.bin", b"one").unwrap();
+        assert_eq!(
+            std::fs::read(root.join("data/a/saves/one.bin")).unwrap(),
+            b"one"
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dangling_links_out_are_rejected() {
+        let root = temp_dir("dangling");
+        let vfs = Vfs::new(&root.join("data"));
+        std::fs::create_dir_all(root.join("data/a")).unwrap();
+        let outside = root.join("outside.bin");
+        std::os::unix::fs::symlink(&outside, root.join("data/a/link.bin")).unwrap();
+        std::os::unix::fs::symlink(root.join("elsewhere"), root.join("data/a/dir")).unwrap();
+
+        assert!(vfs.write("a", "data/link.bin", b"escaped").is_err());
+        assert!(vfs.write("a", "data/dir/file.bin", b"escaped").is_err());
+        assert!(!outside.exists());
+        assert!(!root.join("elsewhere").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
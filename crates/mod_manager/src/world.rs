@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::{debug, trace};
+use types::SerdeType;
+
+pub type Entity = u64;
+
+#[derive(Debug, Clone)]
+enum Change {
+    Set {
+        entity: Entity,
+        type_name: String,
+        value: Vec<u8>,
+    },
+    Remove {
+        entity: Entity,
+        type_name: String,
+    },
+    Despawn {
+        entity: Entity,
+    },
+}
+
+/// Component values shared between mods, keyed by component type name and
+/// entity. Values are `types::SerdeType` encoded and opaque to the host.
+///
+/// Every component type is owned by one mod, the first one to claim or write
+/// it, and only the owner may change it. Changes are staged and applied by
+/// `commit` at the end of the frame, so during a frame every mod reads the
+/// same state no matter the order they run in.
+#[derive(Debug, Default)]
+pub struct WorldStore {
+    components: BTreeMap<String, BTreeMap<Entity, Vec<u8>>>,
+    /// Owning mod id by component type name.
+    owners: BTreeMap<String, String>,
+    /// Changes with the mod that made them, in the order they were made.
+    staged: Vec<(String, Change)>,
+}
+
+impl WorldStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `mod_id` the owner of `type_name` unless another mod owns it.
+    pub fn claim(&mut self, mod_id: &str, type_name: &str) -> Result<(), String> {
+        match self.owners.get(type_name) {
+            Some(owner) if owner != mod_id => {
+                Err(format!("Component {} is owned by {}", type_name, owner))
+            }
+            Some(_) => Ok(()),
+            None => {
+                debug!("{} claimed component {}", mod_id, type_name);
+                self.owners
+                    .insert(type_name.to_string(), mod_id.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn owner(&self, type_name: &str) -> Option<&str> {
+        self.owners.get(type_name).map(String::as_str)
+    }
+
+    /// Stages a new value for a component.
+    pub fn set(
+        &mut self,
+        mod_id: &str,
+        entity: Entity,
+        type_name: &str,
+        value: Vec<u8>,
+    ) -> Result<(), String> {
+        self.claim(mod_id, type_name)?;
+        self.staged.push((
+            mod_id.to_string(),
+            Change::Set {
+                entity,
+                type_name: type_name.to_string(),
+                value,
+            },
+        ));
+        Ok(())
+    }
+
+    /// Stages the removal of a component.
+    pub fn remove(&mut self, mod_id: &str, entity: Entity, type_name: &str) -> Result<(), String> {
+        self.claim(mod_id, type_name)?;
+        self.staged.push((
+            mod_id.to_string(),
+            Change::Remove {
+                entity,
+                type_name: type_name.to_string(),
+            },
+        ));
+        Ok(())
+    }
+
+    /// Value of a component as of the last commit.
+    pub fn get(&self, entity: Entity, type_name: &str) -> Option<&[u8]> {
+        self.components
+            .get(type_name)?
+            .get(&entity)
+            .map(Vec::as_slice)
+    }
+
+    /// Decoded value of a component. `None` if it is missing or doesn't
+    /// decode as `T`.
+    pub fn get_as<T: SerdeType>(&self, entity: Entity, type_name: &str) -> Option<T> {
+        T::de(self.get(entity, type_name)?).ok()
+    }
+
+    /// Stages the removal of every component of `entity`, whoever owns
+    /// their types. Only for the ECS, which checks who may despawn.
+    pub(crate) fn despawn(&mut self, mod_id: &str, entity: Entity) {
+        self.staged
+            .push((mod_id.to_string(), Change::Despawn { entity }));
+    }
+
+    /// Entities that have a `type_name` component, in ascending order.
+    pub fn entities_with(&self, type_name: &str) -> Vec<Entity> {
+        self.components
+            .get(type_name)
+            .map(|values| values.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Applies the staged changes in the order they were made. Changes to
+    /// an entity staged after it was despawned are dropped, they would bring
+    /// back its components.
+    pub fn commit(&mut self) {
+        let mut despawned = BTreeSet::new();
+        for (mod_id, change) in self.staged.drain(..) {
+            match change {
+                Change::Set { entity, .. } | Change::Remove { entity, .. }
+                    if despawned.contains(&entity) =>
+                {
+                    trace!("{} changed {} after it was despawned", mod_id, entity);
+                }
+                Change::Set {
+                    entity,
+                    type_name,
+                    value,
+                } => {
+                    trace!("{} set {} of {}", mod_id, type_name, entity);
+                    self.components
+                        .entry(type_name)
+                        .or_default()
+                        .insert(entity, value);
+                }
+                Change::Remove { entity, type_name } => {
+                    trace!("{} removed {} of {}", mod_id, type_name, entity);
+                    if let Some(values) = self.components.get_mut(&type_name) {
+                        values.remove(&entity);
+                        if values.is_empty() {
+                            self.components.remove(&type_name);
+                        }
+                    }
+                }
+                Change::Despawn { entity } => {
+                    trace!("{} despawned {}", mod_id, entity);
+                    despawned.insert(entity);
+                    self.components.retain(|_, values| {
+                        values.remove(&entity);
+                        !values.is_empty()
+                    });
+                }
+            }
+        }
+    }
+
+    /// Gives up the component types of an unloaded mod and drops its staged
+    /// changes. Committed values stay readable.
+    pub fn release_mod(&mut self, mod_id: &str) {
+        self.owners.retain(|_, owner| owner != mod_id);
+        self.staged.retain(|(changed_by, _)| changed_by != mod_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_owner_changes_a_component() {
+        let mut world = WorldStore::new();
+        world.claim("a", "Position").unwrap();
+        world.claim("a", "Position").unwrap();
+        assert!(world.claim("b", "Position").is_err());
+        assert!(world.set("b", 1, "Position", vec![1]).is_err());
+        assert!(world.remove("b", 1, "Position").is_err());
+        assert_eq!(world.owner("Position"), Some("a"));
+
+        // Writing claims an unowned type.
+        world.set("b", 1, "Health", vec![1]).unwrap();
+        assert!(world.claim("a", "Health").is_err());
+        world.commit();
+        assert_eq!(world.get(1, "Position"), None);
+        assert_eq!(world.get(1, "Health"), Some(&[1][..]));
+
+        world.release_mod("a");
+        world.claim("b", "Position").unwrap();
+    }
+
+    #[test]
+    fn staged_writes_show_after_commit() {
+        let mut world = WorldStore::new();
+        world.set("a", 1, "Position", vec![1]).unwrap();
+        assert_eq!(world.get(1, "Position"), None);
+        assert!(world.entities_with("Position").is_empty());
+        world.commit();
+        assert_eq!(world.get(1, "Position"), Some(&[1][..]));
+
+        world.set("a", 1, "Position", vec![2]).unwrap();
+        world.set("a", 2, "Position", vec![3]).unwrap();
+        world.remove("a", 1, "Position").unwrap();
+        assert_eq!(world.get(1, "Position"), Some(&[1][..]));
+        assert_eq!(world.entities_with("Position"), [1]);
+        world.commit();
+        assert_eq!(world.get(1, "Position"), None);
+        assert_eq!(world.entities_with("Position"), [2]);
+
+        // Changes of an unloaded mod that weren't committed are dropped.
+        world.set("a", 2, "Position", vec![4]).unwrap();
+        world.release_mod("a");
+        world.commit();
+        assert_eq!(world.get(2, "Position"), Some(&[3][..]));
+    }
+
+    #[test]
+    fn changes_after_despawn_are_dropped() {
+        let mut world = WorldStore::new();
+        world.set("a", 1, "Position", vec![1]).unwrap();
+        world.set("a", 2, "Position", vec![2]).unwrap();
+        world.commit();
+
+        world.despawn("a", 1);
+        world.set("a", 1, "Position", vec![3]).unwrap();
+        world.set("a", 2, "Position", vec![4]).unwrap();
+        world.commit();
+
+        assert_eq!(world.get(1, "Position"), None);
+        assert_eq!(world.get(2, "Position"), Some(&[4][..]));
+    }
+}
@@ -0,0 +1,99 @@
+//! Sounds and the voices playing them.
+
+use crate::{bindings::module::guest::audio as raw, Error, Result};
+use std::time::Duration;
+
+pub use raw::Waveform;
+
+/// Loaded or generated sound, freed when dropped. Voices playing it keep
+/// playing.
+#[derive(Debug)]
+pub struct Sound {
+    handle: u32,
+}
+
+impl Sound {
+    /// Loads a WAV file from the mod's assets.
+    pub fn load(path: &str) -> Result<Self> {
+        let handle = raw::load_sound(path).map_err(Error::from)?;
+        Ok(Self { handle })
+    }
+
+    /// Fails if the mod holds too many sounds, like `load` and `noise`.
+    pub fn tone(waveform: Waveform, frequency: f32, duration: Duration) -> Result<Self> {
+        let handle = raw::tone(waveform, frequency, duration.as_secs_f32()).map_err(Error::from)?;
+        Ok(Self { handle })
+    }
+
+    /// White noise, the same `seed` gives the same noise.
+    pub fn noise(duration: Duration, seed: u32) -> Result<Self> {
+        let handle = raw::noise(duration.as_secs_f32(), seed).map_err(Error::from)?;
+        Ok(Self { handle })
+    }
+
+    /// Starts building a voice playing the sound, see `Play::start`.
+    pub fn play(&self) -> Play<'_> {
+        Play {
+            sound: self,
+            volume: 1.0,
+            pan: 0.0,
+            looping: false,
+        }
+    }
+}
+
+impl Drop for Sound {
+    fn drop(&mut self) {
+        raw::free_sound(self.handle);
+    }
+}
+
+#[must_use = "nothing plays until `start` is called"]
+#[derive(Debug)]
+pub struct Play<'a> {
+    sound: &'a Sound,
+    volume: f32,
+    pan: f32,
+    looping: bool,
+}
+
+impl Play<'_> {
+    /// Gain, `0.0..=1.0`.
+    pub fn volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    /// `-1.0` is left, `1.0` right.
+    pub fn pan(mut self, pan: f32) -> Self {
+        self.pan = pan;
+        self
+    }
+
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn start(self) -> Result<Voice> {
+        let handle = raw::play(self.sound.handle, self.volume, self.pan, self.looping)
+            .map_err(Error::from)?;
+        Ok(Voice { handle })
+    }
+}
+
+/// A sound playing. Dropping it doesn't stop it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Voice {
+    handle: u32,
+}
+
+impl Voice {
+    pub fn stop(self) {
+        raw::stop(self.handle);
+    }
+
+    pub fn is_playing(self) -> bool {
+        raw::is_playing(self.handle)
+    }
+}
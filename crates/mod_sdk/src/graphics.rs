@@ -0,0 +1,200 @@
+//! Drawing. Shapes are built up and drawn with `draw`:
+//!
+//! ```ignore
+//! graphics::rect(Rect::new(position, size))
+//!     .color(Color::RED)
+//!     .layer(Layer::Ui)
+//!     .draw();
+//! ```
+//!
+//! Options a shape doesn't set keep what the last draw call of the mod set
+//! this frame, like the raw calls do.
+
+use crate::{bindings::module::guest::graphics as raw, Color, Error, Rect, Result, Vec2};
+
+pub use raw::{BlendMode, Camera, Layer};
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Style {
+    color: Option<Color>,
+    layer: Option<Layer>,
+    z: Option<u32>,
+    blend_mode: Option<BlendMode>,
+}
+
+impl Style {
+    fn apply(&self) {
+        if let Some(color) = self.color {
+            raw::color(color.r, color.g, color.b, color.a);
+        }
+        if let Some(layer) = self.layer {
+            raw::set_layer(layer);
+        }
+        if let Some(z) = self.z {
+            raw::set_z(z);
+        }
+        if let Some(blend_mode) = self.blend_mode {
+            raw::set_blend_mode(blend_mode);
+        }
+    }
+}
+
+macro_rules! style_methods {
+    () => {
+        pub fn color(mut self, color: Color) -> Self {
+            self.style.color = Some(color);
+            self
+        }
+
+        pub fn layer(mut self, layer: Layer) -> Self {
+            self.style.layer = Some(layer);
+            self
+        }
+
+        /// Order within the layer, higher is drawn later.
+        pub fn z(mut self, z: u32) -> Self {
+            self.style.z = Some(z);
+            self
+        }
+
+        pub fn blend_mode(mut self, blend_mode: BlendMode) -> Self {
+            self.style.blend_mode = Some(blend_mode);
+            self
+        }
+    };
+}
+
+#[must_use = "nothing is drawn until `draw` is called"]
+#[derive(Debug, Clone, Copy)]
+pub struct RectShape {
+    rect: Rect,
+    style: Style,
+}
+
+pub fn rect(rect: Rect) -> RectShape {
+    RectShape {
+        rect,
+        style: Style::default(),
+    }
+}
+
+impl RectShape {
+    style_methods!();
+
+    pub fn draw(self) {
+        self.style.apply();
+        let Rect { position, size } = self.rect;
+        raw::draw_rect(position.x, position.y, size.x, size.y);
+    }
+}
+
+#[must_use = "nothing is drawn until `draw` is called"]
+#[derive(Debug, Clone, Copy)]
+pub struct LineShape {
+    from: Vec2,
+    to: Vec2,
+    width: Option<f32>,
+    style: Style,
+}
+
+pub fn line(from: Vec2, to: Vec2) -> LineShape {
+    LineShape {
+        from,
+        to,
+        width: None,
+        style: Style::default(),
+    }
+}
+
+impl LineShape {
+    style_methods!();
+
+    pub fn width(mut self, width: f32) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    pub fn draw(self) {
+        self.style.apply();
+        if let Some(width) = self.width {
+            raw::set_line_width(width);
+        }
+        raw::draw_line(self.from.x, self.from.y, self.to.x, self.to.y);
+    }
+}
+
+/// Runs `f` with its own transform, the transforms it applies are undone
+/// afterwards.
+pub fn with_transform<R>(f: impl FnOnce() -> R) -> R {
+    raw::push_transform();
+    let result = f();
+    raw::pop_transform();
+    result
+}
+
+pub fn translate(by: Vec2) {
+    raw::translate(by.x, by.y);
+}
+
+pub fn rotate(radians: f32) {
+    raw::rotate(radians);
+}
+
+pub fn scale(by: Vec2) {
+    raw::scale(by.x, by.y);
+}
+
+pub fn set_camera(camera: Camera) {
+    raw::set_camera(camera);
+}
+
+pub fn camera() -> Camera {
+    raw::get_camera()
+}
+
+pub fn world_to_screen(point: Vec2) -> Vec2 {
+    raw::world_to_screen(point.x, point.y).into()
+}
+
+pub fn screen_to_world(point: Vec2) -> Vec2 {
+    raw::screen_to_world(point.x, point.y).into()
+}
+
+/// Offscreen image to draw into, destroyed when dropped.
+#[derive(Debug)]
+pub struct RenderTarget {
+    handle: u32,
+}
+
+impl RenderTarget {
+    /// Fails if the size is 0 or above 4096 in either direction.
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        let handle = raw::create_render_target(width, height).map_err(Error::from)?;
+        Ok(Self { handle })
+    }
+
+    /// Draws what `f` draws into the target instead of the screen.
+    pub fn draw_into<R>(&self, f: impl FnOnce() -> R) -> R {
+        raw::begin_target(self.handle);
+        let result = f();
+        raw::end_target();
+        result
+    }
+
+    /// Draws the target stretched over `rect`.
+    pub fn draw(&self, rect: Rect) {
+        raw::draw_target(
+            self.handle,
+            rect.position.x,
+            rect.position.y,
+            rect.size.x,
+            rect.size.y,
+        );
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        raw::destroy_render_target(self.handle);
+    }
+}
@@ -0,0 +1,64 @@
+//! Keyboard, mouse, gamepads and the actions bound to them. Key and button
+//! states are per simulation step, read them in `Mod::update`.
+
+use crate::{
+    bindings::module::guest::{actions as raw_actions, input as raw},
+    Vec2,
+};
+
+pub use raw::{
+    gamepad_axis_value, gamepad_button_down, gamepad_button_pressed, gamepad_button_released,
+    gamepad_name, gamepad_rumble, gamepads, key_down, key_pressed, key_released, mouse_button_down,
+    mouse_button_pressed, mouse_button_released, text_input, GamepadAxis, GamepadButton, Key,
+    MouseButton,
+};
+pub use raw_actions::{
+    action_down, action_pressed, action_released, action_value, list_actions, rebind_action,
+    reset_action, ActionInfo, Binding, InputSource,
+};
+
+pub fn window_size() -> Vec2 {
+    raw::get_window_size().into()
+}
+
+pub fn mouse_position() -> Vec2 {
+    raw::mouse_position().into()
+}
+
+/// Wheel movement since the last step.
+pub fn mouse_wheel() -> Vec2 {
+    raw::mouse_wheel().into()
+}
+
+/// Registers an action with its default bindings; players may rebind it.
+pub fn register_action(name: &str, bindings: &[Binding]) {
+    raw_actions::register_action(name, bindings);
+}
+
+impl Binding {
+    pub fn key(keycode: &str) -> Self {
+        Self::new(InputSource::Key(Key::Keycode(keycode.to_string())))
+    }
+
+    pub fn mouse_button(button: MouseButton) -> Self {
+        Self::new(InputSource::MouseButton(button))
+    }
+
+    pub fn gamepad_button(button: GamepadButton) -> Self {
+        Self::new(InputSource::GamepadButton(button))
+    }
+
+    pub fn gamepad_axis(axis: GamepadAxis) -> Self {
+        Self::new(InputSource::GamepadAxis(axis))
+    }
+
+    fn new(source: InputSource) -> Self {
+        Self { source, scale: 1.0 }
+    }
+
+    /// Scales the value the binding contributes, e.g. `-1.0` to flip an
+    /// axis.
+    pub fn scale(self, scale: f32) -> Self {
+        Self { scale, ..self }
+    }
+}
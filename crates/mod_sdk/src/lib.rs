@@ -0,0 +1,237 @@
+//! Idiomatic wrappers around the host API for writing mods in Rust.
+//!
+//! A mod is a type implementing [`Mod`], exported with [`export_mod`]:
+//!
+//! ```ignore
+//! use mod_sdk::prelude::*;
+//!
+//! #[mod_sdk::export_mod("../../wit/module.wit")]
+//! pub struct MyMod {
+//!     position: Vec2,
+//! }
+//!
+//! impl Mod for MyMod {
+//!     fn new() -> Self {
+//!         MyMod { position: Vec2::ZERO }
+//!     }
+//!
+//!     fn draw(&mut self) {
+//!         graphics::rect(Rect::new(self.position, Vec2::new(80.0, 60.0)))
+//!             .color(Color::WHITE)
+//!             .draw();
+//!     }
+//! }
+//! ```
+//!
+//! The mod is named after its package unless the attribute gives a name, as
+//! in `export_mod("../../wit/module.wit", name = "My Mod")`.
+//!
+//! The bindings for the exports are generated where the attribute is, so it
+//! has to be at the crate root and the mod needs `wit-bindgen` as a
+//! dependency. The raw bindings of everything the host provides are in
+//! [`bindings`] for what the wrappers don't cover.
+
+mod error;
+mod math;
+
+pub mod audio;
+pub mod graphics;
+pub mod input;
+pub mod messaging;
+pub mod random;
+pub mod settings;
+pub mod storage;
+pub mod time;
+pub mod world;
+
+pub use error::{Error, Result};
+pub use math::{Color, Rect, Vec2};
+pub use mod_macros::export_mod;
+
+pub mod bindings {
+    wit_bindgen::generate!({
+        path: "../../wit/module.wit",
+        world: "sdk",
+    });
+}
+
+/// Events mods can subscribe to, delivered to `Mod::on_event`.
+pub mod events {
+    pub use crate::bindings::module::guest::events::{
+        subscribe, unsubscribe, Event, EventKind, HotplugEvent, KeyEvent, MouseEvent,
+    };
+}
+
+pub use bindings::module::guest::utils::{fatal, log};
+
+use std::time::Duration;
+
+/// A mod. The host creates it once when the mod is loaded and calls these
+/// as the game runs; everything has a default that does nothing.
+pub trait Mod: Sized + 'static {
+    fn new() -> Self;
+
+    fn init(&mut self) {}
+
+    /// One simulation step of `delta`.
+    fn update(&mut self, _delta: Duration) {}
+
+    fn draw(&mut self) {}
+
+    /// Handles an event the mod subscribed to. Returns whether the mod
+    /// consumed it, which stops it from reaching later mods.
+    fn on_event(&mut self, _event: &events::Event) -> bool {
+        false
+    }
+
+    /// Answers a call another mod made to a service this mod provides.
+    fn handle_call(&mut self, service: &str, method: &str, _request: &[u8]) -> Result<Vec<u8>> {
+        Err(Error::new(format!(
+            "Unknown method {} of {}",
+            method, service
+        )))
+    }
+
+    /// Runs one of the systems the mod registered.
+    fn run_system(&mut self, _name: &str) {}
+
+    /// Section of the mod for a save slot, `None` if it has nothing to save.
+    fn save_section(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Restores the mod from its section of a save slot.
+    fn load_section(&mut self, _data: &[u8]) {}
+
+    /// Converts a save section written by another version of the mod. The
+    /// default keeps it as it is.
+    fn migrate_section(&mut self, _from_version: &str, data: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(data)
+    }
+
+    fn shutdown(&mut self) {}
+}
+
+/// Everything a mod usually needs.
+pub mod prelude {
+    pub use crate::{
+        audio, events,
+        events::{Event, EventKind},
+        graphics, input, log, messaging, random, settings, storage, time, world, Color, Error, Mod,
+        Rect, Result, Vec2,
+    };
+}
+
+/// Used by the code `export_mod` generates.
+#[doc(hidden)]
+pub mod __private {
+    use crate::{events::Event, Mod};
+    use std::{cell::RefCell, time::Duration};
+
+    /// The exported resource. Every call borrows the mod mutably, so calling
+    /// back into a mod that is already running would panic. The host rules
+    /// that out by locking each mod for the whole call: `call-service` uses
+    /// `try_lock` and returns an error instead of reentering a busy mod.
+    pub struct Instance<M: Mod>(RefCell<M>);
+
+    impl<M: Mod> Instance<M> {
+        #[allow(clippy::new_without_default)]
+        pub fn new() -> Self {
+            Self(RefCell::new(M::new()))
+        }
+
+        pub fn init(&self) {
+            self.0.borrow_mut().init()
+        }
+
+        /// `delta` comes in milliseconds.
+        pub fn update(&self, delta: f32) {
+            self.0
+                .borrow_mut()
+                .update(Duration::from_secs_f32(delta.max(0.0) / 1000.0))
+        }
+
+        pub fn draw(&self) {
+            self.0.borrow_mut().draw()
+        }
+
+        pub fn on_event(&self, event: Event) -> bool {
+            self.0.borrow_mut().on_event(&event)
+        }
+
+        pub fn handle_call(
+            &self,
+            service: String,
+            method: String,
+            request: Vec<u8>,
+        ) -> Result<Vec<u8>, String> {
+            self.0
+                .borrow_mut()
+                .handle_call(&service, &method, &request)
+                .map_err(|e| e.to_string())
+        }
+
+        pub fn run_system(&self, name: String) {
+            self.0.borrow_mut().run_system(&name)
+        }
+
+        pub fn save_section(&self) -> Option<Vec<u8>> {
+            self.0.borrow_mut().save_section()
+        }
+
+        pub fn load_section(&self, data: Vec<u8>) {
+            self.0.borrow_mut().load_section(&data)
+        }
+
+        pub fn migrate_section(
+            &self,
+            from_version: String,
+            data: Vec<u8>,
+        ) -> Result<Vec<u8>, String> {
+            self.0
+                .borrow_mut()
+                .migrate_section(&from_version, data)
+                .map_err(|e| e.to_string())
+        }
+
+        pub fn shutdown(&self) {
+            self.0.borrow_mut().shutdown()
+        }
+    }
+
+    /// Mod info in the order the host expects: id, name, version, author,
+    /// description. `authors` is Cargo's colon separated list.
+    pub fn info(
+        id: &str,
+        name: &str,
+        version: &str,
+        authors: &str,
+        description: &str,
+    ) -> Vec<String> {
+        let author = authors.split(':').collect::<Vec<&str>>().join(", ");
+        vec![
+            id.to_string(),
+            name.to_string(),
+            version.to_string(),
+            author,
+            description.to_string(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::__private::info;
+
+    #[test]
+    fn authors_are_joined_with_commas() {
+        let info = info("id", "Name", "0.1.0", "Ann <a@b.c>:Bob", "Desc");
+        assert_eq!(info, ["id", "Name", "0.1.0", "Ann <a@b.c>, Bob", "Desc"]);
+    }
+
+    #[test]
+    fn single_and_missing_authors_stay_as_they_are() {
+        assert_eq!(info("id", "Name", "0.1.0", "Ann", "")[3], "Ann");
+        assert_eq!(info("id", "Name", "0.1.0", "", "")[3], "");
+    }
+}
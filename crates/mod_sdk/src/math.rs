@@ -0,0 +1,261 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+/// Point or offset in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2::new(0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn length(self) -> f32 {
+        self.x.hypot(self.y)
+    }
+
+    /// Same direction with length 1, zero stays zero.
+    pub fn normalized(self) -> Self {
+        let length = self.length();
+        if length == 0.0 {
+            self
+        } else {
+            self / length
+        }
+    }
+
+    /// `t` of the way from `self` to `other`.
+    pub fn lerp(self, other: Vec2, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl From<(f32, f32)> for Vec2 {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Vec2> for (f32, f32) {
+    fn from(vec: Vec2) -> Self {
+        (vec.x, vec.y)
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Vec2) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, other: Vec2) {
+        *self = *self - other;
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, factor: f32) -> Vec2 {
+        Vec2::new(self.x * factor, self.y * factor)
+    }
+}
+
+impl Div<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn div(self, divisor: f32) -> Vec2 {
+        Vec2::new(self.x / divisor, self.y / divisor)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+/// Axis-aligned rectangle from its top left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+impl Rect {
+    pub const fn new(position: Vec2, size: Vec2) -> Self {
+        Self { position, size }
+    }
+
+    pub fn from_center(center: Vec2, size: Vec2) -> Self {
+        Self::new(center - size / 2.0, size)
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.position + self.size / 2.0
+    }
+
+    /// Bottom right corner.
+    pub fn end(&self) -> Vec2 {
+        self.position + self.size
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        let end = self.end();
+        point.x >= self.position.x
+            && point.y >= self.position.y
+            && point.x < end.x
+            && point.y < end.y
+    }
+
+    pub fn offset(self, by: Vec2) -> Self {
+        Self::new(self.position + by, self.size)
+    }
+}
+
+/// Color with components in `0.0..=1.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Color = Color::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Color = Color::rgb(1.0, 1.0, 1.0);
+    pub const RED: Color = Color::rgb(1.0, 0.0, 0.0);
+    pub const GREEN: Color = Color::rgb(0.0, 1.0, 0.0);
+    pub const BLUE: Color = Color::rgb(0.0, 0.0, 1.0);
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self::rgba(r, g, b, 1.0)
+    }
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::rgba(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            a as f32 / 255.0,
+        )
+    }
+
+    pub fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+
+    /// `t` of the way from `self` to `other`.
+    pub fn lerp(self, other: Color, t: f32) -> Self {
+        Self::rgba(
+            self.r + (other.r - self.r) * t,
+            self.g + (other.g - self.g) * t,
+            self.b + (other.b - self.b) * t,
+            self.a + (other.a - self.a) * t,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vec2_arithmetic() {
+        let mut a = Vec2::new(1.0, 2.0);
+        let b = Vec2::new(3.0, -1.0);
+        assert_eq!(a + b, Vec2::new(4.0, 1.0));
+        assert_eq!(a - b, Vec2::new(-2.0, 3.0));
+        assert_eq!(a * 2.0, Vec2::new(2.0, 4.0));
+        assert_eq!(a / 2.0, Vec2::new(0.5, 1.0));
+        assert_eq!(-a, Vec2::new(-1.0, -2.0));
+        a += b;
+        assert_eq!(a, Vec2::new(4.0, 1.0));
+        a -= b;
+        assert_eq!(a, Vec2::new(1.0, 2.0));
+        assert_eq!(<(f32, f32)>::from(a), (1.0, 2.0));
+        assert_eq!(Vec2::from((1.0, 2.0)), a);
+    }
+
+    #[test]
+    fn vec2_length_and_normalized() {
+        assert_eq!(Vec2::new(3.0, 4.0).length(), 5.0);
+        assert_eq!(Vec2::new(3.0, 4.0).normalized(), Vec2::new(0.6, 0.8));
+        assert_eq!(Vec2::ZERO.normalized(), Vec2::ZERO);
+    }
+
+    #[test]
+    fn vec2_lerp() {
+        let a = Vec2::new(0.0, 10.0);
+        let b = Vec2::new(10.0, 20.0);
+        assert_eq!(a.lerp(b, 0.0), a);
+        assert_eq!(a.lerp(b, 1.0), b);
+        assert_eq!(a.lerp(b, 0.5), Vec2::new(5.0, 15.0));
+    }
+
+    #[test]
+    fn rect_center_and_end() {
+        let rect = Rect::from_center(Vec2::new(5.0, 5.0), Vec2::new(4.0, 2.0));
+        assert_eq!(rect.position, Vec2::new(3.0, 4.0));
+        assert_eq!(rect.center(), Vec2::new(5.0, 5.0));
+        assert_eq!(rect.end(), Vec2::new(7.0, 6.0));
+        assert_eq!(
+            rect.offset(Vec2::new(1.0, -1.0)).position,
+            Vec2::new(4.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn rect_contains_is_half_open() {
+        let rect = Rect::new(Vec2::new(0.0, 0.0), Vec2::new(10.0, 10.0));
+        assert!(rect.contains(Vec2::new(0.0, 0.0)));
+        assert!(rect.contains(Vec2::new(9.9, 9.9)));
+        assert!(!rect.contains(Vec2::new(10.0, 5.0)));
+        assert!(!rect.contains(Vec2::new(5.0, 10.0)));
+        assert!(!rect.contains(Vec2::new(-0.1, 5.0)));
+    }
+
+    #[test]
+    fn color_constructors_and_lerp() {
+        assert_eq!(Color::rgb(0.1, 0.2, 0.3).a, 1.0);
+        assert_eq!(
+            Color::from_rgba8(255, 0, 255, 0),
+            Color::rgba(1.0, 0.0, 1.0, 0.0)
+        );
+        assert_eq!(Color::RED.with_alpha(0.5), Color::rgba(1.0, 0.0, 0.0, 0.5));
+        assert_eq!(
+            Color::BLACK.lerp(Color::WHITE, 0.5),
+            Color::rgb(0.5, 0.5, 0.5)
+        );
+        assert_eq!(Color::TRANSPARENT.lerp(Color::BLUE, 1.0), Color::BLUE);
+    }
+}
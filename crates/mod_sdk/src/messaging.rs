@@ -0,0 +1,23 @@
+//! Messages between mods: topics anyone can publish to, and services one
+//! mod provides and others call.
+
+use crate::{
+    bindings::module::guest::{bus as raw_bus, services as raw_services},
+    Error, Result,
+};
+
+pub use raw_bus::{publish, subscribe_topic, unsubscribe_topic, Message};
+
+/// Messages on the subscribed topics that came in since the last call.
+pub fn messages() -> impl Iterator<Item = Message> {
+    std::iter::from_fn(raw_bus::poll_message)
+}
+
+/// Provides a service, calls to it go to `Mod::handle_call`.
+pub fn provide_service(name: &str) -> Result<()> {
+    raw_services::provide_service(name).map_err(Error::from)
+}
+
+pub fn call_service(service: &str, method: &str, request: &[u8]) -> Result<Vec<u8>> {
+    raw_services::call_service(service, method, request).map_err(Error::from)
+}
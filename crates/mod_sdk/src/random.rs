@@ -0,0 +1,24 @@
+//! Random numbers from the mod's own stream. The host seeds it, so a run
+//! draws the same numbers when replayed or loaded from a save.
+
+use crate::bindings::module::guest::random as raw;
+use std::ops::Range;
+
+pub fn u32() -> u32 {
+    raw::random_u32()
+}
+
+/// Uniform in `0.0..1.0`.
+pub fn f32() -> f32 {
+    raw::random_f32()
+}
+
+/// Uniform in `range`, its start if it is empty.
+pub fn range(range: Range<i32>) -> i32 {
+    raw::random_range(range.start, range.end)
+}
+
+/// `true` with probability `p`.
+pub fn chance(p: f32) -> bool {
+    f32() < p
+}
@@ -0,0 +1,41 @@
+//! The mod's settings, declared in its `mod.toml`.
+
+use crate::{bindings::module::guest::settings as raw, Error, Result};
+
+pub use raw::{list_settings as list, SettingInfo, SettingValue};
+
+pub fn get(name: &str) -> Option<SettingValue> {
+    raw::get_setting(name)
+}
+
+pub fn get_bool(name: &str) -> Option<bool> {
+    match get(name)? {
+        SettingValue::Boolean(value) => Some(value),
+        _ => None,
+    }
+}
+
+pub fn get_integer(name: &str) -> Option<i64> {
+    match get(name)? {
+        SettingValue::Integer(value) => Some(value),
+        _ => None,
+    }
+}
+
+pub fn get_float(name: &str) -> Option<f64> {
+    match get(name)? {
+        SettingValue::Float(value) => Some(value),
+        _ => None,
+    }
+}
+
+pub fn get_text(name: &str) -> Option<String> {
+    match get(name)? {
+        SettingValue::Text(value) => Some(value),
+        _ => None,
+    }
+}
+
+pub fn set(name: &str, value: &SettingValue) -> Result<()> {
+    raw::set_setting(name, value).map_err(Error::from)
+}
@@ -0,0 +1,45 @@
+//! Saved data, the mod's files and resources.
+
+use crate::{
+    bindings::module::guest::{fs as raw_fs, resources as raw_resources, saves as raw_saves},
+    Error, Result,
+};
+
+pub use raw_fs::file_exists;
+pub use raw_resources::{resource_conflicts, resource_provider, ResourceConflict};
+pub use raw_saves::{list_keys, list_slots, load_slot, save_slot, Saved};
+
+/// Saves `data` under `key`, tagged with the mod's version.
+pub fn save(key: &str, data: &[u8]) -> Result<()> {
+    raw_saves::save(key, data).map_err(Error::from)
+}
+
+pub fn load(key: &str) -> Result<Option<Saved>> {
+    raw_saves::load(key).map_err(Error::from)
+}
+
+pub fn delete(key: &str) -> Result<()> {
+    raw_saves::delete(key).map_err(Error::from)
+}
+
+/// Reads a file from the mod's data directory or its assets.
+pub fn read_file(path: &str) -> Result<Vec<u8>> {
+    raw_fs::read_file(path).map_err(Error::from)
+}
+
+pub fn write_file(path: &str, data: &[u8]) -> Result<()> {
+    raw_fs::write_file(path, data).map_err(Error::from)
+}
+
+pub fn remove_file(path: &str) -> Result<()> {
+    raw_fs::remove_file(path).map_err(Error::from)
+}
+
+pub fn list_dir(path: &str) -> Result<Vec<String>> {
+    raw_fs::list_dir(path).map_err(Error::from)
+}
+
+/// Reads a resource from the topmost layer that has it.
+pub fn read_resource(path: &str) -> Result<Vec<u8>> {
+    raw_resources::read_resource(path).map_err(Error::from)
+}
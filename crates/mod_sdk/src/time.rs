@@ -0,0 +1,54 @@
+//! Game time and timers. Game time advances with the simulation steps, it
+//! stands still while the game is paused.
+
+use crate::bindings::module::guest::time as raw;
+use std::time::Duration;
+
+pub fn game_time() -> Duration {
+    Duration::from_secs_f64(raw::now().game_ms.max(0.0) / 1000.0)
+}
+
+/// Wall clock time since the game started. Not reproduced by replays.
+pub fn real_time() -> Duration {
+    Duration::from_secs_f64(raw::now().real_ms.max(0.0) / 1000.0)
+}
+
+/// How far drawing is between the last step and the next, in `0.0..1.0`.
+pub fn alpha() -> f32 {
+    raw::alpha()
+}
+
+/// A pending timer. It fires as `Event::Timer` with its id; dropping it
+/// doesn't cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Timer(u64);
+
+impl Timer {
+    /// Fires once after `delay` of game time.
+    pub fn timeout(delay: Duration) -> Self {
+        Self(raw::set_timeout(millis(delay)))
+    }
+
+    /// Fires every `interval` of game time until cancelled.
+    pub fn interval(interval: Duration) -> Self {
+        Self(raw::set_interval(millis(interval)))
+    }
+
+    pub fn id(self) -> u64 {
+        self.0
+    }
+
+    /// Whether `Event::Timer(id)` is this timer firing.
+    pub fn is(self, id: u64) -> bool {
+        self.0 == id
+    }
+
+    /// Returns whether the timer was still pending.
+    pub fn cancel(self) -> bool {
+        raw::cancel(self.0)
+    }
+}
+
+fn millis(duration: Duration) -> u32 {
+    duration.as_millis().min(u32::MAX as u128) as u32
+}
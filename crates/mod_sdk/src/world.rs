@@ -0,0 +1,65 @@
+//! Entities and their components, shared between all mods.
+
+use crate::{
+    bindings::module::guest::{ecs as raw_ecs, world as raw_world},
+    Error, Result,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity(pub u64);
+
+impl Entity {
+    pub fn spawn() -> Self {
+        Self(raw_ecs::spawn())
+    }
+
+    pub fn despawn(self) -> Result<()> {
+        raw_ecs::despawn(self.0).map_err(Error::from)
+    }
+
+    pub fn alive(self) -> bool {
+        raw_ecs::alive(self.0)
+    }
+
+    /// Serialized component of type `type_name`, if the entity has one.
+    pub fn component(self, type_name: &str) -> Option<Vec<u8>> {
+        raw_world::get_component(self.0, type_name)
+    }
+
+    pub fn set_component(self, type_name: &str, value: &[u8]) -> Result<()> {
+        raw_world::set_component(self.0, type_name, value).map_err(Error::from)
+    }
+
+    pub fn remove_component(self, type_name: &str) -> Result<()> {
+        raw_world::remove_component(self.0, type_name).map_err(Error::from)
+    }
+}
+
+/// Makes the mod the only one allowed to write components of `type_name`.
+pub fn claim_component(type_name: &str) -> Result<()> {
+    raw_world::claim_component(type_name).map_err(Error::from)
+}
+
+pub fn entities_with(type_name: &str) -> Vec<Entity> {
+    raw_world::entities_with(type_name)
+        .into_iter()
+        .map(Entity)
+        .collect()
+}
+
+/// Entities that have a component of every one of `type_names`. Fails
+/// inside a system that doesn't declare reading all of them.
+pub fn query(type_names: &[&str]) -> Result<Vec<Entity>> {
+    let entities = raw_ecs::query(&strings(type_names)).map_err(Error::from)?;
+    Ok(entities.into_iter().map(Entity).collect())
+}
+
+/// Registers a system, run through `Mod::run_system` every step. Systems
+/// that don't write what another reads or writes may run in the same stage.
+pub fn register_system(name: &str, reads: &[&str], writes: &[&str]) -> Result<()> {
+    raw_ecs::register_system(name, &strings(reads), &strings(writes)).map_err(Error::from)
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
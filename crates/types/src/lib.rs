@@ -1,12 +1,84 @@
-#[macro_use]
-mod macros;
+// Lets the derive name the crate as `::types` from inside it too.
+extern crate self as types;
+
 mod traits;
-pub use traits::SerdeType;
+pub use mod_macros::SerdeType;
+pub use traits::{DecodeError, EncodeError, SerdeType};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, SerdeType)]
+#[serde_type(wit)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+    pub z: u32,
+}
+
+/// Used by the code the derive generates.
+#[doc(hidden)]
+pub mod __private {
+    pub use serde::{de::DeserializeOwned, Serialize};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::ser::Error as _;
+
+    #[test]
+    fn round_trip() {
+        let position = Position {
+            x: 1.5,
+            y: -2.0,
+            z: 3,
+        };
+        assert_eq!(Position::de(&position.se().unwrap()).unwrap(), position);
+    }
+
+    #[test]
+    fn bad_input_is_an_error() {
+        assert!(Position::de(&[]).is_err());
+        assert!(Position::de(&[0xc1]).is_err());
+        assert!(Position::de(&rmp_serde::to_vec("text").unwrap()).is_err());
+    }
+
+    #[test]
+    fn failing_serialize_is_an_error() {
+        #[derive(Deserialize)]
+        struct Unencodable;
+
+        impl Serialize for Unencodable {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(S::Error::custom("can't be encoded"))
+            }
+        }
+
+        impl SerdeType for Unencodable {}
+
+        assert!(Unencodable.se().is_err());
+    }
+
+    #[test]
+    fn generics_and_enums() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize, SerdeType)]
+        enum Shape<T> {
+            Circle(T),
+            Rect { w: T, h: T },
+            Empty,
+        }
+
+        for shape in [Shape::Circle(1), Shape::Rect { w: 2, h: 3 }, Shape::Empty] {
+            assert_eq!(Shape::de(&shape.se().unwrap()).unwrap(), shape);
+        }
+        assert_eq!(Shape::<u8>::WIT, None);
+    }
 
-new_type! {
-    struct Position {
-        x: f32,
-        y: f32,
-        z: u32,
+    #[test]
+    fn position_wit() {
+        assert_eq!(
+            Position::WIT,
+            Some("record position {\n    x: f32,\n    y: f32,\n    z: u32,\n}")
+        );
     }
 }
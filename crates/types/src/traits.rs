@@ -1,6 +1,21 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Serialize};
 
-pub trait SerdeType: Serialize + for<'a> Deserialize<'a> {
-    fn se(&self) -> Vec<u8>;
-    fn de(ser: Vec<u8>) -> Self;
+pub use rmp_serde::{decode::Error as DecodeError, encode::Error as EncodeError};
+
+/// A type mods exchange as MessagePack, through the bus, services, the world
+/// and save slots. Derive it with `#[derive(SerdeType)]`.
+pub trait SerdeType: Serialize + DeserializeOwned {
+    /// WIT definition of the type, emitted by the derive with
+    /// `#[serde_type(wit)]`.
+    const WIT: Option<&'static str> = None;
+
+    /// Fails if a `Serialize` impl of the value does, MessagePack itself
+    /// can encode anything serde describes.
+    fn se(&self) -> Result<Vec<u8>, EncodeError> {
+        rmp_serde::to_vec(self)
+    }
+
+    fn de(bytes: &[u8]) -> Result<Self, DecodeError> {
+        rmp_serde::from_slice(bytes)
+    }
 }
@@ -1,101 +1,294 @@
-mod_macros::create_mod!("../../wit/module.wit");
+use mod_sdk::{
+    audio::{Sound, Waveform},
+    input::{Binding, GamepadButton, MouseButton},
+    prelude::*,
+    time::Timer,
+    world::Entity,
+};
+use std::time::Duration;
+use types::{Position, SerdeType};
 
-use std::cell::RefCell;
+/// Speed of the rect, in pixels per second.
+const SPEED: f32 = 120.0;
 
-pub struct Main {
-    position: RefCell<(f32, f32)>,
-    reverse: RefCell<(bool, bool)>,
-    ghosts: RefCell<Vec<(f32, f32)>>,
-    since_last_ghost: RefCell<u32>,
-    size: (f32, f32),
+const SIZE: Vec2 = Vec2::new(80.0, 60.0);
+
+#[mod_sdk::export_mod("../../wit/module.wit", name = "Example Mod")]
+pub struct ExampleMod {
+    position: Vec2,
+    /// Position before the last step, drawing interpolates from there.
+    previous: Vec2,
+    reverse: (bool, bool),
+    ghosts: Vec<Vec2>,
+    ghost_timer: Option<Timer>,
+    /// Game time the last ghost was left behind at.
+    last_ghost: Duration,
+    marker: Option<Entity>,
+    blip: Option<Sound>,
     ghost_amount: u32,
     ghost_interval: u32,
 }
 
-impl GuestMain for Main {
+impl Mod for ExampleMod {
     fn new() -> Self {
-        Main {
-            position: RefCell::new((0.0, 0.0)),
-            reverse: RefCell::new((false, false)),
-            ghosts: RefCell::new(Vec::new()),
-            since_last_ghost: RefCell::new(0),
-            size: (80.0, 60.0),
+        ExampleMod {
+            position: Vec2::ZERO,
+            previous: Vec2::ZERO,
+            reverse: (false, false),
+            ghosts: Vec::new(),
+            ghost_timer: None,
+            last_ghost: Duration::ZERO,
+            marker: None,
+            blip: None,
             ghost_amount: 10,
-            ghost_interval: 5,
+            ghost_interval: 80,
+        }
+    }
+
+    fn init(&mut self) {
+        events::subscribe(EventKind::ModLoaded);
+        events::subscribe(EventKind::SettingsChanged);
+        self.read_settings();
+        self.start_ghost_timer();
+        if let Err(error) = messaging::provide_service("example_mod/position") {
+            log(error.message());
+        }
+
+        if let Ok(Some(saved)) = storage::load("position") {
+            self.restore(&saved.data);
+        }
+
+        match Sound::tone(Waveform::Square, 880.0, Duration::from_millis(50)) {
+            Ok(blip) => self.blip = Some(blip),
+            Err(error) => log(error.message()),
         }
+
+        // The host draws entities with a position, this one follows the
+        // rect's center.
+        self.marker = Some(Entity::spawn());
+        if let Err(error) = world::register_system("marker", &[], &["Position"]) {
+            log(error.message());
+        }
+
+        let steer = |keycode: &str, button: GamepadButton| {
+            [Binding::key(keycode), Binding::gamepad_button(button)]
+        };
+        input::register_action("left", &steer("Left", GamepadButton::DpadLeft));
+        input::register_action("right", &steer("Right", GamepadButton::DpadRight));
+        input::register_action("up", &steer("Up", GamepadButton::DpadUp));
+        input::register_action("down", &steer("Down", GamepadButton::DpadDown));
+        input::register_action("teleport", &[Binding::mouse_button(MouseButton::Left)]);
     }
 
-    fn init(&self) {}
-
-    fn update(&self, _: f32) {
-        let mut position = self.position.borrow_mut();
-        let mut reverse = self.reverse.borrow_mut();
-        let mut since_last_ghost = self.since_last_ghost.borrow_mut();
-        let ghost_amount = self.ghost_amount;
-        let ghost_interval = self.ghost_interval;
-        let size = self.size;
-        let window_size = get_window_size();
-
-        position.0 += if reverse.0 { -1.0 } else { 1.0 };
-        position.1 += if reverse.1 { -1.0 } else { 1.0 };
-
-        if position.0 > window_size.0 - size.0 {
-            reverse.0 = true;
-            position.0 = window_size.0 - size.0;
-        } else if position.0 <= 0.0 {
-            reverse.0 = false;
-            position.0 = 0.0;
-        }
-
-        if position.1 > window_size.1 - size.1 {
-            reverse.1 = true;
-            position.1 = window_size.1 - size.1;
-        } else if position.1 <= 0.0 {
-            reverse.1 = false;
-            position.1 = 0.0;
-        }
-
-        if *since_last_ghost >= ghost_interval {
-            let mut ghosts = self.ghosts.borrow_mut();
-            ghosts.push((position.0, position.1));
-            if ghosts.len() > ghost_amount as usize {
-                ghosts.remove(0);
+    fn update(&mut self, delta: Duration) {
+        self.previous = self.position;
+        let window_size = input::window_size();
+
+        // Arrow keys or the d-pad steer, a click moves the rect under the
+        // cursor.
+        if input::action_pressed("left") {
+            self.reverse.0 = true;
+        } else if input::action_pressed("right") {
+            self.reverse.0 = false;
+        }
+        if input::action_pressed("up") {
+            self.reverse.1 = true;
+        } else if input::action_pressed("down") {
+            self.reverse.1 = false;
+        }
+        if input::action_pressed("teleport") {
+            let mouse = input::mouse_position();
+            self.position = mouse - SIZE / 2.0;
+            self.previous = self.position;
+
+            // Lets other mods follow the rect around.
+            match self.saved_position() {
+                Ok(position) => {
+                    messaging::publish("example_mod/teleported", &position);
+                }
+                Err(error) => log(error.message()),
             }
-            *since_last_ghost = 0;
-        } else {
-            *since_last_ghost += 1;
+
+            // Panned to where the rect landed.
+            let pan = (mouse.x / window_size.x) * 2.0 - 1.0;
+            if let Some(blip) = &self.blip {
+                if let Err(error) = blip.play().volume(0.3).pan(pan).start() {
+                    log(error.message());
+                }
+            }
+        }
+
+        let distance = SPEED * delta.as_secs_f32();
+        self.position.x += if self.reverse.0 { -distance } else { distance };
+        self.position.y += if self.reverse.1 { -distance } else { distance };
+
+        if self.position.x > window_size.x - SIZE.x {
+            self.reverse.0 = true;
+            self.position.x = window_size.x - SIZE.x;
+        } else if self.position.x <= 0.0 {
+            self.reverse.0 = false;
+            self.position.x = 0.0;
+        }
+
+        if self.position.y > window_size.y - SIZE.y {
+            self.reverse.1 = true;
+            self.position.y = window_size.y - SIZE.y;
+        } else if self.position.y <= 0.0 {
+            self.reverse.1 = false;
+            self.position.y = 0.0;
         }
     }
 
-    fn draw(&self) {
-        let position = self.position.borrow();
-        let ghosts = self.ghosts.borrow();
-        let since_last_ghost = *self.since_last_ghost.borrow();
-        let ghost_interval = self.ghost_interval;
-        let ghost_amount = self.ghost_amount;
-        let size = self.size;
-        let window_size = get_window_size();
-
-        color(
-            position.0 / window_size.0,
-            position.1 / window_size.1,
-            1.0 - position.0 / window_size.0,
-            1.0,
-        );
-        draw_rect(position.0, position.1, size.0, size.1);
-
-        let ghosts_len = ghosts.len() as f32;
-        for (i, ghost) in ghosts.iter().enumerate() {
-            color(
-                ghost.0 / window_size.0,
-                ghost.1 / window_size.1,
-                1.0 - ghost.0 / window_size.0,
-                ((1.0 / ghosts_len) * i as f32)
-                    - ((since_last_ghost as f32) / ghost_interval as f32 / ghost_amount as f32),
-            );
-            draw_rect(ghost.0, ghost.1, size.0, size.1);
+    fn draw(&mut self) {
+        let position = self.previous.lerp(self.position, time::alpha());
+        let since_last_ghost = time::game_time().saturating_sub(self.last_ghost);
+        let window_size = input::window_size();
+        let tint = |at: Vec2, alpha: f32| {
+            Color::rgba(
+                at.x / window_size.x,
+                at.y / window_size.y,
+                1.0 - at.x / window_size.x,
+                alpha,
+            )
+        };
+
+        graphics::rect(Rect::new(position, SIZE))
+            .color(tint(position, 1.0))
+            .draw();
+
+        let ghosts_len = self.ghosts.len() as f32;
+        for (i, ghost) in self.ghosts.iter().enumerate() {
+            let alpha = ((1.0 / ghosts_len) * i as f32)
+                - (since_last_ghost.as_secs_f32() * 1000.0
+                    / self.ghost_interval as f32
+                    / self.ghost_amount as f32);
+            graphics::rect(Rect::new(*ghost, SIZE))
+                .color(tint(*ghost, alpha))
+                .draw();
         }
     }
 
-    fn shutdown(&self) {}
+    fn on_event(&mut self, event: &Event) -> bool {
+        match event {
+            Event::ModLoaded(mod_id) => log(&format!("Mod loaded: {}", mod_id)),
+            Event::SettingsChanged(_) => {
+                self.read_settings();
+                self.start_ghost_timer();
+            }
+            Event::Timer(id) if self.ghost_timer.is_some_and(|timer| timer.is(*id)) => {
+                self.add_ghost()
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn handle_call(&mut self, service: &str, method: &str, _: &[u8]) -> Result<Vec<u8>> {
+        match (service, method) {
+            ("example_mod/position", "get") => self.saved_position(),
+            _ => Err(Error::new(format!(
+                "Unknown method {} of {}",
+                method, service
+            ))),
+        }
+    }
+
+    fn run_system(&mut self, name: &str) {
+        if name != "marker" {
+            return;
+        }
+        let Some(marker) = self.marker else {
+            return;
+        };
+        let center = Rect::new(self.position, SIZE).center();
+        let center = Position {
+            x: center.x,
+            y: center.y,
+            z: 1,
+        };
+        let set = encode(&center).and_then(|center| marker.set_component("Position", &center));
+        if let Err(error) = set {
+            log(error.message());
+        }
+    }
+
+    fn save_section(&mut self) -> Option<Vec<u8>> {
+        self.saved_position()
+            .inspect_err(|error| log(error.message()))
+            .ok()
+    }
+
+    fn load_section(&mut self, data: &[u8]) {
+        self.restore(data);
+        // The slot brought back the timers from when it was saved, the ghost
+        // timer id isn't part of the section.
+        self.start_ghost_timer();
+    }
+
+    // The section has been a `Position` since the first version, so the
+    // default `migrate_section` keeping it as it is does.
+
+    fn shutdown(&mut self) {
+        let saved = self
+            .saved_position()
+            .and_then(|position| storage::save("position", &position));
+        if let Err(error) = saved {
+            log(error.message());
+        }
+    }
+}
+
+impl ExampleMod {
+    fn saved_position(&self) -> Result<Vec<u8>> {
+        encode(&Position {
+            x: self.position.x,
+            y: self.position.y,
+            z: 0,
+        })
+    }
+
+    /// Ghost settings, declared in `mod.toml`.
+    fn read_settings(&mut self) {
+        if let Some(amount) = settings::get_integer("ghost_amount") {
+            self.ghost_amount = amount as u32;
+        }
+        if let Some(interval) = settings::get_integer("ghost_interval") {
+            self.ghost_interval = interval as u32;
+        }
+    }
+
+    /// (Re)starts the timer that leaves a ghost behind every
+    /// `ghost_interval` milliseconds.
+    fn start_ghost_timer(&mut self) {
+        if let Some(timer) = self.ghost_timer.take() {
+            timer.cancel();
+        }
+        self.ghost_timer = Some(Timer::interval(Duration::from_millis(
+            self.ghost_interval as u64,
+        )));
+    }
+
+    fn add_ghost(&mut self) {
+        self.ghosts.push(self.position);
+        if self.ghosts.len() > self.ghost_amount as usize {
+            self.ghosts.remove(0);
+        }
+        self.last_ghost = time::game_time();
+    }
+
+    fn restore(&mut self, data: &[u8]) {
+        let saved = match Position::de(data) {
+            Ok(saved) => saved,
+            Err(error) => {
+                log(&format!("Ignoring saved position: {}", error));
+                return;
+            }
+        };
+        self.position = Vec2::new(saved.x, saved.y);
+        self.previous = self.position;
+    }
+}
+
+fn encode(value: &impl SerdeType) -> Result<Vec<u8>> {
+    value.se().map_err(|error| Error::new(error.to_string()))
 }
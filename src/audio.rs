@@ -0,0 +1,42 @@
+use anyhow::Result;
+use mod_manager::audio::{AudioBackend, SAMPLE_RATE};
+use sdl2::{
+    audio::{AudioQueue, AudioSpecDesired},
+    AudioSubsystem,
+};
+use utils::logging::*;
+
+/// Audio kept queued ahead of the device, in frames. More survives longer
+/// frame hitches, less makes sounds start sooner.
+const LATENCY_FRAMES: usize = SAMPLE_RATE as usize / 15;
+
+/// Audio backend on top of an SDL audio queue. SDL converts to whatever the
+/// device wants.
+pub struct SdlAudio {
+    queue: AudioQueue<f32>,
+}
+
+impl SdlAudio {
+    pub fn new(subsystem: &AudioSubsystem) -> Result<Self> {
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE as i32),
+            channels: Some(2),
+            samples: Some(1024),
+        };
+        let queue = subsystem.open_queue::<f32, _>(None, &desired).anyhow()?;
+        queue.resume();
+        Ok(Self { queue })
+    }
+}
+
+impl AudioBackend for SdlAudio {
+    fn wanted_frames(&mut self) -> usize {
+        // Two f32 samples per frame.
+        let queued = self.queue.size() as usize / 8;
+        LATENCY_FRAMES.saturating_sub(queued)
+    }
+
+    fn queue(&mut self, samples: &[f32]) -> Result<()> {
+        self.queue.queue_audio(samples).anyhow()
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::Result;
+use mod_manager::gamepad::{
+    GamepadAxis, GamepadBackend, GamepadButton, GamepadEvent, RumbleRequest,
+};
+use sdl2::{
+    controller::{Axis, Button, GameController},
+    event::Event,
+    GameControllerSubsystem,
+};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+/// Gamepad backend on top of SDL's GameController subsystem. SDL delivers
+/// controller events through the shared event pump, so they are handed in
+/// with `handle_event` and buffered until the next poll.
+pub struct SdlGamepads {
+    subsystem: GameControllerSubsystem,
+    /// Open controllers by joystick instance id.
+    controllers: HashMap<u32, GameController>,
+    events: Vec<GamepadEvent>,
+}
+
+impl SdlGamepads {
+    pub fn new(subsystem: GameControllerSubsystem) -> Self {
+        Self {
+            subsystem,
+            controllers: HashMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::ControllerDeviceAdded { which, .. } => match self.subsystem.open(*which) {
+                Ok(controller) => {
+                    let id = controller.instance_id();
+                    let name = controller.name();
+                    info!("Gamepad {} connected: {}", id, name);
+                    self.controllers.insert(id, controller);
+                    self.events.push(GamepadEvent::Connected { id, name });
+                }
+                Err(e) => warn!("Failed to open gamepad {}: {}", which, e),
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                // Controllers that failed to open were never reported.
+                if self.controllers.remove(which).is_none() {
+                    return;
+                }
+                info!("Gamepad {} disconnected", which);
+                self.events.push(GamepadEvent::Disconnected { id: *which });
+            }
+            Event::ControllerButtonDown { which, button, .. } => {
+                if let Some(button) = gamepad_button(*button) {
+                    self.events
+                        .push(GamepadEvent::ButtonDown { id: *which, button });
+                }
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                if let Some(button) = gamepad_button(*button) {
+                    self.events
+                        .push(GamepadEvent::ButtonUp { id: *which, button });
+                }
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                let axis = gamepad_axis(*axis);
+                self.events.push(GamepadEvent::AxisMoved {
+                    id: *which,
+                    axis,
+                    value: (*value as f32 / i16::MAX as f32).max(-1.0),
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+impl GamepadBackend for SdlGamepads {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn rumble(&mut self, request: &RumbleRequest) -> Result<()> {
+        let Some(controller) = self.controllers.get_mut(&request.id) else {
+            return Ok(());
+        };
+        controller.set_rumble(
+            (request.low_frequency * u16::MAX as f32) as u16,
+            (request.high_frequency * u16::MAX as f32) as u16,
+            request.duration_ms,
+        )?;
+        Ok(())
+    }
+}
+
+fn gamepad_button(button: Button) -> Option<GamepadButton> {
+    match button {
+        Button::A => Some(GamepadButton::A),
+        Button::B => Some(GamepadButton::B),
+        Button::X => Some(GamepadButton::X),
+        Button::Y => Some(GamepadButton::Y),
+        Button::Back => Some(GamepadButton::Back),
+        Button::Guide => Some(GamepadButton::Guide),
+        Button::Start => Some(GamepadButton::Start),
+        Button::LeftStick => Some(GamepadButton::LeftStick),
+        Button::RightStick => Some(GamepadButton::RightStick),
+        Button::LeftShoulder => Some(GamepadButton::LeftShoulder),
+        Button::RightShoulder => Some(GamepadButton::RightShoulder),
+        Button::DPadUp => Some(GamepadButton::DpadUp),
+        Button::DPadDown => Some(GamepadButton::DpadDown),
+        Button::DPadLeft => Some(GamepadButton::DpadLeft),
+        Button::DPadRight => Some(GamepadButton::DpadRight),
+        _ => None,
+    }
+}
+
+fn gamepad_axis(axis: Axis) -> GamepadAxis {
+    match axis {
+        Axis::LeftX => GamepadAxis::LeftX,
+        Axis::LeftY => GamepadAxis::LeftY,
+        Axis::RightX => GamepadAxis::RightX,
+        Axis::RightY => GamepadAxis::RightY,
+        Axis::TriggerLeft => GamepadAxis::LeftTrigger,
+        Axis::TriggerRight => GamepadAxis::RightTrigger,
+    }
+}
@@ -1,12 +1,48 @@
+mod audio;
+mod gamepad;
+mod renderer;
+
 use anyhow::{Error, Result};
-use mod_manager::{ModContext, ModManager};
-use sdl2::{event::Event, keyboard::Keycode, pixels::Color, render::BlendMode};
-use std::time::Duration;
-use tracing::info;
+use audio::SdlAudio;
+use gamepad::SdlGamepads;
+use mod_manager::{
+    audio::{AudioBackend, NullAudio, SAMPLE_RATE},
+    input::{InputEvent, MouseButton},
+    render::{self, Renderer},
+    replay::Recording,
+    ModContext, ModManager,
+};
+use renderer::SdlRenderer;
+use sdl2::{
+    event::{Event, WindowEvent},
+    keyboard::Keycode,
+    mouse::{MouseButton as SdlMouseButton, MouseWheelDirection},
+    pixels::Color,
+    render::BlendMode,
+};
+use std::{path::Path, time::Instant};
+use tracing::{info, warn};
 use utils::logging::*;
 
+/// Command line: `--record <file>` records the run, with `--hash-draws`
+/// including a hash of every frame's draw list; `--replay <file>` replays a
+/// recording without a window and fails if it diverged.
 fn main() -> Result<(), Error> {
     tracing_subscriber::fmt::init();
+    let args: Vec<String> = std::env::args().collect();
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|index| args.get(index + 1))
+    };
+    let context = ModContext {
+        game_version: "1.0".to_string(),
+        api_version: "1.0".to_string(),
+    };
+    if let Some(path) = arg_value("--replay") {
+        return replay(Path::new(path), context);
+    }
+
     let sdl_context = sdl2::init().anyhow()?;
     let video_subsystem = sdl_context.video().anyhow()?;
 
@@ -21,6 +57,7 @@ fn main() -> Result<(), Error> {
 
     let mut canvas = window
         .into_canvas()
+        .present_vsync()
         .build()
         .map_err(|e| e.to_string())
         .anyhow()?;
@@ -29,20 +66,39 @@ fn main() -> Result<(), Error> {
     canvas.set_draw_color(Color::RGB(0, 0, 0));
     canvas.clear();
     canvas.present();
+    let mut renderer = SdlRenderer::new(canvas)?;
     let mut event_pump = sdl_context.event_pump().anyhow()?;
-
-    let context = ModContext {
-        game_version: "1.0".to_string(),
-        api_version: "1.0".to_string(),
+    let mut gamepads = SdlGamepads::new(sdl_context.game_controller().anyhow()?);
+    // The game runs without sound if there is no audio device.
+    let mut audio: Box<dyn AudioBackend> = match sdl_context
+        .audio()
+        .and_then(|audio| SdlAudio::new(&audio).map_err(|e| e.to_string()))
+    {
+        Ok(audio) => Box::new(audio),
+        Err(e) => {
+            warn!("Failed to open audio, continuing without sound: {}", e);
+            Box::new(NullAudio::new(SAMPLE_RATE as usize / 120))
+        }
     };
+
     let mut manager = ModManager::new("wasm", context)?;
+    manager.set_window_size(renderer.viewport());
     manager.load_all_mods()?;
+    if let Some(path) = arg_value("--record") {
+        let hash_draws = args.iter().any(|arg| arg == "--hash-draws");
+        manager.start_recording(Path::new(path), hash_draws)?;
+    }
 
     let init_instant = std::time::Instant::now();
     manager.call_init()?;
     info!("Initialized in {}us", init_instant.elapsed().as_micros());
 
+    let mut last_frame = Instant::now();
     'running: loop {
+        let frame_start = Instant::now();
+        let real_delta = frame_start - last_frame;
+        last_frame = frame_start;
+
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. }
@@ -50,43 +106,50 @@ fn main() -> Result<(), Error> {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => break 'running,
-                _ => {}
+                event => {
+                    gamepads.handle_event(&event);
+                    if let Some(input) = input_event(event) {
+                        manager.handle_input(input);
+                    }
+                }
             }
         }
+        manager.pump_gamepads(&mut gamepads);
+        manager.poll_resources();
 
         let update_instant = std::time::Instant::now();
-        manager.update_all_mods(1000.0 / 16.0)?;
-        info!("Updated in {}us", update_instant.elapsed().as_micros());
+        let frame = manager.run_frame(real_delta)?;
+        info!(
+            "Ran {} steps in {}us",
+            frame.steps,
+            update_instant.elapsed().as_micros()
+        );
 
         let draw_instant = std::time::Instant::now();
         manager.call_draw()?;
         info!("Drawn in {}us", draw_instant.elapsed().as_micros());
 
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
+        renderer.clear((0, 0, 0, 255))?;
 
         {
             let storages_ref = manager.storages();
-            let mut storages = storages_ref.lock().unwrap();
-            let textures = &mut storages.textures;
-            for ((x, y, w, h), (r, g, b, a)) in textures.iter() {
-                canvas.set_draw_color(Color::RGBA(*r, *g, *b, *a));
-                canvas
-                    .fill_rect(sdl2::rect::Rect::new(*x as i32, *y as i32, *w, *h))
-                    .anyhow()?;
-            }
+            let mut storages_guard = storages_ref.lock().unwrap();
+            let storages = &mut *storages_guard;
+            render::render(
+                &mut renderer,
+                &storages.draw_list,
+                &mut storages.render_targets,
+            )?;
         }
         {
-            let window = canvas.window_mut();
-            let window_size = window.size();
-
             let storages_ref = manager.storages();
             let mut storages = storages_ref.lock().unwrap();
-            storages.clear(window_size);
+            mod_manager::audio::pump(audio.as_mut(), &mut storages.audio)?;
         }
+        manager.end_frame(renderer.viewport());
 
-        canvas.present();
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 120));
+        // Paced by vsync.
+        renderer.present();
     }
 
     let unload_instant = std::time::Instant::now();
@@ -95,3 +158,96 @@ fn main() -> Result<(), Error> {
 
     Ok(())
 }
+
+fn replay(path: &Path, context: ModContext) -> Result<(), Error> {
+    let recording = Recording::load(path)?;
+    let mut manager = ModManager::new("wasm", context)?;
+    manager.load_all_mods()?;
+
+    let report = manager.replay(&recording)?;
+    manager.unload_all_mods()?;
+    info!("Replayed {} frames", report.frames);
+    match report.divergences.first() {
+        Some(first) => Err(Error::msg(format!(
+            "Replay diverged in {} frames, first in frame {}",
+            report.divergences.len(),
+            first.frame
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn mouse_button(button: SdlMouseButton) -> Option<MouseButton> {
+    match button {
+        SdlMouseButton::Left => Some(MouseButton::Left),
+        SdlMouseButton::Middle => Some(MouseButton::Middle),
+        SdlMouseButton::Right => Some(MouseButton::Right),
+        SdlMouseButton::X1 => Some(MouseButton::X1),
+        SdlMouseButton::X2 => Some(MouseButton::X2),
+        SdlMouseButton::Unknown => None,
+    }
+}
+
+/// Translates a window event into the input the host hands to mods.
+fn input_event(event: Event) -> Option<InputEvent> {
+    match event {
+        Event::KeyDown {
+            keycode,
+            scancode,
+            repeat: false,
+            ..
+        } => Some(InputEvent::Key {
+            keycode: keycode.map(|keycode| keycode.name()),
+            scancode: scancode.map(|scancode| scancode.name().to_string()),
+            down: true,
+        }),
+        Event::KeyUp {
+            keycode, scancode, ..
+        } => Some(InputEvent::Key {
+            keycode: keycode.map(|keycode| keycode.name()),
+            scancode: scancode.map(|scancode| scancode.name().to_string()),
+            down: false,
+        }),
+        Event::MouseMotion { x, y, .. } => Some(InputEvent::MouseMoved {
+            x: x as f32,
+            y: y as f32,
+        }),
+        Event::MouseButtonDown { mouse_btn, .. } => {
+            mouse_button(mouse_btn).map(|button| InputEvent::MouseButton { button, down: true })
+        }
+        Event::MouseButtonUp { mouse_btn, .. } => {
+            mouse_button(mouse_btn).map(|button| InputEvent::MouseButton {
+                button,
+                down: false,
+            })
+        }
+        Event::MouseWheel {
+            precise_x,
+            precise_y,
+            direction,
+            ..
+        } => {
+            let sign = match direction {
+                MouseWheelDirection::Flipped => -1.0,
+                _ => 1.0,
+            };
+            Some(InputEvent::MouseWheel {
+                x: precise_x * sign,
+                y: precise_y * sign,
+            })
+        }
+        Event::TextInput { text, .. } => Some(InputEvent::Text(text)),
+        Event::Window {
+            win_event: WindowEvent::SizeChanged(width, height),
+            ..
+        } => Some(InputEvent::WindowResized {
+            width: width as u32,
+            height: height as u32,
+        }),
+        Event::Window {
+            win_event: WindowEvent::FocusLost,
+            ..
+        } => Some(InputEvent::FocusLost),
+        _ => None,
+    }
+}
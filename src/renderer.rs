@@ -0,0 +1,203 @@
+use anyhow::{Error, Result};
+use mod_manager::render::{BlendMode as ModBlendMode, Rect, Renderer};
+use sdl2::{
+    pixels::{Color, PixelFormatEnum},
+    rect::FRect,
+    render::{BlendMode, Canvas, Texture},
+    video::Window,
+};
+use std::collections::HashMap;
+use utils::logging::*;
+
+pub struct SdlRenderer {
+    canvas: Canvas<Window>,
+    /// 1x1 white texture, tinted and stretched to draw rotated rects.
+    white: Texture,
+    targets: HashMap<u32, Texture>,
+    current_target: Option<u32>,
+    blend_mode: BlendMode,
+}
+
+impl SdlRenderer {
+    pub fn new(canvas: Canvas<Window>) -> Result<Self> {
+        let mut white = canvas
+            .create_texture_static(PixelFormatEnum::RGBA32, 1, 1)
+            .map_err(|e| e.to_string())
+            .anyhow()?;
+        white
+            .update(None, &[255, 255, 255, 255], 4)
+            .map_err(|e| e.to_string())
+            .anyhow()?;
+        white.set_blend_mode(BlendMode::Blend);
+
+        Ok(Self {
+            canvas,
+            white,
+            targets: HashMap::new(),
+            current_target: None,
+            blend_mode: BlendMode::Blend,
+        })
+    }
+
+    pub fn present(&mut self) {
+        self.canvas.present();
+    }
+}
+
+impl Drop for SdlRenderer {
+    fn drop(&mut self) {
+        // Textures are not freed on drop with `unsafe_textures`, the canvas
+        // is still alive here.
+        for (_, texture) in self.targets.drain() {
+            unsafe { texture.destroy() };
+        }
+    }
+}
+
+impl Renderer for SdlRenderer {
+    fn viewport(&self) -> (u32, u32) {
+        match self
+            .current_target
+            .and_then(|handle| self.targets.get(&handle))
+        {
+            Some(texture) => {
+                let query = texture.query();
+                (query.width, query.height)
+            }
+            None => self.canvas.window().size(),
+        }
+    }
+
+    fn set_blend_mode(&mut self, blend_mode: ModBlendMode) -> Result<()> {
+        let blend_mode = match blend_mode {
+            ModBlendMode::Alpha => BlendMode::Blend,
+            ModBlendMode::Additive => BlendMode::Add,
+            ModBlendMode::Multiply => BlendMode::Mul,
+            ModBlendMode::None => BlendMode::None,
+        };
+        self.canvas.set_blend_mode(blend_mode);
+        self.white.set_blend_mode(blend_mode);
+        self.blend_mode = blend_mode;
+        Ok(())
+    }
+
+    fn clear(&mut self, color: (u8, u8, u8, u8)) -> Result<()> {
+        self.canvas
+            .set_draw_color(Color::RGBA(color.0, color.1, color.2, color.3));
+        self.canvas.clear();
+        Ok(())
+    }
+
+    #[cfg(feature = "float-rects")]
+    fn fill_rect(&mut self, rect: Rect, color: (u8, u8, u8, u8)) -> Result<()> {
+        self.canvas
+            .set_draw_color(Color::RGBA(color.0, color.1, color.2, color.3));
+        self.canvas
+            .fill_frect(FRect::new(rect.x, rect.y, rect.w, rect.h))
+            .anyhow()
+    }
+
+    #[cfg(not(feature = "float-rects"))]
+    fn fill_rect(&mut self, rect: Rect, color: (u8, u8, u8, u8)) -> Result<()> {
+        // Round the edges rather than position and size separately, so
+        // adjacent rects never overlap or leave gaps.
+        let x0 = rect.x.round() as i32;
+        let y0 = rect.y.round() as i32;
+        let x1 = (rect.x + rect.w).round() as i32;
+        let y1 = (rect.y + rect.h).round() as i32;
+        if x1 <= x0 || y1 <= y0 {
+            return Ok(());
+        }
+
+        self.canvas
+            .set_draw_color(Color::RGBA(color.0, color.1, color.2, color.3));
+        self.canvas
+            .fill_rect(sdl2::rect::Rect::new(
+                x0,
+                y0,
+                (x1 - x0) as u32,
+                (y1 - y0) as u32,
+            ))
+            .anyhow()
+    }
+
+    fn fill_rotated_rect(
+        &mut self,
+        rect: Rect,
+        rotation: f32,
+        color: (u8, u8, u8, u8),
+    ) -> Result<()> {
+        self.white.set_color_mod(color.0, color.1, color.2);
+        self.white.set_alpha_mod(color.3);
+        self.canvas
+            .copy_ex_f(
+                &self.white,
+                None,
+                FRect::new(rect.x, rect.y, rect.w, rect.h),
+                rotation.to_degrees() as f64,
+                None,
+                false,
+                false,
+            )
+            .anyhow()
+    }
+
+    fn create_target(&mut self, handle: u32, width: u32, height: u32) -> Result<()> {
+        let mut texture = self
+            .canvas
+            .create_texture_target(PixelFormatEnum::RGBA8888, width, height)
+            .map_err(|e| e.to_string())
+            .anyhow()?;
+        texture.set_blend_mode(BlendMode::Blend);
+        self.targets.insert(handle, texture);
+        Ok(())
+    }
+
+    fn destroy_target(&mut self, handle: u32) -> Result<()> {
+        if self.current_target == Some(handle) {
+            self.set_target(None)?;
+        }
+        if let Some(texture) = self.targets.remove(&handle) {
+            // Textures are not freed on drop with `unsafe_textures`. The
+            // canvas outlives every target, so destroying here is sound.
+            unsafe { texture.destroy() };
+        }
+        Ok(())
+    }
+
+    fn set_target(&mut self, target: Option<u32>) -> Result<()> {
+        let raw_texture = match target {
+            Some(handle) => self
+                .targets
+                .get(&handle)
+                .ok_or_else(|| Error::msg(format!("Unknown render target {}", handle)))?
+                .raw(),
+            None => std::ptr::null_mut(),
+        };
+        let result = unsafe { sdl2::sys::SDL_SetRenderTarget(self.canvas.raw(), raw_texture) };
+        if result != 0 {
+            return Err(Error::msg(sdl2::get_error()));
+        }
+        self.current_target = target;
+        Ok(())
+    }
+
+    fn draw_target(&mut self, handle: u32, rect: Rect, rotation: f32) -> Result<()> {
+        let texture = self
+            .targets
+            .get_mut(&handle)
+            .ok_or_else(|| Error::msg(format!("Unknown render target {}", handle)))?;
+        texture.set_blend_mode(self.blend_mode);
+        self.canvas
+            .copy_ex_f(
+                texture,
+                None,
+                FRect::new(rect.x, rect.y, rect.w, rect.h),
+                rotation.to_degrees() as f64,
+                None,
+                false,
+                false,
+            )
+            .anyhow()
+    }
+}